@@ -0,0 +1,376 @@
+use crate::io::{inw, io_wait_n, outb, outw};
+use crate::memory::{align_down, paging, PAGE_SIZE};
+use core::ptr::{addr_of, addr_of_mut};
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+const RSDP_V1_LEN: usize = 20;
+const RSDP_V2_LEN: usize = 36;
+const SDT_HEADER_LEN: usize = 36;
+
+const EBDA_POINTER: usize = 0x40E;
+const EBDA_SEARCH_LEN: usize = 1024;
+const BIOS_AREA_START: usize = 0xE0000;
+const BIOS_AREA_END: usize = 0x100000;
+
+const FADT_DSDT: usize = 40;
+const FADT_SMI_COMMAND: usize = 48;
+const FADT_ACPI_ENABLE: usize = 52;
+const FADT_PM1A_CONTROL: usize = 64;
+const FADT_PM1B_CONTROL: usize = 68;
+const FADT_MIN_LEN: usize = 72;
+
+const MAX_TABLE_LEN: usize = 0x100000; // 1MB
+const MAX_TABLES: usize = 32;
+
+const AML_NAME_OP: u8 = 0x08;
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+const AML_ROOT_PREFIX: u8 = b'\\';
+
+const SCI_ENABLED: u16 = 1 << 0;
+const SLP_TYP_SHIFT: u16 = 10;
+const SLP_EN: u16 = 1 << 13;
+const ENABLE_ATTEMPTS: u32 = 300;
+const ENABLE_WAIT_IO: u32 = 10_000; // ~10ms
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    RsdpNotFound,
+    BadRootTable,
+    FadtNotFound,
+    BadDsdt,
+    S5NotFound,
+}
+
+impl AcpiError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            AcpiError::RsdpNotFound => "no RSDP found",
+            AcpiError::BadRootTable => "RSDT/XSDT is missing or corrupt",
+            AcpiError::FadtNotFound => "no usable FADT",
+            AcpiError::BadDsdt => "DSDT is missing or corrupt",
+            AcpiError::S5NotFound => "no _S5 sleep package in the DSDT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rsdp {
+    pub revision: u8,
+    pub rsdt: u32,
+    pub xsdt: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableInfo {
+    pub signature: [u8; 4],
+    pub address: usize,
+    pub length: usize,
+}
+
+impl TableInfo {
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.signature).unwrap_or("????")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerInfo {
+    pub smi_command: u32,
+    pub acpi_enable: u8,
+    pub pm1a_control: u16,
+    pub pm1b_control: u16,
+    pub slp_typ_a: u8,
+    pub slp_typ_b: u8,
+}
+
+struct Acpi {
+    initialized: bool,
+    result: Result<(), AcpiError>,
+    rsdp: Option<(usize, Rsdp)>,
+    tables: [TableInfo; MAX_TABLES],
+    table_count: usize,
+    power: Option<PowerInfo>,
+}
+
+static mut ACPI: Acpi = Acpi {
+    initialized: false,
+    result: Ok(()),
+    rsdp: None,
+    tables: [TableInfo {
+        signature: [0; 4],
+        address: 0,
+        length: 0,
+    }; MAX_TABLES],
+    table_count: 0,
+    power: None,
+};
+
+fn acpi() -> &'static mut Acpi {
+    unsafe { &mut *addr_of_mut!(ACPI) }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    read_u32(data, offset) as u64 | (read_u32(data, offset + 4) as u64) << 32
+}
+
+pub fn checksum_ok(data: &[u8]) -> bool {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+pub fn parse_rsdp(data: &[u8]) -> Option<Rsdp> {
+    if data.len() < RSDP_V1_LEN || &data[..8] != RSDP_SIGNATURE {
+        return None;
+    }
+    if !checksum_ok(&data[..RSDP_V1_LEN]) {
+        return None;
+    }
+
+    let revision = data[15];
+    let rsdt = read_u32(data, 16);
+    if revision < 2 {
+        return Some(Rsdp {
+            revision,
+            rsdt,
+            xsdt: 0,
+        });
+    }
+
+    if data.len() < RSDP_V2_LEN {
+        return None;
+    }
+    let length = read_u32(data, 20) as usize;
+    if length < RSDP_V2_LEN || length > data.len() || !checksum_ok(&data[..length]) {
+        return None;
+    }
+    Some(Rsdp {
+        revision,
+        rsdt,
+        xsdt: read_u64(data, 24),
+    })
+}
+
+pub fn parse_s5(dsdt: &[u8]) -> Option<(u8, u8)> {
+    let position = dsdt.windows(4).position(|window| window == b"_S5_")?;
+
+    let named = (position >= 1 && dsdt[position - 1] == AML_NAME_OP)
+        || (position >= 2
+            && dsdt[position - 1] == AML_ROOT_PREFIX
+            && dsdt[position - 2] == AML_NAME_OP);
+    if !named || dsdt.get(position + 4) != Some(&AML_PACKAGE_OP) {
+        return None;
+    }
+
+    let lead = *dsdt.get(position + 5)?;
+    let mut offset = position + 6 + (lead >> 6) as usize + 1;
+
+    let mut values = [0u8; 2];
+    for value in values.iter_mut() {
+        if *dsdt.get(offset)? == AML_BYTE_PREFIX {
+            offset += 1;
+        }
+        *value = *dsdt.get(offset)?;
+        offset += 1;
+    }
+    Some((values[0], values[1]))
+}
+
+pub fn parse_fadt(fadt: &[u8]) -> Option<(usize, PowerInfo)> {
+    if fadt.len() < FADT_MIN_LEN {
+        return None;
+    }
+    let power = PowerInfo {
+        smi_command: read_u32(fadt, FADT_SMI_COMMAND),
+        acpi_enable: fadt[FADT_ACPI_ENABLE],
+        pm1a_control: read_u32(fadt, FADT_PM1A_CONTROL) as u16,
+        pm1b_control: read_u32(fadt, FADT_PM1B_CONTROL) as u16,
+        slp_typ_a: 0,
+        slp_typ_b: 0,
+    };
+    if power.pm1a_control == 0 {
+        return None;
+    }
+    Some((read_u32(fadt, FADT_DSDT) as usize, power))
+}
+
+fn map_physical(address: usize, len: usize) -> bool {
+    let mut page = align_down(address, PAGE_SIZE);
+    let end = match address.checked_add(len) {
+        Some(end) => end,
+        None => return false,
+    };
+    while page < end {
+        if !paging::is_mapped(page) && !paging::map_page(page, page, paging::KERNEL_PAGE_FLAGS) {
+            return false;
+        }
+        page += PAGE_SIZE;
+    }
+    true
+}
+
+fn physical_slice(address: usize, len: usize) -> Option<&'static [u8]> {
+    if address == 0 || !map_physical(address, len) {
+        return None;
+    }
+    Some(unsafe { core::slice::from_raw_parts(address as *const u8, len) })
+}
+
+fn table_at(address: usize) -> Option<&'static [u8]> {
+    let header = physical_slice(address, SDT_HEADER_LEN)?;
+    let length = read_u32(header, 4) as usize;
+    if !(SDT_HEADER_LEN..=MAX_TABLE_LEN).contains(&length) {
+        return None;
+    }
+    let table = physical_slice(address, length)?;
+    checksum_ok(table).then_some(table)
+}
+
+fn search_rsdp(start: usize, end: usize) -> Option<(usize, Rsdp)> {
+    let area = physical_slice(start, end - start)?;
+    (0..area.len().saturating_sub(RSDP_V1_LEN))
+        .step_by(16)
+        .find_map(|offset| parse_rsdp(&area[offset..]).map(|rsdp| (start + offset, rsdp)))
+}
+
+fn find_rsdp() -> Option<(usize, Rsdp)> {
+    let ebda = physical_slice(EBDA_POINTER, 2).map(|bytes| (read_u16(bytes, 0) as usize) << 4);
+    if let Some(ebda) = ebda.filter(|&ebda| ebda != 0 && ebda < BIOS_AREA_START) {
+        if let Some(found) = search_rsdp(ebda, ebda + EBDA_SEARCH_LEN) {
+            return Some(found);
+        }
+    }
+    search_rsdp(BIOS_AREA_START, BIOS_AREA_END)
+}
+
+fn record_table(state: &mut Acpi, address: usize, table: &[u8]) {
+    if state.table_count == MAX_TABLES {
+        return;
+    }
+    let mut signature = [0; 4];
+    signature.copy_from_slice(&table[..4]);
+    state.tables[state.table_count] = TableInfo {
+        signature,
+        address,
+        length: table.len(),
+    };
+    state.table_count += 1;
+}
+
+fn discover(state: &mut Acpi) -> Result<(), AcpiError> {
+    let (rsdp_address, rsdp) = find_rsdp().ok_or(AcpiError::RsdpNotFound)?;
+    state.rsdp = Some((rsdp_address, rsdp));
+
+    let use_xsdt = rsdp.revision >= 2 && rsdp.xsdt != 0 && rsdp.xsdt <= u32::MAX as u64;
+    let (root_address, entry_size) = if use_xsdt {
+        (rsdp.xsdt as usize, 8)
+    } else {
+        (rsdp.rsdt as usize, 4)
+    };
+    let root = table_at(root_address).ok_or(AcpiError::BadRootTable)?;
+    record_table(state, root_address, root);
+
+    let mut fadt = None;
+    for entry in root[SDT_HEADER_LEN..].chunks_exact(entry_size) {
+        let address = if entry_size == 8 {
+            match read_u64(entry, 0) {
+                address if address <= u32::MAX as u64 => address as usize,
+                _ => continue,
+            }
+        } else {
+            read_u32(entry, 0) as usize
+        };
+
+        if let Some(table) = table_at(address) {
+            record_table(state, address, table);
+            if &table[..4] == b"FACP" {
+                fadt = Some(table);
+            }
+        }
+    }
+
+    let (dsdt_address, mut power) = fadt.and_then(parse_fadt).ok_or(AcpiError::FadtNotFound)?;
+    let dsdt = table_at(dsdt_address).ok_or(AcpiError::BadDsdt)?;
+    record_table(state, dsdt_address, dsdt);
+
+    let (a, b) = parse_s5(dsdt).ok_or(AcpiError::S5NotFound)?;
+    power.slp_typ_a = a;
+    power.slp_typ_b = b;
+    state.power = Some(power);
+    Ok(())
+}
+
+pub fn init() -> Result<(), AcpiError> {
+    let state = acpi();
+    if !state.initialized {
+        state.initialized = true;
+        state.result = discover(state);
+    }
+    state.result
+}
+
+pub fn rsdp() -> Option<(usize, Rsdp)> {
+    unsafe { (*addr_of!(ACPI)).rsdp }
+}
+
+pub fn tables() -> &'static [TableInfo] {
+    let state = unsafe { &*addr_of!(ACPI) };
+    &state.tables[..state.table_count]
+}
+
+pub fn power_info() -> Option<PowerInfo> {
+    unsafe { (*addr_of!(ACPI)).power }
+}
+
+fn enable(power: &PowerInfo) -> bool {
+    unsafe {
+        if inw(power.pm1a_control) & SCI_ENABLED != 0 {
+            return true;
+        }
+        if power.smi_command == 0 || power.acpi_enable == 0 {
+            return false;
+        }
+
+        outb(power.smi_command as u16, power.acpi_enable);
+        for _ in 0..ENABLE_ATTEMPTS {
+            if inw(power.pm1a_control) & SCI_ENABLED != 0 {
+                return true;
+            }
+            io_wait_n(ENABLE_WAIT_IO);
+        }
+    }
+    false
+}
+
+pub fn poweroff() -> Result<(), AcpiError> {
+    init()?;
+    let power = power_info().ok_or(AcpiError::S5NotFound)?;
+
+    enable(&power);
+    unsafe {
+        outw(
+            power.pm1a_control,
+            (power.slp_typ_a as u16) << SLP_TYP_SHIFT | SLP_EN,
+        );
+        if power.pm1b_control != 0 {
+            outw(
+                power.pm1b_control,
+                (power.slp_typ_b as u16) << SLP_TYP_SHIFT | SLP_EN,
+            );
+        }
+        io_wait_n(ENABLE_WAIT_IO);
+    }
+    Ok(())
+}
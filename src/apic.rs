@@ -0,0 +1,242 @@
+use crate::memory::paging::{self, PageFlags};
+use crate::sync::without_interrupts;
+use crate::{cpuid, mouse, pic, timer};
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+const IA32_APIC_BASE: u32 = 0x1B;
+const APIC_BASE_ENABLE: u32 = 1 << 11;
+const APIC_BASE_MASK: u32 = 0xFFFF_F000;
+
+const IOAPIC_BASE: u32 = 0xFEC0_0000;
+
+const LAPIC_ID: usize = 0x020;
+const LAPIC_EOI: usize = 0x0B0;
+const LAPIC_SPURIOUS: usize = 0x0F0;
+const LAPIC_LVT_TIMER: usize = 0x320;
+const LAPIC_TIMER_INITIAL: usize = 0x380;
+const LAPIC_TIMER_CURRENT: usize = 0x390;
+const LAPIC_TIMER_DIVIDE: usize = 0x3E0;
+
+const SPURIOUS_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIVIDE_BY_16: u32 = 0x3;
+
+const IOAPIC_REGSEL: usize = 0x00;
+const IOAPIC_WINDOW: usize = 0x10;
+const IOAPIC_VERSION: u32 = 0x01;
+const IOAPIC_REDIRECTION: u32 = 0x10;
+const REDIRECTION_MASKED: u32 = 1 << 16;
+
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+const CALIBRATION_TICKS: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApicError {
+    Unsupported,
+    MappingFailed,
+    TimerNotRunning,
+    CalibrationFailed,
+}
+
+impl ApicError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ApicError::Unsupported => "CPU has no local APIC",
+            ApicError::MappingFailed => "could not map the APIC registers",
+            ApicError::TimerNotRunning => "PIT timer must be running to calibrate",
+            ApicError::CalibrationFailed => "local APIC timer calibration failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptModel {
+    Pic,
+    Apic,
+}
+
+impl InterruptModel {
+    pub fn from_name(name: &str) -> Option<InterruptModel> {
+        match name {
+            "pic" => Some(InterruptModel::Pic),
+            "apic" => Some(InterruptModel::Apic),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InterruptModel::Pic => "pic",
+            InterruptModel::Apic => "apic",
+        }
+    }
+}
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static LAPIC_BASE: AtomicU32 = AtomicU32::new(0);
+static TIMER_COUNT: AtomicU32 = AtomicU32::new(0);
+
+unsafe fn read_msr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack));
+    (high as u64) << 32 | low as u64
+}
+
+unsafe fn write_msr(msr: u32, value: u64) {
+    asm!(
+        "wrmsr",
+        in("ecx") msr,
+        in("eax") value as u32,
+        in("edx") (value >> 32) as u32,
+        options(nomem, nostack)
+    );
+}
+
+fn lapic_read(register: usize) -> u32 {
+    let base = LAPIC_BASE.load(Ordering::SeqCst) as usize;
+    unsafe { core::ptr::read_volatile((base + register) as *const u32) }
+}
+
+fn lapic_write(register: usize, value: u32) {
+    let base = LAPIC_BASE.load(Ordering::SeqCst) as usize;
+    unsafe { core::ptr::write_volatile((base + register) as *mut u32, value) }
+}
+
+fn ioapic_read(register: u32) -> u32 {
+    let base = IOAPIC_BASE as usize;
+    unsafe {
+        core::ptr::write_volatile((base + IOAPIC_REGSEL) as *mut u32, register);
+        core::ptr::read_volatile((base + IOAPIC_WINDOW) as *const u32)
+    }
+}
+
+fn ioapic_write(register: u32, value: u32) {
+    let base = IOAPIC_BASE as usize;
+    unsafe {
+        core::ptr::write_volatile((base + IOAPIC_REGSEL) as *mut u32, register);
+        core::ptr::write_volatile((base + IOAPIC_WINDOW) as *mut u32, value);
+    }
+}
+
+fn map_mmio(address: u32) -> bool {
+    let flags = paging::flags(&[
+        PageFlags::Present,
+        PageFlags::Writable,
+        PageFlags::CacheDisable,
+    ]);
+    paging::map_page(address as usize, address as usize, flags)
+}
+
+fn set_redirection(irq: u8, vector: u8, destination: u32) {
+    let register = IOAPIC_REDIRECTION + irq as u32 * 2;
+    ioapic_write(register + 1, destination << 24);
+    ioapic_write(register, vector as u32);
+}
+
+fn calibrate_timer() -> Result<u32, ApicError> {
+    if !timer::is_initialized() || !crate::idt::interrupts_enabled() {
+        return Err(ApicError::TimerNotRunning);
+    }
+
+    lapic_write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+    lapic_write(LAPIC_LVT_TIMER, LVT_MASKED);
+
+    let start = timer::ticks();
+    while timer::ticks() == start {
+        core::hint::spin_loop();
+    }
+
+    lapic_write(LAPIC_TIMER_INITIAL, u32::MAX);
+    let start = timer::ticks();
+    while timer::ticks().wrapping_sub(start) < CALIBRATION_TICKS {
+        core::hint::spin_loop();
+    }
+    let elapsed = u32::MAX - lapic_read(LAPIC_TIMER_CURRENT);
+    lapic_write(LAPIC_TIMER_INITIAL, 0);
+
+    match elapsed / CALIBRATION_TICKS {
+        0 => Err(ApicError::CalibrationFailed),
+        count => Ok(count),
+    }
+}
+
+pub fn init() -> Result<(), ApicError> {
+    if !cpuid::has_apic() {
+        return Err(ApicError::Unsupported);
+    }
+
+    let msr = unsafe { read_msr(IA32_APIC_BASE) };
+    let base = msr as u32 & APIC_BASE_MASK;
+    if !map_mmio(base) || !map_mmio(IOAPIC_BASE) {
+        return Err(ApicError::MappingFailed);
+    }
+    unsafe {
+        write_msr(IA32_APIC_BASE, msr | APIC_BASE_ENABLE as u64);
+    }
+    LAPIC_BASE.store(base, Ordering::SeqCst);
+    lapic_write(LAPIC_SPURIOUS, SPURIOUS_ENABLE | SPURIOUS_VECTOR as u32);
+
+    let count = calibrate_timer()?;
+    TIMER_COUNT.store(count, Ordering::SeqCst);
+
+    without_interrupts(|| {
+        pic::disable();
+
+        let max_entry = (ioapic_read(IOAPIC_VERSION) >> 16) & 0xFF;
+        for irq in 0..=max_entry {
+            ioapic_write(IOAPIC_REDIRECTION + irq * 2, REDIRECTION_MASKED);
+        }
+        let destination = lapic_read(LAPIC_ID) >> 24;
+        set_redirection(
+            pic::IRQ_KEYBOARD,
+            pic::PIC1_OFFSET + pic::IRQ_KEYBOARD,
+            destination,
+        );
+        if mouse::is_initialized() {
+            set_redirection(
+                pic::IRQ_MOUSE,
+                pic::PIC1_OFFSET + pic::IRQ_MOUSE,
+                destination,
+            );
+        }
+
+        lapic_write(
+            LAPIC_LVT_TIMER,
+            LVT_TIMER_PERIODIC | (pic::PIC1_OFFSET + pic::IRQ_TIMER) as u32,
+        );
+        lapic_write(LAPIC_TIMER_INITIAL, count);
+        ACTIVE.store(true, Ordering::SeqCst);
+    });
+
+    Ok(())
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+pub fn model() -> InterruptModel {
+    if is_active() {
+        InterruptModel::Apic
+    } else {
+        InterruptModel::Pic
+    }
+}
+
+pub fn timer_count() -> u32 {
+    TIMER_COUNT.load(Ordering::SeqCst)
+}
+
+pub fn send_eoi() {
+    lapic_write(LAPIC_EOI, 0);
+}
+
+pub fn end_of_interrupt(irq: u8) {
+    if is_active() {
+        send_eoi();
+    } else {
+        pic::send_eoi(irq);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::block::{BlockDevice, BlockError};
+use crate::io::{inb, inw, outb, outw};
+use crate::sync::without_interrupts;
+use core::ptr::addr_of_mut;
+
+pub const SECTOR_SIZE: usize = 512;
+pub const MAX_LBA28: u64 = 0x0FFF_FFFF;
+pub const MAX_DRIVES: usize = 2;
+const MODEL_LEN: usize = 40;
+
+const PRIMARY_IO: u16 = 0x1F0;
+const PRIMARY_CONTROL: u16 = 0x3F6;
+
+const REG_DATA: u16 = 0;
+const REG_ERROR: u16 = 1;
+const REG_SECTOR_COUNT: u16 = 2;
+const REG_LBA_LOW: u16 = 3;
+const REG_LBA_MID: u16 = 4;
+const REG_LBA_HIGH: u16 = 5;
+const REG_DRIVE: u16 = 6;
+const REG_STATUS: u16 = 7;
+const REG_COMMAND: u16 = 7;
+
+const STATUS_ERR: u8 = 1 << 0;
+const STATUS_DRQ: u8 = 1 << 3;
+const STATUS_DF: u8 = 1 << 5;
+const STATUS_BSY: u8 = 1 << 7;
+
+const CONTROL_NIEN: u8 = 1 << 1;
+
+const DRIVE_MASTER: u8 = 0xA0;
+const DRIVE_LBA: u8 = 0x40;
+const DRIVE_SLAVE_BIT: u8 = 0x10;
+
+const COMMAND_READ_SECTORS: u8 = 0x20;
+const COMMAND_WRITE_SECTORS: u8 = 0x30;
+const COMMAND_CACHE_FLUSH: u8 = 0xE7;
+const COMMAND_IDENTIFY: u8 = 0xEC;
+
+const IDENTIFY_MODEL: usize = 27;
+const IDENTIFY_LBA28_SECTORS: usize = 60;
+
+const POLL_ATTEMPTS: u32 = 1_000_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AtaDrive {
+    io_base: u16,
+    control: u16,
+    slave: bool,
+    sectors: u64,
+    model: [u8; MODEL_LEN],
+}
+
+static mut DRIVES: [Option<AtaDrive>; MAX_DRIVES] = [None; MAX_DRIVES];
+
+fn drives_mut() -> &'static mut [Option<AtaDrive>; MAX_DRIVES] {
+    unsafe { &mut *addr_of_mut!(DRIVES) }
+}
+
+pub fn init() -> usize {
+    unsafe {
+        outb(PRIMARY_CONTROL, CONTROL_NIEN);
+    }
+
+    let drives = drives_mut();
+    for (index, slot) in drives.iter_mut().enumerate() {
+        *slot = AtaDrive::identify(PRIMARY_IO, PRIMARY_CONTROL, index == 1);
+    }
+    drives.iter().flatten().count()
+}
+
+pub fn drive(index: usize) -> Option<&'static AtaDrive> {
+    drives_mut().get(index)?.as_ref()
+}
+
+pub fn drives() -> impl Iterator<Item = (usize, &'static AtaDrive)> {
+    drives_mut()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, drive)| drive.as_ref().map(|drive| (index, drive)))
+}
+
+impl AtaDrive {
+    fn identify(io_base: u16, control: u16, slave: bool) -> Option<AtaDrive> {
+        let mut drive = AtaDrive {
+            io_base,
+            control,
+            slave,
+            sectors: 0,
+            model: [b' '; MODEL_LEN],
+        };
+
+        let mut identify = [0u16; 256];
+        let found = without_interrupts(|| unsafe {
+            drive.select(0);
+            outb(io_base + REG_SECTOR_COUNT, 0);
+            outb(io_base + REG_LBA_LOW, 0);
+            outb(io_base + REG_LBA_MID, 0);
+            outb(io_base + REG_LBA_HIGH, 0);
+            outb(io_base + REG_COMMAND, COMMAND_IDENTIFY);
+
+            let status = inb(io_base + REG_STATUS);
+            if status == 0 || status == 0xFF {
+                return false;
+            }
+            if drive.wait_not_busy().is_err() {
+                return false;
+            }
+            if inb(io_base + REG_LBA_MID) != 0 || inb(io_base + REG_LBA_HIGH) != 0 {
+                return false;
+            }
+            if drive.wait_data_request().is_err() {
+                return false;
+            }
+
+            for word in identify.iter_mut() {
+                *word = inw(io_base + REG_DATA);
+            }
+            true
+        });
+
+        if !found {
+            return None;
+        }
+
+        for (i, word) in identify[IDENTIFY_MODEL..IDENTIFY_MODEL + MODEL_LEN / 2]
+            .iter()
+            .enumerate()
+        {
+            drive.model[i * 2] = (word >> 8) as u8;
+            drive.model[i * 2 + 1] = (word & 0xFF) as u8;
+        }
+        drive.sectors = identify[IDENTIFY_LBA28_SECTORS] as u64
+            | (identify[IDENTIFY_LBA28_SECTORS + 1] as u64) << 16;
+
+        Some(drive)
+    }
+
+    pub fn name(&self) -> &'static str {
+        if self.slave {
+            "hdb"
+        } else {
+            "hda"
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        core::str::from_utf8(&self.model).unwrap_or("").trim()
+    }
+
+    pub fn sectors(&self) -> u64 {
+        self.sectors
+    }
+
+    fn delay_400ns(&self) {
+        for _ in 0..4 {
+            unsafe {
+                inb(self.control);
+            }
+        }
+    }
+
+    fn select(&self, lba_high: u8) {
+        let slave = if self.slave { DRIVE_SLAVE_BIT } else { 0 };
+        unsafe {
+            outb(
+                self.io_base + REG_DRIVE,
+                DRIVE_MASTER | DRIVE_LBA | slave | (lba_high & 0x0F),
+            );
+        }
+        self.delay_400ns();
+    }
+
+    fn wait_not_busy(&self) -> Result<u8, BlockError> {
+        for _ in 0..POLL_ATTEMPTS {
+            let status = unsafe { inb(self.io_base + REG_STATUS) };
+            if status & STATUS_BSY == 0 {
+                return Ok(status);
+            }
+        }
+        Err(BlockError::Timeout)
+    }
+
+    fn wait_data_request(&self) -> Result<(), BlockError> {
+        for _ in 0..POLL_ATTEMPTS {
+            let status = unsafe { inb(self.io_base + REG_STATUS) };
+            if status & STATUS_BSY != 0 {
+                continue;
+            }
+            if status & (STATUS_ERR | STATUS_DF) != 0 {
+                return Err(BlockError::DeviceError(unsafe {
+                    inb(self.io_base + REG_ERROR)
+                }));
+            }
+            if status & STATUS_DRQ != 0 {
+                return Ok(());
+            }
+        }
+        Err(BlockError::Timeout)
+    }
+
+    fn start_transfer(&self, lba: u64, count: u8, command: u8) -> Result<(), BlockError> {
+        if count == 0 || lba + count as u64 - 1 > MAX_LBA28 || lba + count as u64 > self.sectors {
+            return Err(BlockError::OutOfRange);
+        }
+
+        self.wait_not_busy()?;
+        self.select((lba >> 24) as u8);
+        unsafe {
+            outb(self.io_base + REG_SECTOR_COUNT, count);
+            outb(self.io_base + REG_LBA_LOW, lba as u8);
+            outb(self.io_base + REG_LBA_MID, (lba >> 8) as u8);
+            outb(self.io_base + REG_LBA_HIGH, (lba >> 16) as u8);
+            outb(self.io_base + REG_COMMAND, command);
+        }
+        Ok(())
+    }
+
+    pub fn read_sectors(&self, lba: u64, count: u8, buf: &mut [u8]) -> Result<(), BlockError> {
+        if buf.len() < count as usize * SECTOR_SIZE {
+            return Err(BlockError::BufferTooSmall);
+        }
+
+        without_interrupts(|| {
+            self.start_transfer(lba, count, COMMAND_READ_SECTORS)?;
+
+            for sector in buf.chunks_exact_mut(SECTOR_SIZE).take(count as usize) {
+                self.delay_400ns();
+                self.wait_data_request()?;
+                for pair in sector.chunks_exact_mut(2) {
+                    let word = unsafe { inw(self.io_base + REG_DATA) };
+                    pair.copy_from_slice(&word.to_le_bytes());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn write_sectors(&self, lba: u64, count: u8, buf: &[u8]) -> Result<(), BlockError> {
+        if buf.len() < count as usize * SECTOR_SIZE {
+            return Err(BlockError::BufferTooSmall);
+        }
+
+        without_interrupts(|| {
+            self.start_transfer(lba, count, COMMAND_WRITE_SECTORS)?;
+
+            for sector in buf.chunks_exact(SECTOR_SIZE).take(count as usize) {
+                self.delay_400ns();
+                self.wait_data_request()?;
+                for pair in sector.chunks_exact(2) {
+                    unsafe {
+                        outw(
+                            self.io_base + REG_DATA,
+                            u16::from_le_bytes([pair[0], pair[1]]),
+                        );
+                    }
+                }
+            }
+
+            unsafe {
+                outb(self.io_base + REG_COMMAND, COMMAND_CACHE_FLUSH);
+            }
+            self.wait_not_busy()?;
+            Ok(())
+        })
+    }
+}
+
+impl BlockDevice for AtaDrive {
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.sectors
+    }
+
+    fn read_block(&self, block: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.read_sectors(block, 1, buf)
+    }
+
+    fn write_block(&self, block: u64, buf: &[u8]) -> Result<(), BlockError> {
+        self.write_sectors(block, 1, buf)
+    }
+}
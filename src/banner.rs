@@ -0,0 +1,157 @@
+use crate::kfs_lib::{FixedBuffer, HumanSize};
+use crate::printk::{self, println};
+use crate::theme::{self, Role};
+use crate::vga::VGA_BUFFER_WIDTH;
+use crate::{cpuid, initrd, memory};
+use core::fmt::Write;
+
+const DEFAULT_MOTD: &str = "42\nKFS - Kernel From Scratch\ntype 'help' for commands";
+const MOTD_PATH: &str = "/etc/motd";
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const BUILD_DATE: Option<&str> = option_env!("KFS_BUILD_DATE");
+
+const MAX_LINES: usize = 12;
+const LINE_CAPACITY: usize = 128;
+const MIN_WIDTH: usize = 40;
+const PADDING: usize = 2;
+const ELLIPSIS: &str = "...";
+
+const SEPARATOR_LEFT: u8 = 0xC7;
+const SEPARATOR: u8 = 0xC4;
+const SEPARATOR_RIGHT: u8 = 0xB6;
+
+type Line = FixedBuffer<LINE_CAPACITY>;
+
+struct Banner {
+    lines: [Line; MAX_LINES],
+    count: usize,
+    separator: Option<usize>,
+}
+
+impl Banner {
+    const fn new() -> Banner {
+        Banner {
+            lines: [const { FixedBuffer::new() }; MAX_LINES],
+            count: 0,
+            separator: None,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<&mut Line> {
+        let line = self.lines.get_mut(self.count)?;
+        self.count += 1;
+        Some(line)
+    }
+
+    fn push(&mut self, text: &str) {
+        if let Some(line) = self.next_line() {
+            for c in text.chars() {
+                let c = if c.is_ascii() && !c.is_ascii_control() {
+                    c
+                } else {
+                    '?'
+                };
+                if line.write_char(c).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines[..self.count].iter().map(|line| line.as_str())
+    }
+}
+
+pub fn motd() -> &'static str {
+    initrd::fs()
+        .and_then(|fs| fs.open(MOTD_PATH))
+        .and_then(|data| core::str::from_utf8(data).ok())
+        .filter(|text| !text.trim().is_empty())
+        .unwrap_or(DEFAULT_MOTD)
+}
+
+pub fn fit(text: &str, width: usize) -> (&str, bool) {
+    if text.len() <= width {
+        (text, false)
+    } else {
+        (&text[..width.saturating_sub(ELLIPSIS.len())], true)
+    }
+}
+
+fn build() -> Banner {
+    let mut banner = Banner::new();
+    let motd = motd().trim_end();
+    for text in motd.lines().take(MAX_LINES - 4) {
+        banner.push(text.trim_end());
+    }
+    banner.separator = Some(banner.count);
+
+    let mut line = Line::new();
+    let _ = write!(
+        line,
+        "KFS v{} (built {})",
+        VERSION,
+        BUILD_DATE.unwrap_or("unknown")
+    );
+    banner.push(line.as_str());
+
+    line.clear();
+    let _ = write!(
+        line,
+        "RAM: {}",
+        HumanSize(memory::get_stats().total_memory as u64)
+    );
+    banner.push(line.as_str());
+
+    let cpu = cpuid::info();
+    line.clear();
+    let _ = match cpu.brand() {
+        "" => write!(line, "CPU: {}", cpu.vendor()),
+        brand => write!(line, "CPU: {}", brand),
+    };
+    banner.push(line.as_str());
+
+    banner
+}
+
+pub fn show() {
+    let banner = build();
+    let longest = banner.lines().map(str::len).max().unwrap_or(0);
+    let width = (longest + 2 * PADDING + 2).clamp(MIN_WIDTH, VGA_BUFFER_WIDTH);
+    let inner = width - 2 * PADDING - 2;
+    let height = banner.count + 2 + banner.separator.map_or(0, |_| 1);
+
+    for _ in 0..height {
+        println("");
+    }
+    let (_, row) = printk::cursor_position();
+    let top = row.saturating_sub(height);
+    let left = (VGA_BUFFER_WIDTH - width) / 2;
+
+    let (fg, bg) = theme::colors(Role::Info);
+    printk::fill_rect(left, top, width, height, b' ', fg, bg);
+    printk::draw_box(left, top, width, height, fg, bg);
+
+    let mut y = top + 1;
+    for (index, text) in banner.lines().enumerate() {
+        if banner.separator == Some(index) {
+            printk::write_char_at(left, y, SEPARATOR_LEFT, fg, bg);
+            for x in left + 1..left + width - 1 {
+                printk::write_char_at(x, y, SEPARATOR, fg, bg);
+            }
+            printk::write_char_at(left + width - 1, y, SEPARATOR_RIGHT, fg, bg);
+            y += 1;
+        }
+
+        let (text, truncated) = fit(text, inner);
+        let len = text.len() + if truncated { ELLIPSIS.len() } else { 0 };
+        let x = left + (width - len) / 2;
+        printk::write_str_at(x, y, text, fg, bg);
+        if truncated {
+            printk::write_str_at(x + text.len(), y, ELLIPSIS, fg, bg);
+        }
+        y += 1;
+    }
+}
@@ -0,0 +1,86 @@
+use crate::memory::heap;
+
+pub const MEMORY_BLOCK_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    OutOfRange,
+    BufferTooSmall,
+    Timeout,
+    DeviceError(u8),
+}
+
+impl BlockError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            BlockError::OutOfRange => "block number out of range",
+            BlockError::BufferTooSmall => "buffer too small",
+            BlockError::Timeout => "device timed out",
+            BlockError::DeviceError(_) => "device reported an error",
+        }
+    }
+}
+
+pub trait BlockDevice {
+    fn block_size(&self) -> usize;
+
+    fn num_blocks(&self) -> u64;
+
+    fn read_block(&self, block: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+
+    fn write_block(&self, block: u64, buf: &[u8]) -> Result<(), BlockError>;
+}
+
+pub struct MemoryDisk {
+    data: *mut u8,
+    blocks: usize,
+}
+
+impl MemoryDisk {
+    pub fn new(blocks: usize) -> Option<MemoryDisk> {
+        let data = heap::kcalloc(blocks, MEMORY_BLOCK_SIZE)?;
+        Some(MemoryDisk { data, blocks })
+    }
+
+    fn block(&self, block: u64, len: usize) -> Result<*mut u8, BlockError> {
+        if block >= self.blocks as u64 {
+            return Err(BlockError::OutOfRange);
+        }
+        if len < MEMORY_BLOCK_SIZE {
+            return Err(BlockError::BufferTooSmall);
+        }
+        Ok(unsafe { self.data.add(block as usize * MEMORY_BLOCK_SIZE) })
+    }
+}
+
+impl Drop for MemoryDisk {
+    fn drop(&mut self) {
+        heap::kfree(self.data);
+    }
+}
+
+impl BlockDevice for MemoryDisk {
+    fn block_size(&self) -> usize {
+        MEMORY_BLOCK_SIZE
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.blocks as u64
+    }
+
+    fn read_block(&self, block: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let data = self.block(block, buf.len())?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(data, buf.as_mut_ptr(), MEMORY_BLOCK_SIZE);
+        }
+        Ok(())
+    }
+
+    fn write_block(&self, block: u64, buf: &[u8]) -> Result<(), BlockError> {
+        let data = self.block(block, buf.len())?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(buf.as_ptr(), data, MEMORY_BLOCK_SIZE);
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 MBOOT_MAGIC         equ 0x1BADB002
 MBOOT_PAGE_ALIGN    equ 1 << 0
 MBOOT_MEM_INFO      equ 1 << 1
-MBOOT_FLAGS         equ MBOOT_PAGE_ALIGN | MBOOT_MEM_INFO
+MBOOT_VIDEO_MODE    equ 1 << 2
+MBOOT_FLAGS         equ MBOOT_PAGE_ALIGN | MBOOT_MEM_INFO | MBOOT_VIDEO_MODE
 MBOOT_CHECKSUM      equ -(MBOOT_MAGIC + MBOOT_FLAGS)
+MBOOT_MODE_TEXT     equ 1
 STACK_SIZE          equ 0x4000
 
 global _start
@@ -15,9 +17,16 @@ align 4
     dd MBOOT_MAGIC
     dd MBOOT_FLAGS
     dd MBOOT_CHECKSUM
+    dd 0, 0, 0, 0, 0
+    dd MBOOT_MODE_TEXT
+    dd 80
+    dd 25
+    dd 0
 
-section .bss
-align 16
+section .bss align=4096
+alignb 4096
+stack_guard_page:
+    resb 4096
 stack_bottom:
     resb STACK_SIZE
 stack_top:
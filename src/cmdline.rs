@@ -0,0 +1,50 @@
+use core::ptr::{addr_of, addr_of_mut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+const MULTIBOOT_FLAG_CMDLINE: u32 = 1 << 2;
+const MAX_CMDLINE: usize = 256;
+
+static mut CMDLINE: [u8; MAX_CMDLINE] = [0; MAX_CMDLINE];
+static CMDLINE_LEN: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init(multiboot_info: u32) {
+    if multiboot_info == 0 {
+        return;
+    }
+
+    unsafe {
+        let info = multiboot_info as *const u32;
+        if *info & MULTIBOOT_FLAG_CMDLINE == 0 {
+            return;
+        }
+
+        let source = *info.add(4) as *const u8;
+        if source.is_null() {
+            return;
+        }
+
+        let buffer = &mut *addr_of_mut!(CMDLINE);
+        let mut len = 0;
+        while len < MAX_CMDLINE {
+            let byte = *source.add(len);
+            if byte == 0 {
+                break;
+            }
+            buffer[len] = byte;
+            len += 1;
+        }
+        CMDLINE_LEN.store(len, Ordering::SeqCst);
+    }
+}
+
+pub fn as_str() -> &'static str {
+    let buffer = unsafe { &*addr_of!(CMDLINE) };
+    core::str::from_utf8(&buffer[..CMDLINE_LEN.load(Ordering::SeqCst)]).unwrap_or("")
+}
+
+pub fn get(key: &str) -> Option<&'static str> {
+    as_str().split_whitespace().find_map(|option| {
+        let (name, value) = option.split_once('=').unwrap_or((option, ""));
+        (name == key).then_some(value)
+    })
+}
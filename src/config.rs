@@ -0,0 +1,97 @@
+use crate::printk::{self, LogLevel};
+use crate::rtc;
+use crate::theme::{self, Role};
+use crate::vga::Color;
+
+const REG_MAGIC: u8 = 0x40;
+const REG_LOG_LEVEL: u8 = 0x41;
+const REG_COLORS: u8 = 0x42;
+const REG_CHECKSUM: u8 = 0x43;
+
+const CONFIG_MAGIC: u8 = 0x4B;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub log_level: LogLevel,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Config {
+    pub fn current() -> Config {
+        let (foreground, background) = theme::colors(Role::Normal);
+        Config {
+            log_level: printk::level(),
+            foreground,
+            background,
+        }
+    }
+
+    pub fn apply(&self) {
+        printk::set_level(self.log_level);
+        theme::set_colors(Role::Normal, self.foreground, self.background);
+        printk::reset_color();
+    }
+}
+
+fn checksum(magic: u8, log_level: u8, colors: u8) -> u8 {
+    magic ^ log_level ^ colors ^ 0xFF
+}
+
+pub fn decode(bytes: [u8; 4], defaults: Config) -> Config {
+    let [magic, log_level, colors, sum] = bytes;
+    if magic != CONFIG_MAGIC || sum != checksum(magic, log_level, colors) {
+        return defaults;
+    }
+
+    let log_level = printk::LOG_LEVELS
+        .get(log_level as usize)
+        .copied()
+        .unwrap_or(defaults.log_level);
+
+    let foreground = Color::from_index(colors & 0x0F);
+    let background = Color::from_index(colors >> 4);
+    let (foreground, background) = match (foreground, background) {
+        (Some(fg), Some(bg)) if fg != bg => (fg, bg),
+        _ => (defaults.foreground, defaults.background),
+    };
+
+    Config {
+        log_level,
+        foreground,
+        background,
+    }
+}
+
+pub fn encode(config: &Config) -> [u8; 4] {
+    let log_level = config.log_level as u8;
+    let colors = (config.background as u8) << 4 | config.foreground as u8;
+    [
+        CONFIG_MAGIC,
+        log_level,
+        colors,
+        checksum(CONFIG_MAGIC, log_level, colors),
+    ]
+}
+
+pub fn load() -> Config {
+    let bytes = [
+        rtc::read_cmos(REG_MAGIC),
+        rtc::read_cmos(REG_LOG_LEVEL),
+        rtc::read_cmos(REG_COLORS),
+        rtc::read_cmos(REG_CHECKSUM),
+    ];
+    decode(bytes, Config::current())
+}
+
+pub fn init() {
+    load().apply();
+}
+
+pub fn save() {
+    let [magic, log_level, colors, sum] = encode(&Config::current());
+    rtc::write_cmos(REG_LOG_LEVEL, log_level);
+    rtc::write_cmos(REG_COLORS, colors);
+    rtc::write_cmos(REG_CHECKSUM, sum);
+    rtc::write_cmos(REG_MAGIC, magic);
+}
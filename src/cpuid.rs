@@ -0,0 +1,233 @@
+use core::arch::asm;
+use core::arch::x86::{__cpuid, CpuidResult};
+
+const EFLAGS_ID: u32 = 1 << 21;
+
+const LEAF_VENDOR: u32 = 0;
+const LEAF_FEATURES: u32 = 1;
+const LEAF_EXTENDED_MAX: u32 = 0x80000000;
+const LEAF_BRAND_FIRST: u32 = 0x80000002;
+const LEAF_BRAND_LAST: u32 = 0x80000004;
+
+const FEATURE_ECX_MONITOR: u32 = 1 << 3;
+const FEATURE_EDX_TSC: u32 = 1 << 4;
+const FEATURE_EDX_APIC: u32 = 1 << 9;
+const FEATURE_ECX_RDRAND: u32 = 1 << 30;
+
+const VENDOR_LEN: usize = 12;
+const BRAND_LEN: usize = 48;
+
+const EDX_FEATURES: [(u32, &str); 18] = [
+    (0, "fpu"),
+    (4, "tsc"),
+    (5, "msr"),
+    (6, "pae"),
+    (8, "cx8"),
+    (9, "apic"),
+    (11, "sep"),
+    (12, "mtrr"),
+    (13, "pge"),
+    (15, "cmov"),
+    (16, "pat"),
+    (17, "pse36"),
+    (19, "clflush"),
+    (23, "mmx"),
+    (24, "fxsr"),
+    (25, "sse"),
+    (26, "sse2"),
+    (28, "htt"),
+];
+
+const ECX_FEATURES: [(u32, &str); 17] = [
+    (0, "sse3"),
+    (1, "pclmulqdq"),
+    (3, "monitor"),
+    (9, "ssse3"),
+    (12, "fma"),
+    (13, "cx16"),
+    (19, "sse4_1"),
+    (20, "sse4_2"),
+    (21, "x2apic"),
+    (22, "movbe"),
+    (23, "popcnt"),
+    (25, "aes"),
+    (26, "xsave"),
+    (28, "avx"),
+    (29, "f16c"),
+    (30, "rdrand"),
+    (31, "hypervisor"),
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct CpuInfo {
+    vendor: [u8; VENDOR_LEN],
+    brand: [u8; BRAND_LEN],
+    pub max_leaf: u32,
+    pub max_extended_leaf: u32,
+    pub family: u32,
+    pub model: u32,
+    pub stepping: u32,
+    pub features_ecx: u32,
+    pub features_edx: u32,
+}
+
+impl CpuInfo {
+    pub fn vendor(&self) -> &str {
+        text(&self.vendor)
+    }
+
+    pub fn brand(&self) -> &str {
+        text(&self.brand)
+    }
+
+    pub fn features(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let edx = EDX_FEATURES
+            .iter()
+            .filter(|(bit, _)| self.features_edx & (1 << bit) != 0);
+        let ecx = ECX_FEATURES
+            .iter()
+            .filter(|(bit, _)| self.features_ecx & (1 << bit) != 0);
+        edx.chain(ecx).map(|&(_, name)| name)
+    }
+}
+
+fn text(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..len]).unwrap_or("").trim()
+}
+
+pub fn is_supported() -> bool {
+    let original: u32;
+    let toggled: u32;
+    unsafe {
+        asm!(
+            "pushfd",
+            "pop {original}",
+            "mov {toggled}, {original}",
+            "xor {toggled}, {id}",
+            "push {toggled}",
+            "popfd",
+            "pushfd",
+            "pop {toggled}",
+            "push {original}",
+            "popfd",
+            original = out(reg) original,
+            toggled = out(reg) toggled,
+            id = const EFLAGS_ID,
+        );
+    }
+    (original ^ toggled) & EFLAGS_ID != 0
+}
+
+pub fn max_leaf() -> u32 {
+    if !is_supported() {
+        return 0;
+    }
+    __cpuid(0).eax
+}
+
+pub fn query(leaf: u32) -> Option<CpuidResult> {
+    if leaf > max_leaf() {
+        return None;
+    }
+    Some(__cpuid(leaf))
+}
+
+pub fn max_extended_leaf() -> u32 {
+    if !is_supported() {
+        return 0;
+    }
+    let max = __cpuid(LEAF_EXTENDED_MAX).eax;
+    if max >= LEAF_EXTENDED_MAX {
+        max
+    } else {
+        0
+    }
+}
+
+pub fn query_extended(leaf: u32) -> Option<CpuidResult> {
+    if leaf > max_extended_leaf() {
+        return None;
+    }
+    Some(__cpuid(leaf))
+}
+
+pub fn info() -> CpuInfo {
+    let mut info = CpuInfo {
+        vendor: [0; VENDOR_LEN],
+        brand: [0; BRAND_LEN],
+        max_leaf: max_leaf(),
+        max_extended_leaf: max_extended_leaf(),
+        family: 0,
+        model: 0,
+        stepping: 0,
+        features_ecx: 0,
+        features_edx: 0,
+    };
+
+    if let Some(result) = query(LEAF_VENDOR) {
+        for (i, register) in [result.ebx, result.edx, result.ecx].iter().enumerate() {
+            info.vendor[i * 4..i * 4 + 4].copy_from_slice(&register.to_le_bytes());
+        }
+    }
+
+    if let Some(result) = query(LEAF_FEATURES) {
+        let base_family = (result.eax >> 8) & 0xF;
+        let base_model = (result.eax >> 4) & 0xF;
+        info.stepping = result.eax & 0xF;
+        info.family = base_family;
+        info.model = base_model;
+        if base_family == 0xF {
+            info.family += (result.eax >> 20) & 0xFF;
+        }
+        if base_family == 0x6 || base_family == 0xF {
+            info.model |= ((result.eax >> 16) & 0xF) << 4;
+        }
+        info.features_ecx = result.ecx;
+        info.features_edx = result.edx;
+    }
+
+    for (i, leaf) in (LEAF_BRAND_FIRST..=LEAF_BRAND_LAST).enumerate() {
+        let result = match query_extended(leaf) {
+            Some(result) => result,
+            None => break,
+        };
+        for (j, register) in [result.eax, result.ebx, result.ecx, result.edx]
+            .iter()
+            .enumerate()
+        {
+            let offset = i * 16 + j * 4;
+            info.brand[offset..offset + 4].copy_from_slice(&register.to_le_bytes());
+        }
+    }
+
+    info
+}
+
+pub fn has_monitor_mwait() -> bool {
+    match query(LEAF_FEATURES) {
+        Some(result) => result.ecx & FEATURE_ECX_MONITOR != 0,
+        None => false,
+    }
+}
+
+pub fn has_apic() -> bool {
+    match query(LEAF_FEATURES) {
+        Some(result) => result.edx & FEATURE_EDX_APIC != 0,
+        None => false,
+    }
+}
+
+pub fn has_tsc() -> bool {
+    match query(LEAF_FEATURES) {
+        Some(result) => result.edx & FEATURE_EDX_TSC != 0,
+        None => false,
+    }
+}
+
+pub fn has_rdrand() -> bool {
+    match query(LEAF_FEATURES) {
+        Some(result) => result.ecx & FEATURE_ECX_RDRAND != 0,
+        None => false,
+    }
+}
@@ -0,0 +1,121 @@
+use crate::vga::{VGA_BUFFER_ADDR, VGA_BUFFER_HEIGHT, VGA_BUFFER_WIDTH};
+use crate::{fbcon, serial};
+use core::arch::asm;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+const ATTRIBUTE: u8 = 0x4F;
+
+static READY: AtomicBool = AtomicBool::new(false);
+static COLUMN: AtomicUsize = AtomicUsize::new(0);
+static ROW: AtomicUsize = AtomicUsize::new(0);
+
+pub struct EarlyWriter;
+
+impl Write for EarlyWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_str(s);
+        Ok(())
+    }
+}
+
+pub fn mark_ready() {
+    READY.store(true, Ordering::SeqCst);
+}
+
+pub fn is_ready() -> bool {
+    READY.load(Ordering::SeqCst)
+}
+
+pub fn set_position(column: usize, row: usize) {
+    COLUMN.store(column.min(VGA_BUFFER_WIDTH - 1), Ordering::SeqCst);
+    ROW.store(row.min(VGA_BUFFER_HEIGHT - 1), Ordering::SeqCst);
+}
+
+fn put(column: usize, row: usize, byte: u8) {
+    let offset = row * VGA_BUFFER_WIDTH + column;
+    let cell = (ATTRIBUTE as u16) << 8 | byte as u16;
+    unsafe {
+        core::ptr::write_volatile((VGA_BUFFER_ADDR as *mut u16).add(offset), cell);
+    }
+    if fbcon::is_active() {
+        fbcon::draw_cell(column, row, byte, ATTRIBUTE);
+    }
+}
+
+fn new_line() {
+    let row = (ROW.load(Ordering::SeqCst) + 1) % VGA_BUFFER_HEIGHT;
+    COLUMN.store(0, Ordering::SeqCst);
+    ROW.store(row, Ordering::SeqCst);
+    for column in 0..VGA_BUFFER_WIDTH {
+        put(column, row, b' ');
+    }
+}
+
+pub fn write_byte(byte: u8) {
+    if serial::is_present() {
+        serial::write_byte(byte);
+    }
+
+    if byte == b'\n' {
+        new_line();
+        return;
+    }
+
+    let column = COLUMN.load(Ordering::SeqCst);
+    let byte = if (0x20..0x7F).contains(&byte) {
+        byte
+    } else {
+        0xFE
+    };
+    put(column, ROW.load(Ordering::SeqCst), byte);
+    if column + 1 >= VGA_BUFFER_WIDTH {
+        new_line();
+    } else {
+        COLUMN.store(column + 1, Ordering::SeqCst);
+    }
+}
+
+pub fn write_str(s: &str) {
+    for byte in s.bytes() {
+        write_byte(byte);
+    }
+}
+
+pub fn panic(message: &dyn fmt::Display, location: Option<(&str, u32)>) -> ! {
+    unsafe {
+        asm!("cli", options(nomem, nostack));
+    }
+
+    let mut writer = EarlyWriter;
+    let _ = write!(writer, "\nKERNEL PANIC (early): {}", message);
+    if let Some((file, line)) = location {
+        let _ = write!(writer, "\n  at {}:{}", file, line);
+    }
+    write_str("\nSystem halted. Please reboot.");
+
+    crate::panic::halt_loop()
+}
+
+#[inline(never)]
+pub fn assert_failed(message: &str, file: &str, line: u32) -> ! {
+    panic(&message, Some((file, line)))
+}
+
+#[macro_export]
+macro_rules! assert_early {
+    ($cond:expr) => {
+        if !$cond {
+            $crate::early_console::assert_failed(
+                concat!("Assertion failed: ", stringify!($cond)),
+                file!(),
+                line!(),
+            );
+        }
+    };
+    ($cond:expr, $msg:expr) => {
+        if !$cond {
+            $crate::early_console::assert_failed($msg, file!(), line!());
+        }
+    };
+}
@@ -0,0 +1,248 @@
+use crate::memory::{align_down, align_up, paging, PAGE_SIZE, USER_SPACE_START};
+use crate::usermode::{self, UserModeError};
+
+const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+const ELF_CLASS_32: u8 = 1;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_TYPE_EXEC: u16 = 2;
+const ELF_MACHINE_386: u16 = 3;
+
+const HEADER_SIZE: usize = 52;
+const PROGRAM_HEADER_SIZE: usize = 32;
+
+const PT_LOAD: u32 = 1;
+const PF_W: u32 = 1 << 1;
+
+pub const MAX_SEGMENTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfError {
+    Truncated,
+    BadMagic,
+    WrongClass,
+    WrongEndian,
+    WrongMachine,
+    NotExecutable,
+    BadProgramHeader,
+    TooManySegments,
+    SegmentOutOfRange,
+    OverlappingSegments,
+    EntryOutsideSegment,
+    Memory(UserModeError),
+}
+
+impl ElfError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ElfError::Truncated => "file is truncated",
+            ElfError::BadMagic => "not an ELF file",
+            ElfError::WrongClass => "not a 32-bit ELF file",
+            ElfError::WrongEndian => "not a little-endian ELF file",
+            ElfError::WrongMachine => "not an i386 executable",
+            ElfError::NotExecutable => "not an executable file",
+            ElfError::BadProgramHeader => "malformed program header",
+            ElfError::TooManySegments => "too many loadable segments",
+            ElfError::SegmentOutOfRange => "segment outside user space",
+            ElfError::OverlappingSegments => "segments overlap",
+            ElfError::EntryOutsideSegment => "entry point outside any segment",
+            ElfError::Memory(error) => error.description(),
+        }
+    }
+}
+
+impl From<UserModeError> for ElfError {
+    fn from(error: UserModeError) -> ElfError {
+        ElfError::Memory(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Segment {
+    pub vaddr: usize,
+    pub mem_size: usize,
+    pub offset: usize,
+    pub file_size: usize,
+    pub flags: u32,
+}
+
+impl Segment {
+    pub fn is_writable(&self) -> bool {
+        self.flags & PF_W != 0
+    }
+
+    fn page_start(&self) -> usize {
+        align_down(self.vaddr, PAGE_SIZE)
+    }
+
+    fn page_end(&self) -> usize {
+        align_up(self.vaddr + self.mem_size, PAGE_SIZE)
+    }
+
+    fn pages(&self) -> usize {
+        (self.page_end() - self.page_start()) / PAGE_SIZE
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        address >= self.vaddr && address < self.vaddr + self.mem_size
+    }
+}
+
+pub struct ElfImage {
+    data: &'static [u8],
+    pub entry: usize,
+    segments: [Segment; MAX_SEGMENTS],
+    count: usize,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+pub fn parse(data: &'static [u8]) -> Result<ElfImage, ElfError> {
+    if data.len() < HEADER_SIZE {
+        return Err(ElfError::Truncated);
+    }
+    if data[..4] != ELF_MAGIC {
+        return Err(ElfError::BadMagic);
+    }
+    if data[4] != ELF_CLASS_32 {
+        return Err(ElfError::WrongClass);
+    }
+    if data[5] != ELF_DATA_LSB {
+        return Err(ElfError::WrongEndian);
+    }
+    if read_u16(data, 18) != ELF_MACHINE_386 {
+        return Err(ElfError::WrongMachine);
+    }
+    if read_u16(data, 16) != ELF_TYPE_EXEC {
+        return Err(ElfError::NotExecutable);
+    }
+
+    let entry = read_u32(data, 24) as usize;
+    let ph_offset = read_u32(data, 28) as usize;
+    let ph_size = read_u16(data, 42) as usize;
+    let ph_count = read_u16(data, 44) as usize;
+
+    if ph_size < PROGRAM_HEADER_SIZE {
+        return Err(ElfError::BadProgramHeader);
+    }
+    let ph_end = ph_size
+        .checked_mul(ph_count)
+        .and_then(|size| size.checked_add(ph_offset))
+        .ok_or(ElfError::Truncated)?;
+    if ph_end > data.len() {
+        return Err(ElfError::Truncated);
+    }
+
+    let mut image = ElfImage {
+        data,
+        entry,
+        segments: [Segment::default(); MAX_SEGMENTS],
+        count: 0,
+    };
+
+    for index in 0..ph_count {
+        let header = &data[ph_offset + index * ph_size..];
+        if read_u32(header, 0) != PT_LOAD {
+            continue;
+        }
+
+        let segment = Segment {
+            offset: read_u32(header, 4) as usize,
+            vaddr: read_u32(header, 8) as usize,
+            file_size: read_u32(header, 16) as usize,
+            mem_size: read_u32(header, 20) as usize,
+            flags: read_u32(header, 24),
+        };
+        if segment.mem_size == 0 {
+            continue;
+        }
+        if segment.file_size > segment.mem_size {
+            return Err(ElfError::BadProgramHeader);
+        }
+        match segment.offset.checked_add(segment.file_size) {
+            Some(end) if end <= data.len() => {}
+            _ => return Err(ElfError::Truncated),
+        }
+        match segment.vaddr.checked_add(segment.mem_size) {
+            Some(end) if segment.vaddr >= USER_SPACE_START && end <= usermode::STACK_BOTTOM => {}
+            _ => return Err(ElfError::SegmentOutOfRange),
+        }
+
+        if image.segments[..image.count].iter().any(|other| {
+            segment.page_start() < other.page_end() && other.page_start() < segment.page_end()
+        }) {
+            return Err(ElfError::OverlappingSegments);
+        }
+        if image.count == MAX_SEGMENTS {
+            return Err(ElfError::TooManySegments);
+        }
+        image.segments[image.count] = segment;
+        image.count += 1;
+    }
+
+    if !image
+        .segments()
+        .iter()
+        .any(|segment| segment.contains(entry))
+    {
+        return Err(ElfError::EntryOutsideSegment);
+    }
+
+    Ok(image)
+}
+
+impl ElfImage {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments[..self.count]
+    }
+
+    pub fn load(&self) -> Result<(), ElfError> {
+        for (index, segment) in self.segments().iter().enumerate() {
+            if let Err(error) = usermode::map_user_pages(segment.page_start(), segment.pages()) {
+                for loaded in &self.segments()[..index] {
+                    usermode::unmap_user_pages(loaded.page_start(), loaded.pages());
+                }
+                return Err(error.into());
+            }
+
+            let file = &self.data[segment.offset..segment.offset + segment.file_size];
+            unsafe {
+                core::ptr::copy_nonoverlapping(file.as_ptr(), segment.vaddr as *mut u8, file.len());
+            }
+
+            if !segment.is_writable() {
+                for page in 0..segment.pages() {
+                    let address = segment.page_start() + page * PAGE_SIZE;
+                    if let Some(frame) = paging::get_physical_address(address) {
+                        paging::map_page(address, frame, paging::USER_READ_ONLY_FLAGS);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn unload(&self) {
+        for segment in self.segments() {
+            usermode::unmap_user_pages(segment.page_start(), segment.pages());
+        }
+    }
+}
+
+pub fn exec(data: &'static [u8]) -> Result<usermode::UserRun, ElfError> {
+    let image = parse(data)?;
+    image.load()?;
+    let run = usermode::exec(image.entry);
+    image.unload();
+    run.map_err(ElfError::from)
+}
@@ -0,0 +1,339 @@
+use crate::font::{FONT, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::memory::{paging, PAGE_SIZE};
+use crate::vga::{self, VGA_BUFFER_HEIGHT, VGA_BUFFER_WIDTH};
+use core::ptr::{addr_of, addr_of_mut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const MULTIBOOT_FLAG_FRAMEBUFFER: u32 = 1 << 12;
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+const FRAMEBUFFER_TYPE_TEXT: u8 = 2;
+
+const CONSOLE_WIDTH: usize = VGA_BUFFER_WIDTH * GLYPH_WIDTH;
+const CONSOLE_HEIGHT: usize = VGA_BUFFER_HEIGHT * GLYPH_HEIGHT;
+
+const PALETTE: [u32; 16] = [
+    0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA, 0x555555,
+    0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static CURSOR_VISIBLE: AtomicBool = AtomicBool::new(true);
+
+static mut FRAMEBUFFER: Option<Framebuffer> = None;
+static mut CELLS: [[u16; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT] =
+    [[0; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT];
+static mut CURSOR: Option<(usize, usize)> = None;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbconError {
+    NoFramebuffer,
+    TextMode,
+    UnsupportedType(u8),
+    UnsupportedDepth(u8),
+    TooSmall,
+    OutOfReach,
+}
+
+impl FbconError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            FbconError::NoFramebuffer => "no framebuffer in the boot info",
+            FbconError::TextMode => "framebuffer is in text mode",
+            FbconError::UnsupportedType(_) => "framebuffer is not direct RGB",
+            FbconError::UnsupportedDepth(15 | 16) => "16 bpp framebuffers are not supported",
+            FbconError::UnsupportedDepth(_) => "only 24 and 32 bpp framebuffers are supported",
+            FbconError::TooSmall => "framebuffer is smaller than 640x400",
+            FbconError::OutOfReach => "framebuffer lies above 4 GiB",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Channel {
+    shift: u8,
+    size: u8,
+}
+
+impl Channel {
+    fn encode(&self, value: u32) -> u32 {
+        (value >> (8 - self.size.min(8))) << self.shift
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+    pub address: usize,
+    pub pitch: usize,
+    pub width: usize,
+    pub height: usize,
+    pub bpp: u8,
+    red: Channel,
+    green: Channel,
+    blue: Channel,
+}
+
+impl Framebuffer {
+    fn bytes_per_pixel(&self) -> usize {
+        self.bpp as usize / 8
+    }
+
+    fn size(&self) -> usize {
+        self.pitch * self.height
+    }
+
+    fn origin(&self) -> usize {
+        let left = (self.width - CONSOLE_WIDTH) / 2;
+        let top = (self.height - CONSOLE_HEIGHT) / 2;
+        self.address + top * self.pitch + left * self.bytes_per_pixel()
+    }
+
+    fn cell_address(&self, x: usize, y: usize) -> usize {
+        self.origin() + y * GLYPH_HEIGHT * self.pitch + x * GLYPH_WIDTH * self.bytes_per_pixel()
+    }
+
+    fn color(&self, index: u8) -> u32 {
+        let rgb = PALETTE[index as usize & 0x0F];
+        self.red.encode((rgb >> 16) & 0xFF)
+            | self.green.encode((rgb >> 8) & 0xFF)
+            | self.blue.encode(rgb & 0xFF)
+    }
+
+    fn put_pixel(&self, address: usize, color: u32) {
+        unsafe {
+            if self.bpp == 32 {
+                core::ptr::write_volatile(address as *mut u32, color);
+            } else {
+                let bytes = color.to_le_bytes();
+                for (offset, &byte) in bytes.iter().take(3).enumerate() {
+                    core::ptr::write_volatile((address + offset) as *mut u8, byte);
+                }
+            }
+        }
+    }
+
+    fn draw_glyph(&self, x: usize, y: usize, glyph: u8, foreground: u32, background: u32) {
+        let bytes_per_pixel = self.bytes_per_pixel();
+        let mut row_address = self.cell_address(x, y);
+        for bits in FONT[glyph as usize] {
+            for column in 0..GLYPH_WIDTH {
+                let color = if bits & (0x80 >> column) != 0 {
+                    foreground
+                } else {
+                    background
+                };
+                self.put_pixel(row_address + column * bytes_per_pixel, color);
+            }
+            row_address += self.pitch;
+        }
+    }
+
+    fn clear(&self) {
+        let black = self.color(0);
+        for row in 0..self.height {
+            let line = self.address + row * self.pitch;
+            for column in 0..self.width {
+                self.put_pixel(line + column * self.bytes_per_pixel(), black);
+            }
+        }
+    }
+}
+
+fn parse(multiboot_info: u32) -> Result<Framebuffer, FbconError> {
+    if multiboot_info == 0 {
+        return Err(FbconError::NoFramebuffer);
+    }
+
+    let info = multiboot_info as *const u8;
+    let read_u32 =
+        |offset: usize| unsafe { core::ptr::read_unaligned(info.add(offset) as *const u32) };
+    let read_u8 = |offset: usize| unsafe { *info.add(offset) };
+
+    if read_u32(0) & MULTIBOOT_FLAG_FRAMEBUFFER == 0 {
+        return Err(FbconError::NoFramebuffer);
+    }
+
+    let address = unsafe { core::ptr::read_unaligned(info.add(88) as *const u64) };
+    let kind = read_u8(109);
+    match kind {
+        FRAMEBUFFER_TYPE_RGB => {}
+        FRAMEBUFFER_TYPE_TEXT => return Err(FbconError::TextMode),
+        _ => return Err(FbconError::UnsupportedType(kind)),
+    }
+
+    let framebuffer = Framebuffer {
+        address: usize::try_from(address).map_err(|_| FbconError::OutOfReach)?,
+        pitch: read_u32(96) as usize,
+        width: read_u32(100) as usize,
+        height: read_u32(104) as usize,
+        bpp: read_u8(108),
+        red: Channel {
+            shift: read_u8(110),
+            size: read_u8(111),
+        },
+        green: Channel {
+            shift: read_u8(112),
+            size: read_u8(113),
+        },
+        blue: Channel {
+            shift: read_u8(114),
+            size: read_u8(115),
+        },
+    };
+
+    if framebuffer.bpp != 24 && framebuffer.bpp != 32 {
+        return Err(FbconError::UnsupportedDepth(framebuffer.bpp));
+    }
+    if framebuffer.width < CONSOLE_WIDTH || framebuffer.height < CONSOLE_HEIGHT {
+        return Err(FbconError::TooSmall);
+    }
+    if framebuffer
+        .address
+        .checked_add(framebuffer.size())
+        .is_none()
+    {
+        return Err(FbconError::OutOfReach);
+    }
+    Ok(framebuffer)
+}
+
+pub fn init(multiboot_info: u32) -> Result<Framebuffer, FbconError> {
+    let framebuffer = parse(multiboot_info)?;
+    framebuffer.clear();
+    unsafe {
+        *addr_of_mut!(FRAMEBUFFER) = Some(framebuffer);
+    }
+    ACTIVE.store(true, Ordering::SeqCst);
+    Ok(framebuffer)
+}
+
+pub fn map() -> bool {
+    let Some(framebuffer) = info() else {
+        return true;
+    };
+
+    let start = framebuffer.address & !(PAGE_SIZE - 1);
+    let end = framebuffer.address + framebuffer.size();
+    let mapped = (start..end)
+        .step_by(PAGE_SIZE)
+        .all(|page| paging::map_page(page, page, paging::KERNEL_PAGE_FLAGS));
+    if !mapped {
+        ACTIVE.store(false, Ordering::SeqCst);
+        unsafe {
+            *addr_of_mut!(FRAMEBUFFER) = None;
+        }
+    }
+    mapped
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+pub fn info() -> Option<Framebuffer> {
+    unsafe { *addr_of!(FRAMEBUFFER) }
+}
+
+fn render(framebuffer: &Framebuffer, x: usize, y: usize, inverted: bool) {
+    let cell = unsafe { (*addr_of!(CELLS))[y][x] };
+    let attribute = (cell >> 8) as u8;
+    let foreground = attribute & 0x0F;
+    let background = if vga::blink_mode() {
+        (attribute >> 4) & 0x07
+    } else {
+        attribute >> 4
+    };
+    let (foreground, background) = if inverted {
+        (background, foreground)
+    } else {
+        (foreground, background)
+    };
+    framebuffer.draw_glyph(
+        x,
+        y,
+        cell as u8,
+        framebuffer.color(foreground),
+        framebuffer.color(background),
+    );
+}
+
+fn cursor_at(x: usize, y: usize) -> bool {
+    CURSOR_VISIBLE.load(Ordering::SeqCst) && unsafe { *addr_of!(CURSOR) } == Some((x, y))
+}
+
+pub fn cell(x: usize, y: usize) -> Option<u16> {
+    unsafe { (*addr_of!(CELLS)).get(y)?.get(x).copied() }
+}
+
+pub fn draw_cell(x: usize, y: usize, glyph: u8, attribute: u8) {
+    let Some(framebuffer) = info() else {
+        return;
+    };
+    if x >= VGA_BUFFER_WIDTH || y >= VGA_BUFFER_HEIGHT {
+        return;
+    }
+
+    let cell = (attribute as u16) << 8 | glyph as u16;
+    let cells = unsafe { &mut *addr_of_mut!(CELLS) };
+    if cells[y][x] == cell {
+        return;
+    }
+    cells[y][x] = cell;
+    render(&framebuffer, x, y, cursor_at(x, y));
+}
+
+pub fn scroll(top: usize, bottom: usize) {
+    let Some(framebuffer) = info() else {
+        return;
+    };
+    if top + 1 >= bottom || bottom > VGA_BUFFER_HEIGHT {
+        return;
+    }
+
+    hide_cursor(&framebuffer);
+    let row_bytes = GLYPH_HEIGHT * framebuffer.pitch;
+    let destination = framebuffer.cell_address(0, top);
+    unsafe {
+        core::ptr::copy(
+            (destination + row_bytes) as *const u8,
+            destination as *mut u8,
+            (bottom - top - 1) * row_bytes,
+        );
+        (*addr_of_mut!(CELLS)).copy_within(top + 1..bottom, top);
+    }
+    show_cursor(&framebuffer);
+}
+
+fn hide_cursor(framebuffer: &Framebuffer) {
+    if let Some((x, y)) = unsafe { *addr_of!(CURSOR) } {
+        render(framebuffer, x, y, false);
+    }
+}
+
+fn show_cursor(framebuffer: &Framebuffer) {
+    if let Some((x, y)) = unsafe { *addr_of!(CURSOR) } {
+        render(framebuffer, x, y, CURSOR_VISIBLE.load(Ordering::SeqCst));
+    }
+}
+
+pub fn set_cursor(x: usize, y: usize) {
+    let Some(framebuffer) = info() else {
+        return;
+    };
+    let position = (x < VGA_BUFFER_WIDTH && y < VGA_BUFFER_HEIGHT).then_some((x, y));
+    if unsafe { *addr_of!(CURSOR) } == position {
+        return;
+    }
+
+    hide_cursor(&framebuffer);
+    unsafe {
+        *addr_of_mut!(CURSOR) = position;
+    }
+    show_cursor(&framebuffer);
+}
+
+pub fn set_cursor_visible(visible: bool) {
+    CURSOR_VISIBLE.store(visible, Ordering::SeqCst);
+    if let Some(framebuffer) = info() {
+        show_cursor(&framebuffer);
+    }
+}
@@ -0,0 +1,15 @@
+    .globl _start
+    .text
+_start:
+    movl $1, %eax
+    movl $1, %ebx
+    movl $msg, %ecx
+    movl $len, %edx
+    int $0x80
+    movl $60, %eax
+    xorl %ebx, %ebx
+    int $0x80
+    .data
+msg:
+    .ascii "hello from user space\n"
+    .set len, . - msg
@@ -0,0 +1,152 @@
+pub const BLOCK_SIZE: usize = 512;
+pub const MAGIC: u32 = 0x3153_464B; // "KFS1"
+pub const VERSION: u32 = 1;
+
+pub const SUPERBLOCK: u32 = 0;
+pub const BITS_PER_BITMAP_BLOCK: u32 = (BLOCK_SIZE * 8) as u32;
+
+pub const NAME_LEN: usize = 32;
+pub const DIR_ENTRY_SIZE: usize = 64;
+pub const ENTRIES_PER_BLOCK: usize = BLOCK_SIZE / DIR_ENTRY_SIZE;
+pub const DIR_BLOCKS: u32 = 4;
+pub const MAX_ENTRIES: usize = ENTRIES_PER_BLOCK * DIR_BLOCKS as usize;
+
+const ENTRY_USED: u32 = 1 << 0;
+
+pub fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+pub fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Superblock {
+    pub block_count: u32,
+    pub bitmap_start: u32,
+    pub bitmap_blocks: u32,
+    pub dir_start: u32,
+    pub dir_blocks: u32,
+    pub data_start: u32,
+}
+
+impl Superblock {
+    pub fn new(block_count: u32) -> Superblock {
+        let bitmap_start = SUPERBLOCK + 1;
+        let bitmap_blocks = block_count.div_ceil(BITS_PER_BITMAP_BLOCK);
+        let dir_start = bitmap_start + bitmap_blocks;
+
+        Superblock {
+            block_count,
+            bitmap_start,
+            bitmap_blocks,
+            dir_start,
+            dir_blocks: DIR_BLOCKS,
+            data_start: dir_start + DIR_BLOCKS,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) {
+        buf[..BLOCK_SIZE].fill(0);
+        write_u32(buf, 0, MAGIC);
+        write_u32(buf, 4, VERSION);
+        write_u32(buf, 8, self.block_count);
+        write_u32(buf, 12, self.bitmap_start);
+        write_u32(buf, 16, self.bitmap_blocks);
+        write_u32(buf, 20, self.dir_start);
+        write_u32(buf, 24, self.dir_blocks);
+        write_u32(buf, 28, self.data_start);
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<Superblock> {
+        if read_u32(buf, 0) != MAGIC || read_u32(buf, 4) != VERSION {
+            return None;
+        }
+
+        let superblock = Superblock {
+            block_count: read_u32(buf, 8),
+            bitmap_start: read_u32(buf, 12),
+            bitmap_blocks: read_u32(buf, 16),
+            dir_start: read_u32(buf, 20),
+            dir_blocks: read_u32(buf, 24),
+            data_start: read_u32(buf, 28),
+        };
+
+        if superblock == Superblock::new(superblock.block_count) {
+            Some(superblock)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirEntry {
+    name: [u8; NAME_LEN],
+    name_len: usize,
+    pub size: u32,
+    pub first_block: u32,
+    pub block_count: u32,
+}
+
+impl DirEntry {
+    pub fn new(name: &str, size: u32, first_block: u32, block_count: u32) -> Option<DirEntry> {
+        if name.is_empty() || name.len() > NAME_LEN || name.as_bytes().contains(&0) {
+            return None;
+        }
+
+        let mut entry = DirEntry {
+            name: [0; NAME_LEN],
+            name_len: name.len(),
+            size,
+            first_block,
+            block_count,
+        };
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        Some(entry)
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("?")
+    }
+
+    pub fn encode(entry: Option<&DirEntry>, buf: &mut [u8]) {
+        buf[..DIR_ENTRY_SIZE].fill(0);
+
+        if let Some(entry) = entry {
+            buf[..NAME_LEN].copy_from_slice(&entry.name);
+            write_u32(buf, 32, entry.size);
+            write_u32(buf, 36, entry.first_block);
+            write_u32(buf, 40, entry.block_count);
+            write_u32(buf, 44, ENTRY_USED);
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<DirEntry> {
+        if read_u32(buf, 44) & ENTRY_USED == 0 {
+            return None;
+        }
+
+        let mut name = [0; NAME_LEN];
+        name.copy_from_slice(&buf[..NAME_LEN]);
+        let name_len = name.iter().position(|&byte| byte == 0).unwrap_or(NAME_LEN);
+
+        Some(DirEntry {
+            name,
+            name_len,
+            size: read_u32(buf, 32),
+            first_block: read_u32(buf, 36),
+            block_count: read_u32(buf, 40),
+        })
+    }
+}
+
+pub fn blocks_for(size: usize) -> u32 {
+    size.div_ceil(BLOCK_SIZE) as u32
+}
@@ -0,0 +1,269 @@
+use super::layout::{
+    blocks_for, DirEntry, Superblock, BITS_PER_BITMAP_BLOCK, BLOCK_SIZE, DIR_ENTRY_SIZE,
+    ENTRIES_PER_BLOCK, NAME_LEN, SUPERBLOCK,
+};
+use crate::block::{BlockDevice, BlockError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    Device(BlockError),
+    UnsupportedDevice,
+    NotFormatted,
+    NotFound,
+    AlreadyExists,
+    InvalidName,
+    DirectoryFull,
+    NoSpace,
+}
+
+impl FsError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            FsError::Device(error) => error.description(),
+            FsError::UnsupportedDevice => "unsupported device",
+            FsError::NotFormatted => "no filesystem found, run 'fs format'",
+            FsError::NotFound => "no such file",
+            FsError::AlreadyExists => "file already exists",
+            FsError::InvalidName => "invalid file name",
+            FsError::DirectoryFull => "directory is full",
+            FsError::NoSpace => "not enough free space",
+        }
+    }
+}
+
+impl From<BlockError> for FsError {
+    fn from(error: BlockError) -> FsError {
+        FsError::Device(error)
+    }
+}
+
+pub struct Fs<'a, D: BlockDevice> {
+    device: &'a D,
+    superblock: Superblock,
+}
+
+fn check_device<D: BlockDevice>(device: &D) -> Result<u32, FsError> {
+    if device.block_size() != BLOCK_SIZE {
+        return Err(FsError::UnsupportedDevice);
+    }
+    Ok(core::cmp::min(device.num_blocks(), u32::MAX as u64) as u32)
+}
+
+pub fn format<D: BlockDevice>(device: &D) -> Result<(), FsError> {
+    let block_count = check_device(device)?;
+    let superblock = Superblock::new(block_count);
+    if superblock.data_start >= block_count {
+        return Err(FsError::UnsupportedDevice);
+    }
+
+    let mut block = [0u8; BLOCK_SIZE];
+    for index in 0..superblock.dir_blocks {
+        device.write_block((superblock.dir_start + index) as u64, &block)?;
+    }
+
+    for index in 0..superblock.bitmap_blocks {
+        block.fill(0);
+        let first = index * BITS_PER_BITMAP_BLOCK;
+        for bit in 0..BITS_PER_BITMAP_BLOCK {
+            let number = first + bit;
+            if number < superblock.data_start || number >= block_count {
+                block[bit as usize / 8] |= 1 << (bit % 8);
+            }
+        }
+        device.write_block((superblock.bitmap_start + index) as u64, &block)?;
+    }
+
+    superblock.encode(&mut block);
+    device.write_block(SUPERBLOCK as u64, &block)?;
+    Ok(())
+}
+
+impl<'a, D: BlockDevice> Fs<'a, D> {
+    pub fn mount(device: &'a D) -> Result<Fs<'a, D>, FsError> {
+        let block_count = check_device(device)?;
+
+        let mut block = [0u8; BLOCK_SIZE];
+        device.read_block(SUPERBLOCK as u64, &mut block)?;
+        let superblock = Superblock::decode(&block).ok_or(FsError::NotFormatted)?;
+        if superblock.block_count > block_count {
+            return Err(FsError::NotFormatted);
+        }
+
+        Ok(Fs { device, superblock })
+    }
+
+    pub fn for_each(&self, mut f: impl FnMut(&DirEntry)) -> Result<(), FsError> {
+        let mut block = [0u8; BLOCK_SIZE];
+        for index in 0..self.superblock.dir_blocks {
+            self.device
+                .read_block((self.superblock.dir_start + index) as u64, &mut block)?;
+            for raw in block.chunks_exact(DIR_ENTRY_SIZE) {
+                if let Some(entry) = DirEntry::decode(raw) {
+                    f(&entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn find_slot(&self, name: Option<&str>) -> Result<Option<(usize, Option<DirEntry>)>, FsError> {
+        let mut block = [0u8; BLOCK_SIZE];
+        for index in 0..self.superblock.dir_blocks {
+            self.device
+                .read_block((self.superblock.dir_start + index) as u64, &mut block)?;
+            for (slot, raw) in block.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+                let entry = DirEntry::decode(raw);
+                let found = match (name, &entry) {
+                    (Some(name), Some(entry)) => entry.name() == name,
+                    (None, None) => true,
+                    _ => false,
+                };
+                if found {
+                    return Ok(Some((index as usize * ENTRIES_PER_BLOCK + slot, entry)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn write_slot(&self, slot: usize, entry: Option<&DirEntry>) -> Result<(), FsError> {
+        let block_number = (self.superblock.dir_start as usize + slot / ENTRIES_PER_BLOCK) as u64;
+        let offset = slot % ENTRIES_PER_BLOCK * DIR_ENTRY_SIZE;
+
+        let mut block = [0u8; BLOCK_SIZE];
+        self.device.read_block(block_number, &mut block)?;
+        DirEntry::encode(entry, &mut block[offset..offset + DIR_ENTRY_SIZE]);
+        self.device.write_block(block_number, &block)?;
+        Ok(())
+    }
+
+    pub fn find(&self, name: &str) -> Result<DirEntry, FsError> {
+        match self.find_slot(Some(name))? {
+            Some((_, Some(entry))) => Ok(entry),
+            _ => Err(FsError::NotFound),
+        }
+    }
+
+    fn set_blocks_used(&self, first: u32, count: u32, used: bool) -> Result<(), FsError> {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut loaded = None;
+
+        for number in first..first + count {
+            let bitmap_block = self.superblock.bitmap_start + number / BITS_PER_BITMAP_BLOCK;
+            if loaded != Some(bitmap_block) {
+                if let Some(previous) = loaded {
+                    self.device.write_block(previous as u64, &block)?;
+                }
+                self.device.read_block(bitmap_block as u64, &mut block)?;
+                loaded = Some(bitmap_block);
+            }
+
+            let bit = number % BITS_PER_BITMAP_BLOCK;
+            if used {
+                block[bit as usize / 8] |= 1 << (bit % 8);
+            } else {
+                block[bit as usize / 8] &= !(1 << (bit % 8));
+            }
+        }
+
+        if let Some(previous) = loaded {
+            self.device.write_block(previous as u64, &block)?;
+        }
+        Ok(())
+    }
+
+    fn find_free_run(&self, count: u32) -> Result<u32, FsError> {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut run_start = 0;
+        let mut run_len = 0;
+
+        for index in 0..self.superblock.bitmap_blocks {
+            self.device
+                .read_block((self.superblock.bitmap_start + index) as u64, &mut block)?;
+
+            for bit in 0..BITS_PER_BITMAP_BLOCK {
+                let number = index * BITS_PER_BITMAP_BLOCK + bit;
+                if number >= self.superblock.block_count {
+                    return Err(FsError::NoSpace);
+                }
+
+                if block[bit as usize / 8] & (1 << (bit % 8)) != 0 {
+                    run_len = 0;
+                    continue;
+                }
+
+                if run_len == 0 {
+                    run_start = number;
+                }
+                run_len += 1;
+                if run_len == count {
+                    return Ok(run_start);
+                }
+            }
+        }
+
+        Err(FsError::NoSpace)
+    }
+
+    pub fn create(&mut self, name: &str, data: &[u8]) -> Result<(), FsError> {
+        if name.len() > NAME_LEN || data.len() > u32::MAX as usize {
+            return Err(FsError::InvalidName);
+        }
+        if self.find_slot(Some(name))?.is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        let (slot, _) = self.find_slot(None)?.ok_or(FsError::DirectoryFull)?;
+
+        let block_count = blocks_for(data.len());
+        let first_block = if block_count > 0 {
+            self.find_free_run(block_count)?
+        } else {
+            0
+        };
+        let entry = DirEntry::new(name, data.len() as u32, first_block, block_count)
+            .ok_or(FsError::InvalidName)?;
+
+        let mut block = [0u8; BLOCK_SIZE];
+        for (index, chunk) in data.chunks(BLOCK_SIZE).enumerate() {
+            block.fill(0);
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.device
+                .write_block((first_block as usize + index) as u64, &block)?;
+        }
+
+        self.set_blocks_used(first_block, block_count, true)?;
+        self.write_slot(slot, Some(&entry))
+    }
+
+    pub fn write(&mut self, name: &str, data: &[u8]) -> Result<(), FsError> {
+        match self.remove(name) {
+            Ok(()) | Err(FsError::NotFound) => {}
+            Err(error) => return Err(error),
+        }
+        self.create(name, data)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), FsError> {
+        let (slot, entry) = match self.find_slot(Some(name))? {
+            Some((slot, Some(entry))) => (slot, entry),
+            _ => return Err(FsError::NotFound),
+        };
+
+        self.write_slot(slot, None)?;
+        self.set_blocks_used(entry.first_block, entry.block_count, false)
+    }
+
+    pub fn read(&self, entry: &DirEntry, mut f: impl FnMut(&[u8])) -> Result<(), FsError> {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut remaining = entry.size as usize;
+
+        for index in 0..entry.block_count {
+            self.device
+                .read_block((entry.first_block + index) as u64, &mut block)?;
+            let len = core::cmp::min(remaining, BLOCK_SIZE);
+            f(&block[..len]);
+            remaining -= len;
+        }
+        Ok(())
+    }
+}
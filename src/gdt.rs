@@ -1,7 +1,10 @@
 use core::arch::asm;
+use core::ptr::{addr_of, addr_of_mut};
 
 pub const GDT_ADDRESS: usize = 0x00000800;
-pub const GDT_ENTRIES: usize = 7;
+pub const GDT_ENTRIES: usize = 9;
+const DOUBLE_FAULT_STACK_SIZE: usize = 8192;
+const TASK_EFLAGS: u32 = 0x2;
 
 pub mod selectors {
     pub const NULL: u16 = 0x00;
@@ -11,9 +14,11 @@ pub mod selectors {
     pub const USER_CODE: u16 = 0x20;
     pub const USER_DATA: u16 = 0x28;
     pub const USER_STACK: u16 = 0x30;
+    pub const TSS: u16 = 0x38;
+    pub const DOUBLE_FAULT_TSS: u16 = 0x40;
 }
 
-mod access {
+pub mod access {
     pub const PRESENT: u8 = 1 << 7;
     pub const RING_0: u8 = 0 << 5;
     pub const RING_3: u8 = 3 << 5;
@@ -22,9 +27,10 @@ mod access {
     pub const DIRECTION: u8 = 0 << 2;
     pub const READWRITE: u8 = 1 << 1;
     pub const ACCESSED: u8 = 0 << 0;
+    pub const TSS_32: u8 = 0x09;
 }
 
-mod granularity {
+pub mod granularity {
     pub const PAGE_GRAN: u8 = 1 << 7;
     pub const SIZE_32: u8 = 1 << 6;
     pub const LONG_MODE: u8 = 0 << 5;
@@ -63,6 +69,122 @@ impl GdtEntry {
             base_high: ((base >> 24) & 0xFF) as u8,
         }
     }
+
+    pub fn base(&self) -> u32 {
+        self.base_low as u32 | (self.base_middle as u32) << 16 | (self.base_high as u32) << 24
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit_low as u32 | ((self.granularity & 0x0F) as u32) << 16
+    }
+
+    pub fn access(&self) -> u8 {
+        self.access
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.granularity & 0xF0
+    }
+
+    pub fn decode(&self) -> DecodedEntry {
+        DecodedEntry {
+            base: self.base(),
+            limit: self.limit(),
+            dpl: (self.access >> 5) & 0x3,
+            present: self.access & access::PRESENT != 0,
+            system: self.access & access::DESCRIPTOR == 0,
+            executable: self.access & access::EXECUTABLE != 0,
+            granularity_4k: self.granularity & granularity::PAGE_GRAN != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedEntry {
+    pub base: u32,
+    pub limit: u32,
+    pub dpl: u8,
+    pub present: bool,
+    pub system: bool,
+    pub executable: bool,
+    pub granularity_4k: bool,
+}
+
+impl DecodedEntry {
+    pub fn kind(&self) -> &'static str {
+        match (self.present, self.system, self.executable) {
+            (false, _, _) => "-",
+            (true, true, _) => "system",
+            (true, false, true) => "code",
+            (true, false, false) => "data",
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct TaskStateSegment {
+    link: u32,
+    pub esp0: u32,
+    pub ss0: u32,
+    esp1: u32,
+    ss1: u32,
+    esp2: u32,
+    ss2: u32,
+    pub cr3: u32,
+    pub eip: u32,
+    pub eflags: u32,
+    eax: u32,
+    ecx: u32,
+    edx: u32,
+    ebx: u32,
+    pub esp: u32,
+    ebp: u32,
+    esi: u32,
+    edi: u32,
+    es: u32,
+    pub cs: u32,
+    ss: u32,
+    ds: u32,
+    fs: u32,
+    gs: u32,
+    ldt: u32,
+    trap: u16,
+    pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    pub const fn new() -> TaskStateSegment {
+        TaskStateSegment {
+            link: 0,
+            esp0: 0,
+            ss0: selectors::KERNEL_STACK as u32,
+            esp1: 0,
+            ss1: 0,
+            esp2: 0,
+            ss2: 0,
+            cr3: 0,
+            eip: 0,
+            eflags: 0,
+            eax: 0,
+            ecx: 0,
+            edx: 0,
+            ebx: 0,
+            esp: 0,
+            ebp: 0,
+            esi: 0,
+            edi: 0,
+            es: 0,
+            cs: 0,
+            ss: 0,
+            ds: 0,
+            fs: 0,
+            gs: 0,
+            ldt: 0,
+            trap: 0,
+            iomap_base: core::mem::size_of::<TaskStateSegment>() as u16,
+        }
+    }
 }
 
 #[repr(C, packed)]
@@ -110,6 +232,8 @@ impl Gdt {
                 GdtEntry::new(0, 0xFFFFF, user_code_access, flags),
                 GdtEntry::new(0, 0xFFFFF, user_data_access, flags),
                 GdtEntry::new(0, 0xFFFFF, user_stack_access, flags),
+                GdtEntry::null(),
+                GdtEntry::null(),
             ],
         }
     }
@@ -117,6 +241,14 @@ impl Gdt {
 
 static GDT: Gdt = Gdt::new();
 
+static mut TSS: TaskStateSegment = TaskStateSegment::new();
+
+#[repr(C, align(16))]
+struct DoubleFaultStack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+static mut DOUBLE_FAULT_STACK: DoubleFaultStack = DoubleFaultStack([0; DOUBLE_FAULT_STACK_SIZE]);
+static mut DOUBLE_FAULT_TSS: TaskStateSegment = TaskStateSegment::new();
+
 static mut GDT_PTR: GdtPointer = GdtPointer {
     limit: (core::mem::size_of::<Gdt>() - 1) as u16,
     base: GDT_ADDRESS as u32,
@@ -126,12 +258,76 @@ pub fn init() {
     unsafe {
         let gdt_dest = GDT_ADDRESS as *mut Gdt;
         core::ptr::write_volatile(gdt_dest, GDT);
+        (*gdt_dest).entries[selectors::TSS as usize / 8] = tss_entry(addr_of!(TSS));
+        (*gdt_dest).entries[selectors::DOUBLE_FAULT_TSS as usize / 8] =
+            tss_entry(addr_of!(DOUBLE_FAULT_TSS));
 
         GDT_PTR.base = GDT_ADDRESS as u32;
         GDT_PTR.limit = (core::mem::size_of::<Gdt>() - 1) as u16;
 
         load_gdt(&GDT_PTR);
         reload_segments();
+        load_tss();
+    }
+}
+
+fn tss_entry(tss: *const TaskStateSegment) -> GdtEntry {
+    GdtEntry::new(
+        tss as u32,
+        (core::mem::size_of::<TaskStateSegment>() - 1) as u32,
+        access::PRESENT | access::RING_0 | access::TSS_32,
+        0,
+    )
+}
+
+unsafe fn load_tss() {
+    asm!(
+        "ltr {0:x}",
+        in(reg) selectors::TSS,
+        options(nostack, preserves_flags)
+    );
+}
+
+pub fn task_register() -> u16 {
+    let selector: u16;
+    unsafe {
+        asm!(
+            "str {0:x}",
+            out(reg) selector,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    selector
+}
+
+pub fn kernel_stack() -> u32 {
+    unsafe { (*addr_of!(TSS)).esp0 }
+}
+
+pub fn interrupted_task() -> TaskStateSegment {
+    unsafe { *addr_of!(TSS) }
+}
+
+pub fn set_double_fault_task(entry: u32, page_directory: u32) {
+    unsafe {
+        let tss = &mut *addr_of_mut!(DOUBLE_FAULT_TSS);
+        let stack = addr_of!(DOUBLE_FAULT_STACK) as u32;
+        tss.eip = entry;
+        tss.esp = stack + DOUBLE_FAULT_STACK_SIZE as u32;
+        tss.cr3 = page_directory;
+        tss.eflags = TASK_EFLAGS;
+        tss.cs = selectors::KERNEL_CODE as u32;
+        tss.ss = selectors::KERNEL_STACK as u32;
+        tss.ds = selectors::KERNEL_DATA as u32;
+        tss.es = selectors::KERNEL_DATA as u32;
+        tss.fs = selectors::KERNEL_DATA as u32;
+        tss.gs = selectors::KERNEL_DATA as u32;
+    }
+}
+
+pub fn set_kernel_stack(esp0: u32) {
+    unsafe {
+        (*addr_of_mut!(TSS)).esp0 = esp0;
     }
 }
 
@@ -170,10 +366,23 @@ pub fn get_gdt() -> &'static Gdt {
 }
 
 pub fn get_gdt_info() -> (u32, u16) {
-    unsafe { (GDT_PTR.base, GDT_PTR.limit) }
+    let mut gdtr = GdtPointer { limit: 0, base: 0 };
+    unsafe {
+        asm!(
+            "sgdt [{}]",
+            in(reg) &mut gdtr,
+            options(nostack, preserves_flags)
+        );
+    }
+    (gdtr.base, gdtr.limit)
+}
+
+pub fn is_loaded() -> bool {
+    let (base, limit) = get_gdt_info();
+    base == GDT_ADDRESS as u32 && limit as usize == core::mem::size_of::<Gdt>() - 1
 }
 
-pub fn describe_entry(index: usize) -> (&'static str, u8, u8) {
+pub fn describe_entry(index: usize) -> (&'static str, GdtEntry) {
     let names = [
         "Null",
         "Kernel Code",
@@ -182,16 +391,14 @@ pub fn describe_entry(index: usize) -> (&'static str, u8, u8) {
         "User Code",
         "User Data",
         "User Stack",
+        "TSS",
+        "DF TSS",
     ];
 
     let gdt = get_gdt();
     if index < GDT_ENTRIES {
-        (
-            names[index],
-            gdt.entries[index].access,
-            gdt.entries[index].granularity,
-        )
+        (names[index], gdt.entries[index])
     } else {
-        ("Invalid", 0, 0)
+        ("Invalid", GdtEntry::null())
     }
 }
@@ -0,0 +1,76 @@
+use crate::{cpuid, watchdog};
+use core::arch::asm;
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum IdlePolicy {
+    Halt = 0,
+    Spin = 1,
+    Mwait = 2,
+}
+
+impl IdlePolicy {
+    pub fn from_name(name: &str) -> Option<IdlePolicy> {
+        match name {
+            "hlt" => Some(IdlePolicy::Halt),
+            "spin" => Some(IdlePolicy::Spin),
+            "mwait" => Some(IdlePolicy::Mwait),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IdlePolicy::Halt => "hlt",
+            IdlePolicy::Spin => "spin",
+            IdlePolicy::Mwait => "mwait",
+        }
+    }
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(IdlePolicy::Halt as u8);
+
+static MONITOR_TARGET: AtomicU32 = AtomicU32::new(0);
+
+pub fn select_policy(requested: IdlePolicy, mwait_supported: bool) -> IdlePolicy {
+    match requested {
+        IdlePolicy::Mwait if !mwait_supported => IdlePolicy::Halt,
+        policy => policy,
+    }
+}
+
+pub fn set_policy(requested: IdlePolicy) -> IdlePolicy {
+    let policy = select_policy(requested, cpuid::has_monitor_mwait());
+    POLICY.store(policy as u8, Ordering::SeqCst);
+    policy
+}
+
+pub fn policy() -> IdlePolicy {
+    match POLICY.load(Ordering::SeqCst) {
+        1 => IdlePolicy::Spin,
+        2 => IdlePolicy::Mwait,
+        _ => IdlePolicy::Halt,
+    }
+}
+
+pub fn idle() {
+    watchdog::pet();
+
+    match policy() {
+        IdlePolicy::Halt => unsafe {
+            asm!("hlt", options(nomem, nostack));
+        },
+        IdlePolicy::Spin => core::hint::spin_loop(),
+        IdlePolicy::Mwait => unsafe {
+            asm!(
+                "monitor",
+                in("eax") MONITOR_TARGET.as_ptr(),
+                in("ecx") 0,
+                in("edx") 0,
+                options(nostack)
+            );
+            asm!("mwait", in("eax") 0, in("ecx") 0, options(nomem, nostack));
+        },
+    }
+}
@@ -0,0 +1,525 @@
+use crate::gdt::{self, selectors};
+use crate::kfs_lib::FixedBuffer;
+use crate::pic;
+use crate::printk::{self, LogLevel};
+use crate::theme::Role;
+use crate::{
+    apic, keyboard, klog, memory, mouse, panic, stack, stack_guard, syscall, task, timer, watchdog,
+};
+use core::arch::{asm, global_asm};
+use core::fmt::Write;
+use core::ptr::addr_of;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub const IDT_ENTRIES: usize = 256;
+
+mod gate {
+    pub const PRESENT: u8 = 1 << 7;
+    pub const RING_0: u8 = 0 << 5;
+    pub const RING_3: u8 = 3 << 5;
+    pub const TASK_32: u8 = 0x05;
+    pub const INTERRUPT_32: u8 = 0x0E;
+}
+
+mod page_fault {
+    pub const PRESENT: u32 = 1 << 0;
+    pub const WRITE: u32 = 1 << 1;
+    pub const USER: u32 = 1 << 2;
+    pub const RESERVED_WRITE: u32 = 1 << 3;
+    pub const INSTRUCTION_FETCH: u32 = 1 << 4;
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct IdtEntry {
+    offset_low: u16,
+    selector: u16,
+    zero: u8,
+    type_attr: u8,
+    offset_high: u16,
+}
+
+impl IdtEntry {
+    pub const fn missing() -> IdtEntry {
+        IdtEntry {
+            offset_low: 0,
+            selector: 0,
+            zero: 0,
+            type_attr: 0,
+            offset_high: 0,
+        }
+    }
+
+    pub fn new(handler: u32, selector: u16, type_attr: u8) -> IdtEntry {
+        IdtEntry {
+            offset_low: (handler & 0xFFFF) as u16,
+            selector,
+            zero: 0,
+            type_attr,
+            offset_high: ((handler >> 16) & 0xFFFF) as u16,
+        }
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.type_attr & gate::PRESENT != 0
+    }
+}
+
+#[repr(C, packed)]
+pub struct IdtPointer {
+    pub limit: u16,
+    pub base: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptStackFrame {
+    pub eip: u32,
+    pub cs: u32,
+    pub eflags: u32,
+}
+
+type Handler = extern "x86-interrupt" fn(InterruptStackFrame);
+type HandlerWithErrorCode = extern "x86-interrupt" fn(InterruptStackFrame, u32);
+
+static mut IDT: [IdtEntry; IDT_ENTRIES] = [IdtEntry::missing(); IDT_ENTRIES];
+
+static mut IDT_PTR: IdtPointer = IdtPointer { limit: 0, base: 0 };
+
+const DEFAULT_STUB_SIZE: u32 = 16;
+
+const EXCEPTION_VECTORS: u8 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpuriousSource {
+    Master,
+    Slave,
+    Apic,
+}
+
+pub const SPURIOUS_SOURCES: [SpuriousSource; 3] = [
+    SpuriousSource::Master,
+    SpuriousSource::Slave,
+    SpuriousSource::Apic,
+];
+
+impl SpuriousSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            SpuriousSource::Master => "IRQ7",
+            SpuriousSource::Slave => "IRQ15",
+            SpuriousSource::Apic => "APIC",
+        }
+    }
+}
+
+static UNHANDLED_COUNTS: [AtomicU32; IDT_ENTRIES] = [const { AtomicU32::new(0) }; IDT_ENTRIES];
+static SPURIOUS_COUNTS: [AtomicU32; SPURIOUS_SOURCES.len()] =
+    [const { AtomicU32::new(0) }; SPURIOUS_SOURCES.len()];
+
+global_asm!(
+    ".p2align 4",
+    ".global default_interrupt_stubs",
+    "default_interrupt_stubs:",
+    ".set default_vector, 0",
+    ".rept {entries}",
+    ".p2align 4",
+    ".if default_vector == 8 || (default_vector >= 10 && default_vector <= 14) || default_vector == 17 || default_vector == 21 || default_vector == 29 || default_vector == 30",
+    "add esp, 4",
+    ".endif",
+    "push offset default_vector",
+    "jmp default_interrupt_common",
+    ".set default_vector, default_vector + 1",
+    ".endr",
+    "default_interrupt_common:",
+    "pushad",
+    "cld",
+    "mov eax, [esp + 32]",
+    "lea ecx, [esp + 36]",
+    "mov ebx, esp",
+    "and esp, -16",
+    "sub esp, 8",
+    "push ecx",
+    "push eax",
+    "call {handler}",
+    "mov esp, ebx",
+    "popad",
+    "add esp, 4",
+    "iretd",
+    entries = const IDT_ENTRIES,
+    handler = sym default_interrupt_handler,
+);
+
+extern "C" {
+    fn default_interrupt_stubs();
+}
+
+pub fn set_gate(vector: u8, handler: u32) {
+    let type_attr = gate::PRESENT | gate::RING_0 | gate::INTERRUPT_32;
+    unsafe {
+        IDT[vector as usize] = IdtEntry::new(handler, selectors::KERNEL_CODE, type_attr);
+    }
+}
+
+pub fn set_user_gate(vector: u8, handler: u32) {
+    let type_attr = gate::PRESENT | gate::RING_3 | gate::INTERRUPT_32;
+    unsafe {
+        IDT[vector as usize] = IdtEntry::new(handler, selectors::KERNEL_CODE, type_attr);
+    }
+}
+
+pub fn set_task_gate(vector: u8, selector: u16) {
+    let type_attr = gate::PRESENT | gate::RING_0 | gate::TASK_32;
+    unsafe {
+        IDT[vector as usize] = IdtEntry::new(0, selector, type_attr);
+    }
+}
+
+fn set_handler(vector: u8, handler: Handler) {
+    set_gate(vector, handler as usize as u32);
+}
+
+fn set_handler_with_error_code(vector: u8, handler: HandlerWithErrorCode) {
+    set_gate(vector, handler as usize as u32);
+}
+
+fn set_default_handlers() {
+    let base = default_interrupt_stubs as *const () as u32;
+    for vector in 0..IDT_ENTRIES {
+        set_gate(vector as u8, base + vector as u32 * DEFAULT_STUB_SIZE);
+    }
+}
+
+pub fn init() {
+    set_default_handlers();
+
+    set_handler(0, divide_error_handler);
+    set_handler(1, debug_handler);
+    set_handler(2, nmi_handler);
+    set_handler(3, breakpoint_handler);
+    set_handler(4, overflow_handler);
+    set_handler(5, bound_range_handler);
+    set_handler(6, invalid_opcode_handler);
+    set_handler(7, device_not_available_handler);
+    gdt::set_double_fault_task(
+        double_fault_task as *const () as u32,
+        memory::paging::get_cr3(),
+    );
+    set_task_gate(8, selectors::DOUBLE_FAULT_TSS);
+    set_handler_with_error_code(10, invalid_tss_handler);
+    set_handler_with_error_code(11, segment_not_present_handler);
+    set_handler_with_error_code(12, stack_segment_handler);
+    set_handler_with_error_code(13, general_protection_handler);
+    set_handler_with_error_code(14, page_fault_handler);
+    set_handler(16, x87_floating_point_handler);
+    set_handler_with_error_code(17, alignment_check_handler);
+    set_handler(18, machine_check_handler);
+    set_handler(19, simd_floating_point_handler);
+
+    set_handler(pic::PIC1_OFFSET + pic::IRQ_TIMER, timer_interrupt_handler);
+    set_handler(
+        pic::PIC1_OFFSET + pic::IRQ_KEYBOARD,
+        keyboard_interrupt_handler,
+    );
+    set_handler(pic::PIC1_OFFSET + pic::IRQ_MOUSE, mouse_interrupt_handler);
+    set_handler(apic::SPURIOUS_VECTOR, spurious_interrupt_handler);
+    set_user_gate(syscall::VECTOR, syscall::entry_address());
+
+    unsafe {
+        IDT_PTR.limit = (core::mem::size_of::<[IdtEntry; IDT_ENTRIES]>() - 1) as u16;
+        IDT_PTR.base = addr_of!(IDT) as u32;
+
+        asm!(
+            "lidt [{}]",
+            in(reg) addr_of!(IDT_PTR),
+            options(nostack, preserves_flags)
+        );
+    }
+}
+
+pub fn get_idt_info() -> (u32, u16) {
+    unsafe { (IDT_PTR.base, IDT_PTR.limit) }
+}
+
+pub fn unhandled_count(vector: u8) -> u32 {
+    UNHANDLED_COUNTS[vector as usize].load(Ordering::SeqCst)
+}
+
+pub fn unhandled_total() -> u32 {
+    UNHANDLED_COUNTS
+        .iter()
+        .map(|count| count.load(Ordering::SeqCst))
+        .sum()
+}
+
+pub fn spurious_count(source: SpuriousSource) -> u32 {
+    SPURIOUS_COUNTS[source as usize].load(Ordering::SeqCst)
+}
+
+pub fn enable_interrupts() {
+    unsafe {
+        asm!("sti", options(nomem, nostack));
+    }
+}
+
+pub fn disable_interrupts() {
+    unsafe {
+        asm!("cli", options(nomem, nostack));
+    }
+}
+
+pub fn interrupts_enabled() -> bool {
+    let eflags: u32;
+    unsafe {
+        asm!("pushfd", "pop {}", out(reg) eflags, options(nomem, preserves_flags));
+    }
+    eflags & (1 << 9) != 0
+}
+
+pub fn print_exception(name: &str, frame: &InterruptStackFrame, error_code: Option<u32>) {
+    printk::apply_safe(Role::Error);
+    crate::printkln_safe!("\nEXCEPTION: {}", name);
+    printk::reset_color_safe();
+
+    crate::printkln_safe!(
+        "  EIP=0x{:08X}  CS=0x{:08X}  EFLAGS=0x{:08X}",
+        frame.eip,
+        frame.cs,
+        frame.eflags
+    );
+
+    if let Some(code) = error_code {
+        crate::printkln_safe!("  Error code: 0x{:08X}", code);
+    }
+}
+
+fn fatal_exception(name: &str, frame: &InterruptStackFrame, error_code: Option<u32>) -> ! {
+    print_exception(name, frame, error_code);
+    panic::panic(name)
+}
+
+extern "x86-interrupt" fn divide_error_handler(frame: InterruptStackFrame) {
+    fatal_exception("Divide Error", &frame, None);
+}
+
+extern "x86-interrupt" fn debug_handler(frame: InterruptStackFrame) {
+    print_exception("Debug", &frame, None);
+}
+
+extern "x86-interrupt" fn nmi_handler(frame: InterruptStackFrame) {
+    fatal_exception("Non-Maskable Interrupt", &frame, None);
+}
+
+extern "x86-interrupt" fn breakpoint_handler(frame: InterruptStackFrame) {
+    print_exception("Breakpoint", &frame, None);
+}
+
+extern "x86-interrupt" fn overflow_handler(frame: InterruptStackFrame) {
+    fatal_exception("Overflow", &frame, None);
+}
+
+extern "x86-interrupt" fn bound_range_handler(frame: InterruptStackFrame) {
+    fatal_exception("Bound Range Exceeded", &frame, None);
+}
+
+extern "x86-interrupt" fn invalid_opcode_handler(frame: InterruptStackFrame) {
+    fatal_exception("Invalid Opcode", &frame, None);
+}
+
+extern "x86-interrupt" fn device_not_available_handler(frame: InterruptStackFrame) {
+    fatal_exception("Device Not Available", &frame, None);
+}
+
+extern "C" fn double_fault_task() -> ! {
+    let interrupted = gdt::interrupted_task();
+    let frame = InterruptStackFrame {
+        eip: interrupted.eip,
+        cs: interrupted.cs,
+        eflags: interrupted.eflags,
+    };
+    let address = memory::paging::get_cr2();
+
+    print_exception("Double Fault", &frame, Some(0));
+    crate::printkln_safe!("  ESP=0x{:08X}  CR2=0x{:08X}", { interrupted.esp }, address);
+    if report_stack_overflow(address) {
+        panic::panic("Stack Overflow")
+    }
+    panic::panic("Double Fault")
+}
+
+pub fn report_stack_overflow(address: u32) -> bool {
+    match stack_guard::guard_owner(address as usize) {
+        Some(owner) => {
+            printk::apply_safe(Role::Error);
+            crate::printkln_safe!("STACK OVERFLOW: {} stack hit its guard page", owner);
+            printk::reset_color_safe();
+            true
+        }
+        None => false,
+    }
+}
+
+extern "x86-interrupt" fn invalid_tss_handler(frame: InterruptStackFrame, error_code: u32) {
+    fatal_exception("Invalid TSS", &frame, Some(error_code));
+}
+
+extern "x86-interrupt" fn segment_not_present_handler(frame: InterruptStackFrame, error_code: u32) {
+    fatal_exception("Segment Not Present", &frame, Some(error_code));
+}
+
+extern "x86-interrupt" fn stack_segment_handler(frame: InterruptStackFrame, error_code: u32) {
+    fatal_exception("Stack-Segment Fault", &frame, Some(error_code));
+}
+
+extern "x86-interrupt" fn general_protection_handler(frame: InterruptStackFrame, error_code: u32) {
+    fatal_exception("General Protection Fault", &frame, Some(error_code));
+}
+
+pub fn describe_page_fault(error_code: u32) -> [&'static str; 5] {
+    let flag = |bit: u32, set: &'static str, clear: &'static str| {
+        if error_code & bit != 0 {
+            set
+        } else {
+            clear
+        }
+    };
+
+    [
+        flag(
+            page_fault::PRESENT,
+            "protection violation",
+            "page not present",
+        ),
+        flag(page_fault::WRITE, "write", "read"),
+        flag(page_fault::USER, "user mode", "kernel mode"),
+        flag(page_fault::RESERVED_WRITE, "reserved bit set", ""),
+        flag(page_fault::INSTRUCTION_FETCH, "instruction fetch", ""),
+    ]
+}
+
+pub fn fault_region(address: u32) -> Option<&'static str> {
+    let address = address as usize;
+    let stack_bottom = stack::get_stack_bottom() as usize;
+    let stack_top = stack::get_stack_top() as usize;
+    let heap_end = memory::KERNEL_HEAP_START + memory::KERNEL_HEAP_SIZE;
+
+    if address < memory::PAGE_SIZE {
+        Some("null page")
+    } else if (stack_bottom..stack_top).contains(&address) {
+        Some("kernel stack")
+    } else if stack_guard::guard_owner(address).is_some() {
+        Some("stack guard page")
+    } else if (memory::KERNEL_HEAP_START..heap_end).contains(&address) {
+        Some("kernel heap")
+    } else if (heap_end..heap_end + memory::PAGE_SIZE).contains(&address) {
+        Some("just past the kernel heap")
+    } else if task::stack_owner(address).is_some() {
+        Some("task stack")
+    } else {
+        None
+    }
+}
+
+extern "x86-interrupt" fn page_fault_handler(frame: InterruptStackFrame, error_code: u32) {
+    let address = memory::paging::get_cr2();
+
+    print_exception("Page Fault", &frame, Some(error_code));
+    crate::printkln_safe!("  Faulting address (CR2): 0x{:08X}", address);
+
+    crate::printk_safe!("  Cause:");
+    for flag in describe_page_fault(error_code) {
+        if !flag.is_empty() {
+            crate::printk_safe!(" {}", flag);
+        }
+    }
+    crate::printkln_safe!();
+
+    if let Some(region) = fault_region(address) {
+        crate::printkln_safe!("  Region: {}", region);
+    }
+    if report_stack_overflow(address) {
+        panic::panic("Stack Overflow")
+    }
+    panic::panic("Page Fault")
+}
+
+extern "x86-interrupt" fn x87_floating_point_handler(frame: InterruptStackFrame) {
+    fatal_exception("x87 Floating-Point Exception", &frame, None);
+}
+
+extern "x86-interrupt" fn alignment_check_handler(frame: InterruptStackFrame, error_code: u32) {
+    fatal_exception("Alignment Check", &frame, Some(error_code));
+}
+
+extern "x86-interrupt" fn machine_check_handler(frame: InterruptStackFrame) {
+    fatal_exception("Machine Check", &frame, None);
+}
+
+extern "x86-interrupt" fn simd_floating_point_handler(frame: InterruptStackFrame) {
+    fatal_exception("SIMD Floating-Point Exception", &frame, None);
+}
+
+extern "x86-interrupt" fn timer_interrupt_handler(frame: InterruptStackFrame) {
+    timer::on_tick();
+    watchdog::on_tick(&frame);
+    apic::end_of_interrupt(pic::IRQ_TIMER);
+}
+
+extern "x86-interrupt" fn keyboard_interrupt_handler(_frame: InterruptStackFrame) {
+    keyboard::handle_interrupt();
+    apic::end_of_interrupt(pic::IRQ_KEYBOARD);
+}
+
+extern "x86-interrupt" fn mouse_interrupt_handler(_frame: InterruptStackFrame) {
+    mouse::handle_interrupt();
+    apic::end_of_interrupt(pic::IRQ_MOUSE);
+}
+
+extern "x86-interrupt" fn spurious_interrupt_handler(_frame: InterruptStackFrame) {
+    SPURIOUS_COUNTS[SpuriousSource::Apic as usize].fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn default_interrupt_handler(vector: u32, frame: &InterruptStackFrame) {
+    let vector = vector as u8;
+    let irq = pic::irq_for_vector(vector);
+
+    if let Some(irq) = irq.filter(|&irq| !apic::is_active() && pic::is_spurious(irq)) {
+        let source = if irq == pic::IRQ_SPURIOUS_SLAVE {
+            SpuriousSource::Slave
+        } else {
+            SpuriousSource::Master
+        };
+        SPURIOUS_COUNTS[source as usize].fetch_add(1, Ordering::SeqCst);
+        pic::acknowledge_spurious(irq);
+        return;
+    }
+
+    UNHANDLED_COUNTS[vector as usize].fetch_add(1, Ordering::SeqCst);
+    klog::record_at(
+        LogLevel::Warn,
+        format_args!(
+            "unhandled interrupt vector {} at EIP=0x{:08X} CS=0x{:08X} EFLAGS=0x{:08X}",
+            vector, frame.eip, frame.cs, frame.eflags
+        ),
+    );
+
+    if vector < EXCEPTION_VECTORS {
+        let mut message = FixedBuffer::<40>::new();
+        let _ = write!(message, "Unhandled Exception (vector {})", vector);
+        fatal_exception(message.as_str(), frame, None);
+    }
+
+    printk::apply_safe(Role::Warning);
+    crate::printkln_safe!(
+        "UNHANDLED INTERRUPT: vector {} at EIP=0x{:08X}",
+        vector,
+        frame.eip
+    );
+    printk::reset_color_safe();
+
+    match irq {
+        Some(irq) => apic::end_of_interrupt(irq),
+        None if apic::is_active() => apic::send_eoi(),
+        None => {}
+    }
+}
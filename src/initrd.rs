@@ -0,0 +1,70 @@
+use crate::memory::KERNEL_HEAP_START;
+use crate::panic;
+use crate::tar::TarFs;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+const MULTIBOOT_FLAG_MODS: u32 = 1 << 3;
+
+#[repr(C, packed)]
+struct MultibootModule {
+    mod_start: u32,
+    mod_end: u32,
+    cmdline: u32,
+    reserved: u32,
+}
+
+static INITRD_START: AtomicUsize = AtomicUsize::new(0);
+static INITRD_LEN: AtomicUsize = AtomicUsize::new(0);
+
+pub fn find_module(multiboot_info: u32) -> Option<(usize, usize)> {
+    if multiboot_info == 0 {
+        return None;
+    }
+
+    unsafe {
+        let info = multiboot_info as *const u32;
+        let flags = *info;
+        let mods_count = *info.add(5);
+        let mods_addr = *info.add(6);
+
+        if flags & MULTIBOOT_FLAG_MODS == 0 || mods_count == 0 {
+            return None;
+        }
+
+        let module = &*(mods_addr as *const MultibootModule);
+        let start = module.mod_start as usize;
+        let end = module.mod_end as usize;
+        if end <= start {
+            return None;
+        }
+        Some((start, end - start))
+    }
+}
+
+pub fn init(multiboot_info: u32) -> bool {
+    let (start, len) = match find_module(multiboot_info) {
+        Some(module) => module,
+        None => return false,
+    };
+
+    if start + len > KERNEL_HEAP_START {
+        panic::warn("initrd: module overlaps the kernel heap, ignoring it");
+        return false;
+    }
+
+    INITRD_START.store(start, Ordering::SeqCst);
+    INITRD_LEN.store(len, Ordering::SeqCst);
+    true
+}
+
+pub fn fs() -> Option<TarFs> {
+    let len = INITRD_LEN.load(Ordering::SeqCst);
+    if len == 0 {
+        return None;
+    }
+
+    let start = INITRD_START.load(Ordering::SeqCst);
+    Some(TarFs::new(unsafe {
+        core::slice::from_raw_parts(start as *const u8, len)
+    }))
+}
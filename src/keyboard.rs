@@ -0,0 +1,787 @@
+use crate::io::{inb, outb};
+use crate::printk::{print, println, reset_color};
+use crate::ringbuf::RingBuffer;
+use crate::sync::{without_interrupts, IrqSpinlock};
+use crate::theme::{self, Role};
+use crate::{klog, pic, power, stack, timer};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+
+pub const DATA_PORT: u16 = 0x60;
+pub const STATUS_PORT: u16 = 0x64;
+
+pub const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+pub const STATUS_AUX_DATA: u8 = 1 << 5;
+
+pub const BUFFER_SIZE: usize = 64;
+
+const COMMAND_SET_TYPEMATIC: u8 = 0xF3;
+pub const RESPONSE_ACK: u8 = 0xFA;
+pub const RESPONSE_RESEND: u8 = 0xFE;
+pub const CONTROLLER_TIMEOUT: usize = 100_000;
+pub const COMMAND_ATTEMPTS: usize = 3;
+
+pub const TYPEMATIC_MAX_DELAY: u8 = 0x03;
+pub const TYPEMATIC_MAX_RATE: u8 = 0x1F;
+const TYPEMATIC_DEFAULT_DELAY: u8 = 0x01;
+const TYPEMATIC_DEFAULT_RATE: u8 = 0x0B;
+
+static TYPEMATIC_DELAY: AtomicU8 = AtomicU8::new(TYPEMATIC_DEFAULT_DELAY);
+static TYPEMATIC_RATE: AtomicU8 = AtomicU8::new(TYPEMATIC_DEFAULT_RATE);
+
+static RAW_MODE: AtomicBool = AtomicBool::new(false);
+static SOFT_REPEAT: AtomicBool = AtomicBool::new(false);
+
+const OVERFLOW_REPORT_MS: u32 = 1000;
+
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+static REPORTED_DROPS: AtomicU32 = AtomicU32::new(0);
+static LAST_OVERFLOW_REPORT: AtomicU32 = AtomicU32::new(0);
+
+const REBOOT_CONFIRM_MS: u32 = 2000;
+const SYSRQ_LOG_LINES: usize = 16;
+
+const SCANCODE_EXTENDED: u8 = 0xE0;
+const SCANCODE_RELEASED: u8 = 0x80;
+
+pub mod scancodes {
+    pub const ESCAPE: u8 = 0x01;
+    pub const BACKSPACE: u8 = 0x0E;
+    pub const TAB: u8 = 0x0F;
+    pub const ENTER: u8 = 0x1C;
+    pub const CTRL: u8 = 0x1D;
+    pub const LEFT_SHIFT: u8 = 0x2A;
+    pub const RIGHT_SHIFT: u8 = 0x36;
+    pub const ALT: u8 = 0x38;
+    pub const CAPS_LOCK: u8 = 0x3A;
+    pub const NUM_LOCK: u8 = 0x45;
+    pub const HOME: u8 = 0x47;
+    pub const UP: u8 = 0x48;
+    pub const PAGE_UP: u8 = 0x49;
+    pub const LEFT: u8 = 0x4B;
+    pub const RIGHT: u8 = 0x4D;
+    pub const END: u8 = 0x4F;
+    pub const DOWN: u8 = 0x50;
+    pub const PAGE_DOWN: u8 = 0x51;
+    pub const DELETE: u8 = 0x53;
+    pub const PRINT_SCREEN: u8 = 0x37;
+    pub const SYSRQ: u8 = 0x54;
+}
+
+const SCANCODE_MAP: [u8; 58] = [
+    0, 0, b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'0', b'-', b'=', 0, 0, b'q', b'w',
+    b'e', b'r', b't', b'y', b'u', b'i', b'o', b'p', b'[', b']', 0, 0, b'a', b's', b'd', b'f', b'g',
+    b'h', b'j', b'k', b'l', b';', b'\'', b'`', 0, b'\\', b'z', b'x', b'c', b'v', b'b', b'n', b'm',
+    b',', b'.', b'/', 0, b'*', 0, b' ',
+];
+
+const SCANCODE_MAP_SHIFT: [u8; 58] = [
+    0, 0, b'!', b'@', b'#', b'$', b'%', b'^', b'&', b'*', b'(', b')', b'_', b'+', 0, 0, b'Q', b'W',
+    b'E', b'R', b'T', b'Y', b'U', b'I', b'O', b'P', b'{', b'}', 0, 0, b'A', b'S', b'D', b'F', b'G',
+    b'H', b'J', b'K', b'L', b':', b'"', b'~', 0, b'|', b'Z', b'X', b'C', b'V', b'B', b'N', b'M',
+    b'<', b'>', b'?', 0, b'*', 0, b' ',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    InvalidArgument,
+    Timeout,
+    ResendLimit,
+    Failed(u8),
+}
+
+impl CommandError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            CommandError::InvalidArgument => "invalid argument",
+            CommandError::Timeout => "keyboard did not respond",
+            CommandError::ResendLimit => "keyboard kept asking for a resend",
+            CommandError::Failed(_) => "keyboard reported an error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    PageUp,
+    PageDown,
+    Ctrl(char),
+    CtrlShift(char),
+    SwitchScreen(usize),
+}
+
+impl KeyEvent {
+    pub fn control_code(self) -> Option<u8> {
+        match self {
+            KeyEvent::Ctrl(c) if c.is_ascii_lowercase() => Some(c as u8 & 0x1F),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HeldKey {
+    code: u8,
+    extended: bool,
+    event: KeyEvent,
+}
+
+pub struct Keyboard<const N: usize = BUFFER_SIZE> {
+    buffer: RingBuffer<u8, N>,
+    shift_pressed: bool,
+    ctrl_pressed: bool,
+    alt_pressed: bool,
+    caps_lock: bool,
+    num_lock: bool,
+    extended: bool,
+    magic: MagicKeys,
+    held: Option<HeldKey>,
+    repeat_at: u32,
+}
+
+impl<const N: usize> Keyboard<N> {
+    pub const fn new() -> Keyboard<N> {
+        Keyboard {
+            buffer: RingBuffer::new(0),
+            shift_pressed: false,
+            ctrl_pressed: false,
+            alt_pressed: false,
+            caps_lock: false,
+            num_lock: false,
+            extended: false,
+            magic: MagicKeys::new(),
+            held: None,
+            repeat_at: 0,
+        }
+    }
+
+    pub const fn capacity() -> usize {
+        RingBuffer::<u8, N>::capacity()
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn add_to_buffer(&mut self, scancode: u8) -> bool {
+        self.buffer.push(scancode)
+    }
+
+    pub fn get_from_buffer(&mut self) -> Option<u8> {
+        self.buffer.pop()
+    }
+
+    /// Takes a scancode as it arrives from the controller. Magic key
+    /// combinations are recognised here rather than when the buffer is
+    /// read, so they still work while nothing is polling for input; the
+    /// scancodes that complete them are not queued.
+    pub fn receive(&mut self, scancode: u8) -> Option<MagicAction> {
+        let action = self.magic.feed(scancode);
+        if action.is_none() && !self.add_to_buffer(scancode) {
+            DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+        action
+    }
+
+    fn reset_modifiers(&mut self) {
+        self.shift_pressed = false;
+        self.ctrl_pressed = false;
+        self.alt_pressed = false;
+        self.extended = false;
+        self.held = None;
+    }
+
+    pub fn process_scancode(&mut self, scancode: u8) -> Option<KeyEvent> {
+        if scancode == SCANCODE_EXTENDED {
+            self.extended = true;
+            return None;
+        }
+
+        let extended = self.extended;
+        self.extended = false;
+
+        let released = scancode & SCANCODE_RELEASED != 0;
+        let code = scancode & !SCANCODE_RELEASED;
+
+        match code {
+            scancodes::LEFT_SHIFT | scancodes::RIGHT_SHIFT if !extended => {
+                self.shift_pressed = !released;
+                return None;
+            }
+            scancodes::CTRL => {
+                self.ctrl_pressed = !released;
+                return None;
+            }
+            scancodes::ALT => {
+                self.alt_pressed = !released;
+                return None;
+            }
+            scancodes::CAPS_LOCK => {
+                if !released {
+                    self.caps_lock = !self.caps_lock;
+                }
+                return None;
+            }
+            scancodes::NUM_LOCK if !extended => {
+                if !released {
+                    self.num_lock = !self.num_lock;
+                }
+                return None;
+            }
+            _ => {}
+        }
+
+        if released {
+            if self
+                .held
+                .is_some_and(|held| held.code == code && held.extended == extended)
+            {
+                self.held = None;
+            }
+            return None;
+        }
+
+        let event = self.translate(code, extended);
+        match event {
+            Some(KeyEvent::SwitchScreen(_)) | None => self.held = None,
+            Some(event) => self.hold(code, extended, event),
+        }
+        event
+    }
+
+    fn hold(&mut self, code: u8, extended: bool, event: KeyEvent) {
+        let held = HeldKey {
+            code,
+            extended,
+            event,
+        };
+        let (delay, rate) = get_typematic();
+        let wait = if self.held == Some(held) {
+            repeat_period_ms(rate) * 2
+        } else {
+            typematic_delay_ms(delay)
+        };
+
+        self.held = Some(held);
+        self.repeat_at = timer::ticks().wrapping_add(timer::ms_to_ticks(wait));
+    }
+
+    pub fn poll_repeat(&mut self, now: u32) -> Option<KeyEvent> {
+        let held = self.held?;
+        if (now.wrapping_sub(self.repeat_at) as i32) < 0 {
+            return None;
+        }
+
+        let (_, rate) = get_typematic();
+        self.repeat_at = now.wrapping_add(timer::ms_to_ticks(repeat_period_ms(rate)));
+        Some(held.event)
+    }
+
+    fn translate(&mut self, code: u8, extended: bool) -> Option<KeyEvent> {
+        if extended {
+            return match code {
+                scancodes::ENTER => Some(KeyEvent::Enter),
+                scancodes::UP => Some(KeyEvent::Up),
+                scancodes::DOWN => Some(KeyEvent::Down),
+                scancodes::LEFT => Some(KeyEvent::Left),
+                scancodes::RIGHT => Some(KeyEvent::Right),
+                scancodes::HOME => Some(KeyEvent::Home),
+                scancodes::END => Some(KeyEvent::End),
+                scancodes::DELETE => Some(KeyEvent::Delete),
+                scancodes::PAGE_UP => Some(KeyEvent::PageUp),
+                scancodes::PAGE_DOWN => Some(KeyEvent::PageDown),
+                _ => None,
+            };
+        }
+
+        match code {
+            scancodes::ESCAPE => Some(KeyEvent::Escape),
+            scancodes::BACKSPACE => Some(KeyEvent::Backspace),
+            scancodes::TAB => Some(KeyEvent::Tab),
+            scancodes::ENTER => Some(KeyEvent::Enter),
+            _ => self.handle_character(code),
+        }
+    }
+
+    fn handle_character(&mut self, code: u8) -> Option<KeyEvent> {
+        if self.alt_pressed && !self.ctrl_pressed {
+            if let Some(digit @ '1'..='9') = scancode_to_char(code, false, false) {
+                return Some(KeyEvent::SwitchScreen(digit as usize - '1' as usize));
+            }
+        }
+
+        let c = scancode_to_char(code, self.shift_pressed, self.caps_lock)?;
+
+        if self.ctrl_pressed {
+            let c = c.to_ascii_lowercase();
+            return c.is_ascii_alphabetic().then_some(if self.shift_pressed {
+                KeyEvent::CtrlShift(c)
+            } else {
+                KeyEvent::Ctrl(c)
+            });
+        }
+
+        Some(KeyEvent::Char(c))
+    }
+}
+
+fn scancode_to_char(code: u8, shift: bool, caps_lock: bool) -> Option<char> {
+    let index = code as usize;
+    if index >= SCANCODE_MAP.len() {
+        return None;
+    }
+
+    let byte = if shift {
+        SCANCODE_MAP_SHIFT[index]
+    } else {
+        SCANCODE_MAP[index]
+    };
+
+    if byte == 0 {
+        return None;
+    }
+
+    let c = byte as char;
+    if caps_lock && c.is_ascii_alphabetic() {
+        if shift {
+            Some(c.to_ascii_lowercase())
+        } else {
+            Some(c.to_ascii_uppercase())
+        }
+    } else {
+        Some(c)
+    }
+}
+
+/// Tracks just enough modifier state to spot Ctrl+Alt+Del and Alt+SysRq
+/// from the interrupt handler, independently of when the buffered
+/// scancodes are turned into key events.
+struct MagicKeys {
+    ctrl: bool,
+    alt: bool,
+    extended: bool,
+    sysrq_pending: bool,
+    reboot_requested_at: Option<u32>,
+}
+
+impl MagicKeys {
+    const fn new() -> MagicKeys {
+        MagicKeys {
+            ctrl: false,
+            alt: false,
+            extended: false,
+            sysrq_pending: false,
+            reboot_requested_at: None,
+        }
+    }
+
+    fn feed(&mut self, scancode: u8) -> Option<MagicAction> {
+        if scancode == SCANCODE_EXTENDED {
+            self.extended = true;
+            return None;
+        }
+
+        let extended = self.extended;
+        self.extended = false;
+
+        let released = scancode & SCANCODE_RELEASED != 0;
+        let code = scancode & !SCANCODE_RELEASED;
+        match code {
+            scancodes::CTRL => self.ctrl = !released,
+            scancodes::ALT => self.alt = !released,
+            _ if !released => return self.handle_key(code, extended),
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_key(&mut self, code: u8, extended: bool) -> Option<MagicAction> {
+        if self.ctrl && self.alt && code == scancodes::DELETE {
+            return Some(self.handle_ctrl_alt_del());
+        }
+
+        let sysrq = (extended && code == scancodes::PRINT_SCREEN)
+            || (!extended && code == scancodes::SYSRQ);
+        if self.alt && sysrq {
+            self.sysrq_pending = true;
+            return Some(MagicAction::ArmSysRq);
+        }
+
+        if self.sysrq_pending && !extended {
+            self.sysrq_pending = false;
+            return scancode_to_char(code, false, false).map(MagicAction::SysRq);
+        }
+
+        None
+    }
+
+    fn handle_ctrl_alt_del(&mut self) -> MagicAction {
+        let now = timer::ticks();
+
+        match self.reboot_requested_at {
+            Some(requested_at)
+                if now.wrapping_sub(requested_at) <= timer::ms_to_ticks(REBOOT_CONFIRM_MS) =>
+            {
+                MagicAction::Reboot
+            }
+            _ => {
+                self.reboot_requested_at = Some(now);
+                MagicAction::ConfirmReboot
+            }
+        }
+    }
+}
+
+fn handle_sysrq(key: char) {
+    theme::apply(Role::Warning);
+    print("\nSysRq: ");
+    reset_color();
+
+    match key {
+        'b' => {
+            println("Rebooting");
+            power::reboot();
+        }
+        'o' => {
+            println("Powering off");
+            power::shutdown();
+        }
+        's' => {
+            println("Stack trace");
+            stack::print_stack_trace();
+        }
+        'm' => {
+            println("Memory information");
+            crate::print_memory_info();
+        }
+        'l' => {
+            println("Kernel log");
+            klog::print_tail(SYSRQ_LOG_LINES);
+        }
+        _ => println("b=reboot o=poweroff s=stack-trace m=memory l=log"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MagicAction {
+    ArmSysRq,
+    ConfirmReboot,
+    Reboot,
+    SysRq(char),
+}
+
+impl MagicAction {
+    pub fn run(self) {
+        match self {
+            MagicAction::ArmSysRq => {}
+            MagicAction::ConfirmReboot => {
+                theme::apply(Role::Warning);
+                println("\nPress Ctrl+Alt+Del again within 2 seconds to reboot");
+                reset_color();
+            }
+            MagicAction::Reboot => {
+                println("\nRebooting...");
+                power::reboot();
+            }
+            MagicAction::SysRq(key) => handle_sysrq(key),
+        }
+    }
+}
+
+static KEYBOARD: IrqSpinlock<Keyboard> = IrqSpinlock::new(Keyboard::new());
+
+pub fn init() {
+    unsafe {
+        while inb(STATUS_PORT) & STATUS_OUTPUT_FULL != 0 {
+            inb(DATA_PORT);
+        }
+    }
+
+    pic::unmask_irq(pic::IRQ_KEYBOARD);
+}
+
+pub fn handle_interrupt() {
+    unsafe {
+        let status = inb(STATUS_PORT);
+        if status & STATUS_OUTPUT_FULL == 0 {
+            return;
+        }
+
+        let data = inb(DATA_PORT);
+        if status & STATUS_AUX_DATA == 0 {
+            receive_scancode(data);
+        }
+    }
+}
+
+pub fn receive_scancode(scancode: u8) {
+    let action = KEYBOARD.lock().receive(scancode);
+    if let Some(action) = action {
+        action.run();
+    }
+}
+
+pub fn set_raw_mode(enabled: bool) -> bool {
+    let previous = RAW_MODE.swap(enabled, Ordering::SeqCst);
+    if previous != enabled {
+        KEYBOARD.lock().reset_modifiers();
+    }
+    previous
+}
+
+pub fn is_raw_mode() -> bool {
+    RAW_MODE.load(Ordering::SeqCst)
+}
+
+pub fn read_raw() -> Option<u8> {
+    KEYBOARD.lock().get_from_buffer()
+}
+
+pub fn is_locked() -> bool {
+    KEYBOARD.is_locked()
+}
+
+pub fn lock_state() -> (bool, bool) {
+    let keyboard = KEYBOARD.lock();
+    (keyboard.caps_lock, keyboard.num_lock)
+}
+
+pub fn is_release(scancode: u8) -> bool {
+    scancode != SCANCODE_EXTENDED && scancode & SCANCODE_RELEASED != 0
+}
+
+pub fn is_extended_prefix(scancode: u8) -> bool {
+    scancode == SCANCODE_EXTENDED
+}
+
+pub fn dropped_scancodes() -> u32 {
+    DROPPED.load(Ordering::SeqCst)
+}
+
+pub fn buffered() -> usize {
+    KEYBOARD.lock().buffered()
+}
+
+fn report_overflow() {
+    let dropped = dropped_scancodes();
+    let reported = REPORTED_DROPS.load(Ordering::SeqCst);
+    if dropped == reported {
+        return;
+    }
+
+    let now = timer::ticks();
+    let last = LAST_OVERFLOW_REPORT.load(Ordering::SeqCst);
+    if reported != 0 && now.wrapping_sub(last) < timer::ms_to_ticks(OVERFLOW_REPORT_MS) {
+        return;
+    }
+
+    REPORTED_DROPS.store(dropped, Ordering::SeqCst);
+    LAST_OVERFLOW_REPORT.store(now, Ordering::SeqCst);
+    crate::pr_warn!(
+        "keyboard buffer overflow, {} scancodes dropped",
+        dropped.wrapping_sub(reported)
+    );
+}
+
+pub fn read_event() -> Option<KeyEvent> {
+    report_overflow();
+    if is_raw_mode() {
+        return None;
+    }
+
+    loop {
+        let mut keyboard = KEYBOARD.lock();
+        let Some(scancode) = keyboard.get_from_buffer() else {
+            break;
+        };
+        let event = keyboard.process_scancode(scancode);
+        drop(keyboard);
+
+        if event.is_some() {
+            return event;
+        }
+    }
+
+    if is_soft_repeat() {
+        KEYBOARD.lock().poll_repeat(timer::ticks())
+    } else {
+        None
+    }
+}
+
+pub fn set_soft_repeat(enabled: bool) -> bool {
+    SOFT_REPEAT.swap(enabled, Ordering::SeqCst)
+}
+
+pub fn is_soft_repeat() -> bool {
+    SOFT_REPEAT.load(Ordering::SeqCst)
+}
+
+pub fn wait_for_event() -> KeyEvent {
+    loop {
+        if let Some(event) = read_event() {
+            return event;
+        }
+        crate::task::yield_now();
+        crate::idle::idle();
+    }
+}
+
+pub fn wait_for_char() -> char {
+    loop {
+        if let Some(c) = wait_for_char_timeout(u32::MAX) {
+            return c;
+        }
+    }
+}
+
+pub fn wait_for_char_timeout(ms: u32) -> Option<char> {
+    let start = timer::ticks();
+    let timeout = timer::ms_to_ticks(ms);
+    loop {
+        while let Some(event) = read_event() {
+            if let KeyEvent::Char(c) = event {
+                return Some(c);
+            }
+        }
+        if timer::ticks().wrapping_sub(start) >= timeout {
+            return None;
+        }
+        crate::task::yield_now();
+        crate::idle::idle();
+    }
+}
+
+pub fn wait_input_clear() -> bool {
+    for _ in 0..CONTROLLER_TIMEOUT {
+        if unsafe { inb(STATUS_PORT) } & STATUS_INPUT_FULL == 0 {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+    false
+}
+
+fn wait_for_response() -> Option<u8> {
+    for _ in 0..CONTROLLER_TIMEOUT {
+        let status = unsafe { inb(STATUS_PORT) };
+        if status & STATUS_OUTPUT_FULL != 0 {
+            let data = unsafe { inb(DATA_PORT) };
+            if status & STATUS_AUX_DATA != 0 {
+                continue;
+            }
+            if data >= RESPONSE_ACK {
+                return Some(data);
+            }
+            KEYBOARD.lock().add_to_buffer(data);
+        }
+        core::hint::spin_loop();
+    }
+
+    None
+}
+
+pub trait CommandPort {
+    fn write(&mut self, byte: u8) -> bool;
+
+    fn read_response(&mut self) -> Option<u8>;
+}
+
+struct Controller;
+
+impl CommandPort for Controller {
+    fn write(&mut self, byte: u8) -> bool {
+        if !wait_input_clear() {
+            return false;
+        }
+        unsafe {
+            outb(DATA_PORT, byte);
+        }
+        true
+    }
+
+    fn read_response(&mut self) -> Option<u8> {
+        wait_for_response()
+    }
+}
+
+pub fn send_command_to(port: &mut impl CommandPort, byte: u8) -> Result<(), CommandError> {
+    for _ in 0..COMMAND_ATTEMPTS {
+        if !port.write(byte) {
+            return Err(CommandError::Timeout);
+        }
+
+        match port.read_response() {
+            Some(RESPONSE_ACK) => return Ok(()),
+            Some(RESPONSE_RESEND) => continue,
+            Some(response) => return Err(CommandError::Failed(response)),
+            None => return Err(CommandError::Timeout),
+        }
+    }
+    Err(CommandError::ResendLimit)
+}
+
+fn send_command(byte: u8) -> Result<(), CommandError> {
+    without_interrupts(|| send_command_to(&mut Controller, byte))
+}
+
+pub fn set_typematic(delay: u8, rate: u8) -> Result<(), CommandError> {
+    if delay > TYPEMATIC_MAX_DELAY || rate > TYPEMATIC_MAX_RATE {
+        return Err(CommandError::InvalidArgument);
+    }
+
+    send_command(COMMAND_SET_TYPEMATIC)?;
+    send_command((delay << 5) | rate)?;
+
+    set_repeat(delay, rate);
+    Ok(())
+}
+
+pub fn set_repeat(delay: u8, rate: u8) {
+    TYPEMATIC_DELAY.store(delay & TYPEMATIC_MAX_DELAY, Ordering::SeqCst);
+    TYPEMATIC_RATE.store(rate & TYPEMATIC_MAX_RATE, Ordering::SeqCst);
+}
+
+pub fn get_typematic() -> (u8, u8) {
+    (
+        TYPEMATIC_DELAY.load(Ordering::SeqCst),
+        TYPEMATIC_RATE.load(Ordering::SeqCst),
+    )
+}
+
+pub fn typematic_delay_ms(delay: u8) -> u32 {
+    ((delay & TYPEMATIC_MAX_DELAY) as u32 + 1) * 250
+}
+
+pub fn typematic_delay_for_ms(ms: u32) -> u8 {
+    core::cmp::min((ms + 125) / 250, 4).saturating_sub(1) as u8
+}
+
+pub fn typematic_rate_for_cps(cps: u32) -> u8 {
+    (0..=TYPEMATIC_MAX_RATE)
+        .min_by_key(|&rate| typematic_rate_tenths(rate).abs_diff(cps * 10))
+        .unwrap_or(TYPEMATIC_MAX_RATE)
+}
+
+fn repeat_period_ms(rate: u8) -> u32 {
+    10_000 / typematic_rate_tenths(rate)
+}
+
+pub fn typematic_rate_tenths(rate: u8) -> u32 {
+    let mantissa = 8 + (rate & 0x07) as u32;
+    let exponent = ((rate >> 3) & 0x03) as u32;
+    let period_us = mantissa * (1 << exponent) * 4170;
+    (10_000_000 + period_us / 2) / period_us
+}
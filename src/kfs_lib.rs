@@ -0,0 +1,209 @@
+use crate::memory::PAGE_SIZE;
+use core::arch::asm;
+use core::fmt::{self, Write};
+
+const KIB: u64 = 1024;
+const REP_ALIGN_THRESHOLD: usize = 16;
+
+pub fn strtoul(s: &str, base: u32) -> Option<u64> {
+    let s = s.trim();
+    let bytes = s.as_bytes();
+
+    let (digits, base) = if (base == 0 || base == 16)
+        && bytes.len() > 2
+        && bytes[0] == b'0'
+        && (bytes[1] == b'x' || bytes[1] == b'X')
+    {
+        (&s[2..], 16)
+    } else if base == 0
+        && bytes.len() > 2
+        && bytes[0] == b'0'
+        && (bytes[1] == b'b' || bytes[1] == b'B')
+    {
+        (&s[2..], 2)
+    } else if base == 0 {
+        (s, 10)
+    } else {
+        (s, base)
+    };
+
+    if digits.is_empty() || !(2..=36).contains(&base) {
+        return None;
+    }
+
+    let mut value: u64 = 0;
+    for c in digits.chars() {
+        if c == '_' {
+            continue;
+        }
+        let digit = c.to_digit(base)? as u64;
+        value = value.checked_mul(base as u64)?.checked_add(digit)?;
+    }
+
+    Some(value)
+}
+
+pub fn strtol(s: &str, base: u32) -> Option<i64> {
+    let s = s.trim();
+
+    if let Some(rest) = s.strip_prefix('-') {
+        let value = strtoul(rest, base)?;
+        if value > i64::MAX as u64 + 1 {
+            return None;
+        }
+        Some((value as i64).wrapping_neg())
+    } else {
+        let value = strtoul(s.strip_prefix('+').unwrap_or(s), base)?;
+        if value > i64::MAX as u64 {
+            return None;
+        }
+        Some(value as i64)
+    }
+}
+
+pub struct HumanSize(pub u64);
+
+pub struct FixedBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedBuffer<N> {
+    pub const fn new() -> FixedBuffer<N> {
+        FixedBuffer {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+impl<const N: usize> Write for FixedBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let count = core::cmp::min(s.len(), N - self.len);
+        self.bytes[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        if count < s.len() {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buffer = FixedBuffer::<16>::new();
+
+        let mut unit = 1;
+        let mut suffix = "B";
+        for next in ["KiB", "MiB", "GiB"] {
+            if self.0 < unit * KIB {
+                break;
+            }
+            unit *= KIB;
+            suffix = next;
+        }
+
+        if unit == 1 {
+            write!(buffer, "{} {}", self.0, suffix)?;
+        } else {
+            let tenths = self.0 * 10 / unit;
+            write!(buffer, "{}.{} {}", tenths / 10, tenths % 10, suffix)?;
+        }
+
+        f.pad(buffer.as_str())
+    }
+}
+
+pub unsafe fn memset(dest: *mut u8, value: u8, len: usize) -> *mut u8 {
+    let pattern = value as u32 * 0x01010101;
+    if len < REP_ALIGN_THRESHOLD {
+        asm!(
+            "cld",
+            "rep stosb",
+            inout("edi") dest => _,
+            inout("ecx") len => _,
+            in("eax") pattern,
+            options(nostack)
+        );
+        return dest;
+    }
+
+    let head = dest.align_offset(4);
+    let words = (len - head) / 4;
+    let tail = (len - head) % 4;
+    asm!(
+        "cld",
+        "rep stosb",
+        "mov ecx, {words}",
+        "rep stosd",
+        "mov ecx, {tail}",
+        "rep stosb",
+        words = in(reg) words,
+        tail = in(reg) tail,
+        inout("edi") dest => _,
+        inout("ecx") head => _,
+        in("eax") pattern,
+        options(nostack)
+    );
+    dest
+}
+
+pub unsafe fn memcpy(dest: *mut u8, src: *const u8, len: usize) -> *mut u8 {
+    let (head, words, tail) = if len < REP_ALIGN_THRESHOLD {
+        (len, 0, 0)
+    } else {
+        let head = dest.align_offset(4);
+        (head, (len - head) / 4, (len - head) % 4)
+    };
+    asm!(
+        "xchg esi, {src}",
+        "cld",
+        "rep movsb",
+        "mov ecx, {words}",
+        "rep movsd",
+        "mov ecx, {tail}",
+        "rep movsb",
+        "xchg esi, {src}",
+        src = inout(reg) src => _,
+        words = in(reg) words,
+        tail = in(reg) tail,
+        inout("edi") dest => _,
+        inout("ecx") head => _,
+        options(nostack)
+    );
+    dest
+}
+
+pub unsafe fn memset32(dest: *mut u32, value: u32, count: usize) -> *mut u32 {
+    asm!(
+        "cld",
+        "rep stosd",
+        inout("edi") dest => _,
+        inout("ecx") count => _,
+        in("eax") value,
+        options(nostack)
+    );
+    dest
+}
+
+pub fn memzero_frame(frame: usize) {
+    unsafe {
+        memset32(frame as *mut u32, 0, PAGE_SIZE / 4);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::printk::{self, print, println, LogLevel, Timestamp};
+use crate::sync::IrqSpinlock;
+use core::fmt::{self, Write};
+
+pub const KLOG_LINES: usize = 64;
+const KLOG_LINE_LEN: usize = 120;
+
+#[derive(Clone, Copy)]
+pub struct LogLine {
+    level: LogLevel,
+    uptime_ms: u64,
+    text: [u8; KLOG_LINE_LEN],
+    len: usize,
+}
+
+impl LogLine {
+    const fn empty() -> LogLine {
+        LogLine {
+            level: LogLevel::Info,
+            uptime_ms: 0,
+            text: [0; KLOG_LINE_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn level(&self) -> LogLevel {
+        self.level
+    }
+
+    pub fn uptime_ms(&self) -> u64 {
+        self.uptime_ms
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.text[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for LogLine {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            if self.len >= KLOG_LINE_LEN {
+                break;
+            }
+            self.text[self.len] = match byte {
+                0x20..=0x7e => byte,
+                _ => b'?',
+            };
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+struct KernelLog {
+    lines: [LogLine; KLOG_LINES],
+    start: usize,
+    len: usize,
+}
+
+static KLOG: IrqSpinlock<KernelLog> = IrqSpinlock::new(KernelLog {
+    lines: [LogLine::empty(); KLOG_LINES],
+    start: 0,
+    len: 0,
+});
+
+pub fn record(args: fmt::Arguments) {
+    record_at(LogLevel::Info, args);
+}
+
+pub fn record_at(level: LogLevel, args: fmt::Arguments) {
+    let mut line = LogLine::empty();
+    line.level = level;
+    line.uptime_ms = printk::uptime_ms();
+    let _ = line.write_fmt(args);
+
+    let mut log = KLOG.lock();
+    let index = (log.start + log.len) % KLOG_LINES;
+    log.lines[index] = line;
+    if log.len < KLOG_LINES {
+        log.len += 1;
+    } else {
+        log.start = (log.start + 1) % KLOG_LINES;
+    }
+}
+
+pub fn len() -> usize {
+    KLOG.lock().len
+}
+
+pub fn get(index: usize) -> Option<LogLine> {
+    let log = KLOG.lock();
+    (index < log.len).then(|| log.lines[(log.start + index) % KLOG_LINES])
+}
+
+pub fn tail(count: usize) -> impl Iterator<Item = LogLine> {
+    let len = len();
+    (len.saturating_sub(count)..len).map_while(get)
+}
+
+pub fn print_tail(count: usize) {
+    let mut empty = true;
+
+    for line in tail(count) {
+        if printk::timestamps_enabled() {
+            crate::printk!("  {} ", Timestamp(line.uptime_ms()));
+        } else {
+            print("  ");
+        }
+        println(line.as_str());
+        empty = false;
+    }
+
+    if empty {
+        println("  [Kernel log is empty]");
+    }
+}
+
+#[macro_export]
+macro_rules! klog {
+    ($($arg:tt)*) => {
+        $crate::klog::record(format_args!($($arg)*))
+    };
+}
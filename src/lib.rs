@@ -1,57 +1,169 @@
 #![no_std]
 #![no_main]
 #![allow(dead_code)]
-
+#![feature(abi_x86_interrupt)]
+#![feature(never_type)]
+
+mod acpi;
+mod apic;
+mod ata;
+mod banner;
+mod block;
+mod cmdline;
+mod config;
+mod cpuid;
+mod early_console;
+mod elf;
+mod fbcon;
+mod font;
+mod fs;
 mod gdt;
+mod idle;
+mod idt;
+mod initrd;
+mod io;
+mod keyboard;
+mod kfs_lib;
+mod klog;
+mod line_editor;
 mod memory;
+mod mouse;
 mod panic;
+mod pic;
+mod power;
 mod printk;
+mod ramfs;
+mod random;
+mod ringbuf;
+mod rtc;
+mod selection;
+mod selftest;
+mod serial;
+mod shell;
+mod speaker;
 mod stack;
+mod stack_guard;
+mod statusbar;
+mod sync;
+mod syscall;
+mod tar;
+mod task;
+mod theme;
+mod timer;
+mod usermode;
 mod vga;
+mod watchdog;
 
 use core::panic::PanicInfo;
-use vga::Color;
+use theme::Role;
 
 #[no_mangle]
 pub extern "C" fn kernel_main(multiboot_magic: u32, multiboot_info: u32) -> ! {
+    stack_guard::init();
+    let framebuffer = fbcon::init(multiboot_info);
     printk::init();
     printk::clear();
 
-    printk::set_color(Color::LightGreen, Color::Black);
-    printkln!("42");
-    printkln!();
-    printk::set_color(Color::LightCyan, Color::Black);
-    printkln!("KFS - Kernel From Scratch v3");
-    printkln!("============================");
-    printk::reset_color();
-    printkln!();
-
     if multiboot_magic != 0x2BADB002 {
-        printk::set_color(Color::Yellow, Color::Black);
+        theme::apply(Role::Warning);
         printkln!("Warning: Invalid multiboot magic number");
         printk::reset_color();
     }
 
-    printk::set_color(Color::Yellow, Color::Black);
+    config::init();
+    cmdline::init(multiboot_info);
+    if let Some(level) = cmdline::get("loglevel").and_then(printk::LogLevel::from_name) {
+        printk::set_level(level);
+    }
+    if let Some("0" | "off") = cmdline::get("printk.time") {
+        printk::set_timestamps(false);
+    }
+
+    if serial::init() {
+        theme::apply(Role::Success);
+        printkln!("Serial port COM1 found!");
+        printk::reset_color();
+        printkln!();
+    }
+
+    match framebuffer {
+        Ok(framebuffer) => pr_info!(
+            "fbcon: {}x{} {} bpp framebuffer at 0x{:08X}",
+            framebuffer.width,
+            framebuffer.height,
+            framebuffer.bpp,
+            framebuffer.address
+        ),
+        Err(fbcon::FbconError::NoFramebuffer | fbcon::FbconError::TextMode) => {}
+        Err(error) => pr_warn!("fbcon: {}, using VGA text mode", error.description()),
+    }
+
+    theme::apply(Role::Label);
     printkln!("Initializing GDT...");
     printk::reset_color();
     gdt::init();
-    printk::set_color(Color::LightGreen, Color::Black);
+    assert_early!(gdt::is_loaded(), "GDT failed to load");
+    theme::apply(Role::Success);
     printkln!("GDT initialized successfully!");
     printk::reset_color();
     printkln!();
 
-    printk::set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     printkln!("Initializing memory management...");
     printk::reset_color();
 
     memory::init(multiboot_info);
+    stack_guard::install_guard_pages();
 
-    printk::set_color(Color::LightGreen, Color::Black);
+    theme::apply(Role::Success);
     printkln!("Memory management initialized!");
     printk::reset_color();
     printkln!();
 
+    if initrd::init(multiboot_info) {
+        theme::apply(Role::Success);
+        printkln!("Initrd found!");
+        printk::reset_color();
+        printkln!();
+    }
+
+    theme::apply(Role::Label);
+    printkln!("Initializing interrupts...");
+    printk::reset_color();
+
+    idt::init();
+    pic::init();
+    timer::init();
+    keyboard::init();
+    if let Err(error) = mouse::init() {
+        pr_warn!("PS/2 mouse: {}", error.description());
+    }
+    idt::enable_interrupts();
+
+    theme::apply(Role::Success);
+    printkln!("Interrupts enabled!");
+    printk::reset_color();
+    printkln!();
+
+    init_interrupt_model();
+
+    if acpi::init().is_ok() {
+        theme::apply(Role::Success);
+        printkln!("ACPI: {} table(s) found", acpi::tables().len());
+        printk::reset_color();
+        printkln!();
+    }
+
+    statusbar::set_enabled(true);
+
+    let drives = ata::init();
+    if drives > 0 {
+        theme::apply(Role::Success);
+        printkln!("ATA: {} drive(s) found", drives);
+        printk::reset_color();
+        printkln!();
+    }
+
     print_memory_info();
     printkln!();
 
@@ -63,22 +175,53 @@ pub extern "C" fn kernel_main(multiboot_magic: u32, multiboot_info: u32) -> ! {
 
     stack::print_stack_summary();
 
+    banner::show();
     printkln!();
-    printk::set_color(Color::DarkGray, Color::Black);
-    printkln!("Kernel initialization complete. Halting...");
+    theme::apply(Role::Muted);
+    printkln!("Kernel initialization complete. Starting shell...");
     printk::reset_color();
+    printkln!();
 
-    loop {
-        unsafe {
-            core::arch::asm!("hlt");
+    speaker::play(speaker::BOOT_CHIME, speaker::PlayMode::Queue);
+    early_console::mark_ready();
+
+    shell::run()
+}
+
+fn init_interrupt_model() {
+    let model = match cmdline::get("interrupt_model") {
+        Some(name) => match apic::InterruptModel::from_name(name) {
+            Some(model) => model,
+            None => {
+                pr_warn!("Unknown interrupt_model '{}', using the PIC", name);
+                printkln!();
+                return;
+            }
+        },
+        None => return,
+    };
+    if model != apic::InterruptModel::Apic {
+        return;
+    }
+
+    match apic::init() {
+        Ok(()) => {
+            theme::apply(Role::Success);
+            printkln!(
+                "APIC enabled, timer at {} counts per tick",
+                apic::timer_count()
+            );
+            printk::reset_color();
         }
+        Err(error) => pr_warn!("APIC: {}, using the PIC", error.description()),
     }
+    printkln!();
 }
 
 fn print_memory_info() {
-    use printk::{print, print_dec, print_hex_padded, println, reset_color, set_color};
+    use printk::{print, print_dec, print_hex_padded, println, reset_color};
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     println("          MEMORY INFORMATION           ");
     println("========================================");
@@ -86,19 +229,19 @@ fn print_memory_info() {
 
     let stats = memory::get_stats();
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Total Physical Memory: ");
     reset_color();
     print_dec((stats.total_memory / 1024) as u32);
     println(" KB");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Used Physical Memory:  ");
     reset_color();
     print_dec((stats.used_memory / 1024) as u32);
     println(" KB");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Free Physical Memory:  ");
     reset_color();
     print_dec((stats.free_memory / 1024) as u32);
@@ -106,88 +249,88 @@ fn print_memory_info() {
 
     println("");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Kernel Heap Size:      ");
     reset_color();
     print_dec((memory::KERNEL_HEAP_SIZE / 1024) as u32);
     println(" KB");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Heap Used:             ");
     reset_color();
     print_dec(stats.heap_used as u32);
     println(" bytes");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Heap Free:             ");
     reset_color();
     print_dec(stats.heap_free as u32);
     println(" bytes");
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     reset_color();
 }
 
 fn print_paging_info() {
-    use printk::{print, print_dec, print_hex_padded, println, reset_color, set_color};
+    use printk::{print, print_dec, print_hex_padded, println, reset_color};
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     println("         PAGING INFORMATION            ");
     println("========================================");
     reset_color();
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Paging Enabled: ");
     reset_color();
     if memory::paging::is_paging_enabled() {
-        set_color(Color::LightGreen, Color::Black);
+        theme::apply(Role::Success);
         println("Yes");
     } else {
-        set_color(Color::LightRed, Color::Black);
+        theme::apply(Role::Error);
         println("No");
     }
     reset_color();
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Page Directory (CR3): ");
     reset_color();
     print_hex_padded(memory::paging::get_cr3());
     println("");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("CR0: ");
     reset_color();
     print_hex_padded(memory::paging::get_cr0());
     println("");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Page Size: ");
     reset_color();
     print_dec(memory::PAGE_SIZE as u32);
     println(" bytes");
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     reset_color();
 }
 
 fn test_memory_allocation() {
-    use printk::{print, print_dec, print_hex_padded, println, reset_color, set_color};
+    use printk::{print, print_dec, print_hex_padded, println, reset_color};
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     println("       MEMORY ALLOCATION TEST          ");
     println("========================================");
     reset_color();
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Testing kmalloc(64)... ");
     reset_color();
 
     if let Some(ptr) = memory::heap::kmalloc(64) {
-        set_color(Color::LightGreen, Color::Black);
+        theme::apply(Role::Success);
         print("OK ");
         reset_color();
         print("at ");
@@ -195,7 +338,7 @@ fn test_memory_allocation() {
         println("");
 
         let size = memory::heap::ksize(ptr);
-        set_color(Color::Yellow, Color::Black);
+        theme::apply(Role::Label);
         print("  ksize: ");
         reset_color();
         print_dec(size as u32);
@@ -206,20 +349,20 @@ fn test_memory_allocation() {
             *(ptr.add(1)) = 0x43;
         }
 
-        set_color(Color::Yellow, Color::Black);
+        theme::apply(Role::Label);
         print("Testing kfree... ");
         reset_color();
         memory::heap::kfree(ptr);
-        set_color(Color::LightGreen, Color::Black);
+        theme::apply(Role::Success);
         println("OK");
         reset_color();
     } else {
-        set_color(Color::LightRed, Color::Black);
+        theme::apply(Role::Error);
         println("FAILED");
         reset_color();
     }
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Testing multiple allocations... ");
     reset_color();
 
@@ -236,7 +379,7 @@ fn test_memory_allocation() {
     }
 
     if success {
-        set_color(Color::LightGreen, Color::Black);
+        theme::apply(Role::Success);
         println("OK");
         reset_color();
 
@@ -246,94 +389,78 @@ fn test_memory_allocation() {
             }
         }
     } else {
-        set_color(Color::LightRed, Color::Black);
+        theme::apply(Role::Error);
         println("FAILED");
         reset_color();
     }
 
     let (free_blocks, used_blocks) = memory::heap::count_blocks();
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Heap blocks - Free: ");
     reset_color();
     print_dec(free_blocks as u32);
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print(" Used: ");
     reset_color();
     print_dec(used_blocks as u32);
     println("");
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     reset_color();
 }
 
 #[panic_handler]
 fn rust_panic(info: &PanicInfo) -> ! {
-    printk::set_color(Color::White, Color::Red);
-    printkln!();
-    printkln!("================================================================================");
-    printkln!("                            !!! KERNEL PANIC !!!                               ");
-    printkln!("================================================================================");
-    printk::set_color(Color::Red, Color::Black);
-    printkln!();
-
-    if let Some(location) = info.location() {
-        printk::set_color(Color::Yellow, Color::Black);
-        printk!("Location: ");
-        printk::reset_color();
-        printk!(
-            "{}:{}:{}",
-            location.file(),
-            location.line(),
-            location.column()
-        );
-        printkln!();
-    }
-
-    if let Some(message) = info.message().as_str() {
-        printk::set_color(Color::Yellow, Color::Black);
-        printk!("Message: ");
-        printk::reset_color();
-        printk!("{}", message);
-        printkln!();
-    }
-
-    printk::set_color(Color::White, Color::Red);
-    printkln!();
-    printkln!("System halted. Please reboot.");
-    printk::reset_color();
-
-    panic::halt_loop()
+    let location = info
+        .location()
+        .map(|location| (location.file(), location.line()));
+    panic::panic_impl(&info.message(), panic::PanicLevel::Fatal, location)
 }
 
 fn print_gdt_info() {
-    use printk::{print, print_dec, print_hex_padded, println, reset_color, set_color};
+    use printk::{print, print_dec, print_hex_padded, println, reset_color};
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     println("     GLOBAL DESCRIPTOR TABLE (GDT)     ");
     println("========================================");
     reset_color();
 
     let (base, limit) = gdt::get_gdt_info();
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("GDT Base Address: ");
     reset_color();
     print_hex_padded(base);
     println("");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("GDT Limit:        ");
     reset_color();
     print_dec(limit as u32);
     print(" bytes (");
     print_dec((limit as u32 + 1) / 8);
     println(" entries)");
+
+    theme::apply(Role::Label);
+    print("Task Register:    ");
+    reset_color();
+    print_hex_padded(gdt::task_register() as u32);
+    print(" (esp0 ");
+    print_hex_padded(gdt::kernel_stack());
+    println(")");
     println("");
 
-    set_color(Color::DarkGray, Color::Black);
-    println("Index | Selector | Name         | Access | Flags");
-    println("------|----------|--------------|--------|------");
+    if !gdt::is_loaded() {
+        theme::apply(Role::Warning);
+        println("GDTR does not point at the kernel GDT, entries below are not live.");
+        reset_color();
+        println("");
+    }
+
+    theme::apply(Role::Muted);
+    println("Index |   Selector   | Name         | Base       | Limit   | DPL | Type");
+    println("------|--------------|--------------|------------|---------|-----|-------");
     reset_color();
 
     let selectors = [
@@ -344,12 +471,15 @@ fn print_gdt_info() {
         gdt::selectors::USER_CODE,
         gdt::selectors::USER_DATA,
         gdt::selectors::USER_STACK,
+        gdt::selectors::TSS,
+        gdt::selectors::DOUBLE_FAULT_TSS,
     ];
 
     for i in 0..gdt::GDT_ENTRIES {
-        let (name, access, flags) = gdt::describe_entry(i);
+        let (name, entry) = gdt::describe_entry(i);
+        let decoded = entry.decode();
 
-        set_color(Color::DarkGray, Color::Black);
+        theme::apply(Role::Muted);
         print("  ");
         print_dec(i as u32);
         print("   |   ");
@@ -359,11 +489,11 @@ fn print_gdt_info() {
         print(" | ");
 
         if i == 0 {
-            set_color(Color::DarkGray, Color::Black);
+            theme::apply(Role::Muted);
         } else if i <= 3 {
-            set_color(Color::LightGreen, Color::Black);
+            theme::apply(Role::Success);
         } else {
-            set_color(Color::LightBlue, Color::Black);
+            theme::apply(Role::Info);
         }
 
         print(name);
@@ -372,14 +502,17 @@ fn print_gdt_info() {
         }
 
         reset_color();
-        print(" | ");
-        printk::print_byte_hex(access);
-        print("   | ");
-        printk::print_byte_hex(flags);
-        println("");
+        printkln!(
+            " | {:#010x} | {:#07x} |  {}  | {}{}",
+            decoded.base,
+            decoded.limit,
+            decoded.dpl,
+            decoded.kind(),
+            if decoded.granularity_4k { " 4K" } else { "" }
+        );
     }
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     reset_color();
 }
@@ -0,0 +1,346 @@
+use crate::keyboard::KeyEvent;
+use crate::printk::{self, print};
+use crate::vga::VGA_BUFFER_WIDTH;
+
+pub const MAX_LINE: usize = 256;
+const HISTORY_SIZE: usize = 16;
+
+#[derive(Clone, Copy)]
+struct SavedLine {
+    text: [u8; MAX_LINE],
+    len: usize,
+}
+
+impl SavedLine {
+    const fn empty() -> SavedLine {
+        SavedLine {
+            text: [0; MAX_LINE],
+            len: 0,
+        }
+    }
+}
+
+pub struct LineEditor {
+    buffer: [u8; MAX_LINE],
+    len: usize,
+    cursor: usize,
+    origin: usize,
+    rendered_len: usize,
+    history: [SavedLine; HISTORY_SIZE],
+    history_start: usize,
+    history_len: usize,
+    browsing: Option<usize>,
+    killed: SavedLine,
+}
+
+impl LineEditor {
+    pub const fn new() -> LineEditor {
+        LineEditor {
+            buffer: [0; MAX_LINE],
+            len: 0,
+            cursor: 0,
+            origin: 0,
+            rendered_len: 0,
+            history: [SavedLine::empty(); HISTORY_SIZE],
+            history_start: 0,
+            history_len: 0,
+            browsing: None,
+            killed: SavedLine::empty(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buffer[..self.len]).unwrap_or("")
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn begin(&mut self) {
+        let (column, row) = printk::cursor_position();
+        self.origin = row * VGA_BUFFER_WIDTH + column;
+        self.rendered_len = 0;
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.cursor = 0;
+        self.rendered_len = 0;
+        self.browsing = None;
+    }
+
+    pub fn redisplay(&mut self) {
+        self.begin();
+        self.redraw_from(0);
+    }
+
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let linear = self.origin + offset;
+        if offset > 0 && offset == self.len && linear.is_multiple_of(VGA_BUFFER_WIDTH) {
+            (VGA_BUFFER_WIDTH, linear / VGA_BUFFER_WIDTH - 1)
+        } else {
+            (linear % VGA_BUFFER_WIDTH, linear / VGA_BUFFER_WIDTH)
+        }
+    }
+
+    fn move_to(&self, offset: usize) {
+        let (column, row) = self.position(offset);
+        printk::set_cursor_position(column, row);
+    }
+
+    pub fn redraw_from(&mut self, offset: usize) {
+        let offset = core::cmp::min(offset, self.len);
+        self.move_to(offset);
+        print(core::str::from_utf8(&self.buffer[offset..self.len]).unwrap_or(""));
+
+        let end = core::cmp::max(self.len, self.rendered_len);
+        for _ in self.len..end {
+            print(" ");
+        }
+
+        let (column, row) = printk::cursor_position();
+        let actual = row * VGA_BUFFER_WIDTH + column;
+        let expected = self.origin + end;
+        if expected > actual {
+            let scrolled = (expected - actual).div_ceil(VGA_BUFFER_WIDTH);
+            self.origin = self.origin.saturating_sub(scrolled * VGA_BUFFER_WIDTH);
+        }
+
+        self.rendered_len = self.len;
+        self.move_to(self.cursor);
+    }
+
+    pub fn insert(&mut self, byte: u8) -> bool {
+        if self.len >= MAX_LINE {
+            return false;
+        }
+
+        self.buffer
+            .copy_within(self.cursor..self.len, self.cursor + 1);
+        self.buffer[self.cursor] = byte;
+        self.len += 1;
+        self.cursor += 1;
+        self.redraw_from(self.cursor - 1);
+        true
+    }
+
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+
+        self.cursor -= 1;
+        self.remove_at(self.cursor);
+        true
+    }
+
+    pub fn delete(&mut self) -> bool {
+        if self.cursor >= self.len {
+            return false;
+        }
+
+        self.remove_at(self.cursor);
+        true
+    }
+
+    fn remove_at(&mut self, offset: usize) {
+        self.buffer.copy_within(offset + 1..self.len, offset);
+        self.len -= 1;
+        self.redraw_from(offset);
+    }
+
+    pub fn move_left(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        self.move_to(self.cursor);
+        true
+    }
+
+    pub fn move_right(&mut self) -> bool {
+        if self.cursor >= self.len {
+            return false;
+        }
+        self.cursor += 1;
+        self.move_to(self.cursor);
+        true
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+        self.move_to(self.cursor);
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.len;
+        self.move_to(self.cursor);
+    }
+
+    fn kill(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+
+        let killed = end - start;
+        self.killed.text[..killed].copy_from_slice(&self.buffer[start..end]);
+        self.killed.len = killed;
+
+        self.buffer.copy_within(end..self.len, start);
+        self.len -= killed;
+        self.cursor = start;
+        self.redraw_from(start);
+    }
+
+    pub fn kill_to_start(&mut self) {
+        self.kill(0, self.cursor);
+    }
+
+    pub fn kill_to_end(&mut self) {
+        self.kill(self.cursor, self.len);
+    }
+
+    pub fn kill_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.buffer[start - 1] == b' ' {
+            start -= 1;
+        }
+        while start > 0 && self.buffer[start - 1] != b' ' {
+            start -= 1;
+        }
+        self.kill(start, self.cursor);
+    }
+
+    pub fn yank(&mut self) {
+        let count = core::cmp::min(self.killed.len, MAX_LINE - self.len);
+        if count == 0 {
+            return;
+        }
+
+        let start = self.cursor;
+        self.buffer.copy_within(start..self.len, start + count);
+        self.buffer[start..start + count].copy_from_slice(&self.killed.text[..count]);
+        self.len += count;
+        self.cursor += count;
+        self.redraw_from(start);
+    }
+
+    /// Applies an editing key to the line. Returns false for keys that
+    /// are not editing keys, which the caller handles itself.
+    pub fn handle_key(&mut self, event: KeyEvent) -> bool {
+        match event {
+            KeyEvent::Char(c) if c.is_ascii() => {
+                self.insert(c as u8);
+            }
+            KeyEvent::Backspace => {
+                self.backspace();
+            }
+            KeyEvent::Delete => {
+                self.delete();
+            }
+            KeyEvent::Left => {
+                self.move_left();
+            }
+            KeyEvent::Right => {
+                self.move_right();
+            }
+            KeyEvent::Home | KeyEvent::Ctrl('a') => self.move_home(),
+            KeyEvent::End | KeyEvent::Ctrl('e') => self.move_end(),
+            KeyEvent::Up => {
+                self.history_previous();
+            }
+            KeyEvent::Down => {
+                self.history_next();
+            }
+            KeyEvent::Ctrl('u') => self.kill_to_start(),
+            KeyEvent::Ctrl('k') => self.kill_to_end(),
+            KeyEvent::Ctrl('w') => self.kill_word(),
+            KeyEvent::Ctrl('y') => self.yank(),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.cursor = 0;
+        self.browsing = None;
+        self.redraw_from(0);
+    }
+
+    fn replace(&mut self, text: &[u8]) {
+        let len = core::cmp::min(text.len(), MAX_LINE);
+        self.buffer[..len].copy_from_slice(&text[..len]);
+        self.len = len;
+        self.cursor = len;
+        self.redraw_from(0);
+    }
+
+    fn history_entry(&self, index: usize) -> &SavedLine {
+        &self.history[(self.history_start + index) % HISTORY_SIZE]
+    }
+
+    pub fn add_history(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+        if self.history_len > 0 {
+            let last = self.history_entry(self.history_len - 1);
+            if last.text[..last.len] == self.buffer[..self.len] {
+                return;
+            }
+        }
+
+        let mut entry = SavedLine::empty();
+        entry.text[..self.len].copy_from_slice(&self.buffer[..self.len]);
+        entry.len = self.len;
+
+        let index = (self.history_start + self.history_len) % HISTORY_SIZE;
+        self.history[index] = entry;
+        if self.history_len < HISTORY_SIZE {
+            self.history_len += 1;
+        } else {
+            self.history_start = (self.history_start + 1) % HISTORY_SIZE;
+        }
+    }
+
+    pub fn history_previous(&mut self) -> bool {
+        let index = match self.browsing {
+            Some(0) => return false,
+            Some(index) => index - 1,
+            None if self.history_len == 0 => return false,
+            None => self.history_len - 1,
+        };
+
+        self.browsing = Some(index);
+        let entry = *self.history_entry(index);
+        self.replace(&entry.text[..entry.len]);
+        true
+    }
+
+    pub fn history_next(&mut self) -> bool {
+        let index = match self.browsing {
+            Some(index) => index + 1,
+            None => return false,
+        };
+
+        if index >= self.history_len {
+            self.browsing = None;
+            self.replace(&[]);
+        } else {
+            self.browsing = Some(index);
+            let entry = *self.history_entry(index);
+            self.replace(&entry.text[..entry.len]);
+        }
+        true
+    }
+}
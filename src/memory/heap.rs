@@ -31,6 +31,23 @@ static mut FREE_LIST: *mut BlockHeader = core::ptr::null_mut();
 
 static HEAP_USED: AtomicUsize = AtomicUsize::new(0);
 
+#[cfg(feature = "alloc-debug")]
+pub const ALLOC_HISTORY_SIZE: usize = 16;
+
+#[cfg(feature = "alloc-debug")]
+#[derive(Clone, Copy)]
+pub struct AllocRecord {
+    pub size: usize,
+    pub caller: u32,
+}
+
+#[cfg(feature = "alloc-debug")]
+static mut ALLOC_HISTORY: [AllocRecord; ALLOC_HISTORY_SIZE] =
+    [AllocRecord { size: 0, caller: 0 }; ALLOC_HISTORY_SIZE];
+
+#[cfg(feature = "alloc-debug")]
+static ALLOC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 pub fn init() {
     let heap_start = KERNEL_HEAP_START;
     let heap_end = KERNEL_HEAP_START + KERNEL_HEAP_SIZE;
@@ -53,13 +70,21 @@ pub fn init() {
     HEAP_INITIALIZED.store(true, Ordering::SeqCst);
 }
 
+pub fn block_size_for(size: usize) -> usize {
+    let total_size = align_up(size + core::mem::size_of::<BlockHeader>(), ALIGNMENT);
+    core::cmp::max(total_size, MIN_BLOCK_SIZE)
+}
+
+#[cfg_attr(feature = "alloc-debug", inline(never))]
 pub fn kmalloc(size: usize) -> Option<*mut u8> {
+    #[cfg(feature = "alloc-debug")]
+    record_allocation(size, caller_address());
+
     if size == 0 || !HEAP_INITIALIZED.load(Ordering::SeqCst) {
         return None;
     }
 
-    let total_size = align_up(size + core::mem::size_of::<BlockHeader>(), ALIGNMENT);
-    let total_size = core::cmp::max(total_size, MIN_BLOCK_SIZE);
+    let total_size = block_size_for(size);
 
     unsafe {
         let mut current = FREE_LIST;
@@ -232,6 +257,22 @@ pub fn get_total() -> usize {
     KERNEL_HEAP_SIZE
 }
 
+pub fn largest_free_block() -> usize {
+    let mut largest = 0;
+
+    unsafe {
+        let mut current = FREE_LIST;
+        while !current.is_null() {
+            if (*current).free && (*current).size > largest {
+                largest = (*current).size;
+            }
+            current = (*current).next;
+        }
+    }
+
+    largest.saturating_sub(core::mem::size_of::<BlockHeader>())
+}
+
 pub fn count_blocks() -> (usize, usize) {
     let mut free_count = 0;
     let mut used_count = 0;
@@ -250,3 +291,54 @@ pub fn count_blocks() -> (usize, usize) {
 
     (free_count, used_count)
 }
+
+#[cfg(feature = "alloc-debug")]
+#[inline(always)]
+fn caller_address() -> u32 {
+    let ebp = crate::stack::get_ebp();
+    if ebp < crate::stack::get_stack_bottom() || ebp + 8 > crate::stack::get_stack_top() {
+        return 0;
+    }
+    unsafe { *((ebp + 4) as *const u32) }
+}
+
+#[cfg(feature = "alloc-debug")]
+fn record_allocation(size: usize, caller: u32) {
+    let count = ALLOC_COUNT.fetch_add(1, Ordering::SeqCst);
+    unsafe {
+        let history = &mut *core::ptr::addr_of_mut!(ALLOC_HISTORY);
+        history[count % ALLOC_HISTORY_SIZE] = AllocRecord { size, caller };
+    }
+}
+
+#[cfg(feature = "alloc-debug")]
+pub fn recent_allocations() -> impl Iterator<Item = AllocRecord> {
+    let count = ALLOC_COUNT.load(Ordering::SeqCst);
+    let stored = core::cmp::min(count, ALLOC_HISTORY_SIZE);
+    let history = unsafe { &*core::ptr::addr_of!(ALLOC_HISTORY) };
+    (count - stored..count).map(move |i| history[i % ALLOC_HISTORY_SIZE])
+}
+
+#[cfg(feature = "alloc-debug")]
+pub fn print_recent_allocations() {
+    use crate::printk::{print, print_dec, print_hex_padded, println, reset_color};
+    use crate::theme::{self, Role};
+
+    theme::apply(Role::Info);
+    println("Recent allocations (oldest first):");
+    reset_color();
+
+    let mut empty = true;
+    for record in recent_allocations() {
+        print("  size=");
+        print_dec(record.size as u32);
+        print(" caller=");
+        print_hex_padded(record.caller);
+        println("");
+        empty = false;
+    }
+
+    if empty {
+        println("  [No allocations recorded]");
+    }
+}
@@ -9,9 +9,23 @@ pub const KERNEL_SPACE_START: usize = 0x00100000; // 1MB
 pub const KERNEL_SPACE_END: usize = 0x00400000; // 4MB
 pub const USER_SPACE_START: usize = 0x00400000; // 4MB
 pub const USER_SPACE_END: usize = 0xC0000000; // 3GB
-pub const KERNEL_HEAP_START: usize = 0x00200000; // 2MB
+pub const KERNEL_HEAP_START: usize = 0x00300000; // 3MB
 pub const KERNEL_HEAP_SIZE: usize = 0x00100000; // 1MB
 
+extern "C" {
+    static __kernel_start: u8;
+    static __kernel_end: u8;
+}
+
+pub fn kernel_image() -> (usize, usize) {
+    unsafe {
+        (
+            &__kernel_start as *const u8 as usize,
+            &__kernel_end as *const u8 as usize,
+        )
+    }
+}
+
 #[inline]
 pub const fn align_up(addr: usize, align: usize) -> usize {
     (addr + align - 1) & !(align - 1)
@@ -37,20 +51,50 @@ pub const fn frame_to_addr(frame: usize) -> usize {
     frame * PAGE_SIZE
 }
 
+pub fn peek<T: Copy>(addr: usize) -> Option<T> {
+    if !paging::is_range_mapped(addr, core::mem::size_of::<T>()) {
+        return None;
+    }
+
+    Some(unsafe { core::ptr::read_volatile(addr as *const T) })
+}
+
+pub fn poke<T: Copy>(addr: usize, value: T) -> bool {
+    if !paging::is_range_mapped(addr, core::mem::size_of::<T>()) {
+        return false;
+    }
+
+    unsafe {
+        core::ptr::write_volatile(addr as *mut T, value);
+    }
+    true
+}
+
 pub fn init(multiboot_info: u32) {
     pmm::init(multiboot_info);
     paging::init();
+    crate::fbcon::map();
     heap::init();
     vmm::init();
 }
 
 pub fn get_stats() -> MemoryStats {
+    let (free_blocks, used_blocks) = heap::count_blocks();
+
     MemoryStats {
         total_memory: pmm::get_total_memory(),
         free_memory: pmm::get_free_memory(),
         used_memory: pmm::get_used_memory(),
+        reserved_memory: pmm::get_reserved_memory(),
+        total_frames: pmm::get_total_frames(),
+        free_frames: pmm::get_free_frames(),
+        used_frames: pmm::get_used_frames(),
+        heap_size: heap::get_total(),
         heap_used: heap::get_used(),
         heap_free: heap::get_free(),
+        heap_largest_free: heap::largest_free_block(),
+        heap_free_blocks: free_blocks,
+        heap_allocations: used_blocks,
     }
 }
 
@@ -59,6 +103,14 @@ pub struct MemoryStats {
     pub total_memory: usize,
     pub free_memory: usize,
     pub used_memory: usize,
+    pub reserved_memory: usize,
+    pub total_frames: usize,
+    pub free_frames: usize,
+    pub used_frames: usize,
+    pub heap_size: usize,
     pub heap_used: usize,
     pub heap_free: usize,
+    pub heap_largest_free: usize,
+    pub heap_free_blocks: usize,
+    pub heap_allocations: usize,
 }
@@ -1,4 +1,5 @@
 use super::{pmm, PAGE_SIZE};
+use crate::kfs_lib::memzero_frame;
 use core::arch::asm;
 
 pub const ENTRIES_PER_TABLE: usize = 1024;
@@ -29,6 +30,7 @@ pub fn flags(flags: &[PageFlags]) -> u32 {
 
 pub const KERNEL_PAGE_FLAGS: u32 = 0b11; // Present | Writable
 pub const USER_PAGE_FLAGS: u32 = 0b111; // Present | Writable | User
+pub const USER_READ_ONLY_FLAGS: u32 = 0b101; // Present | User
 
 #[repr(transparent)]
 #[derive(Clone, Copy)]
@@ -277,8 +279,7 @@ pub fn map_page(virt_addr: usize, phys_addr: usize, flags: u32) -> bool {
     unsafe {
         if !KERNEL_PAGE_DIRECTORY.entries[pd_index].is_present() {
             if let Some(pt_frame) = pmm::alloc_frame() {
-                let pt_ptr = pt_frame as *mut PageTable;
-                core::ptr::write_bytes(pt_ptr, 0, 1);
+                memzero_frame(pt_frame);
 
                 KERNEL_PAGE_DIRECTORY.entries[pd_index] =
                     PageDirectoryEntry::new(pt_frame as u32, flags | KERNEL_PAGE_FLAGS);
@@ -331,3 +332,53 @@ pub fn get_physical_address(virt_addr: usize) -> Option<usize> {
 pub fn get_kernel_page_directory() -> &'static PageDirectory {
     unsafe { &KERNEL_PAGE_DIRECTORY }
 }
+
+pub fn is_mapped(virt_addr: usize) -> bool {
+    get_physical_address(virt_addr).is_some()
+}
+
+pub fn is_user_accessible(virt_addr: usize, write: bool) -> bool {
+    let pd_index = (virt_addr >> 22) & 0x3FF;
+    let pt_index = (virt_addr >> 12) & 0x3FF;
+
+    unsafe {
+        let pde = KERNEL_PAGE_DIRECTORY.entries[pd_index];
+        if !pde.is_present() || !pde.is_user() || (write && !pde.is_writable()) {
+            return false;
+        }
+
+        let pt_addr = pde.table_addr() as *const PageTable;
+        let pte = (*pt_addr).entries[pt_index];
+        pte.is_present() && pte.is_user() && (!write || pte.is_writable())
+    }
+}
+
+fn all_pages(virt_addr: usize, len: usize, check: impl Fn(usize) -> bool) -> bool {
+    if len == 0 {
+        return check(virt_addr);
+    }
+
+    let end = match virt_addr.checked_add(len - 1) {
+        Some(end) => end,
+        None => return false,
+    };
+
+    let mut page = virt_addr & !(PAGE_SIZE - 1);
+    loop {
+        if !check(page) {
+            return false;
+        }
+        match page.checked_add(PAGE_SIZE) {
+            Some(next) if next <= end => page = next,
+            _ => return true,
+        }
+    }
+}
+
+pub fn is_range_mapped(virt_addr: usize, len: usize) -> bool {
+    all_pages(virt_addr, len, is_mapped)
+}
+
+pub fn is_user_range(virt_addr: usize, len: usize, write: bool) -> bool {
+    all_pages(virt_addr, len, |page| is_user_accessible(page, write))
+}
@@ -1,4 +1,8 @@
-use super::{addr_to_frame, align_up, frame_to_addr, PAGE_SIZE};
+use super::{
+    addr_to_frame, align_up, frame_to_addr, KERNEL_HEAP_SIZE, KERNEL_HEAP_START,
+    KERNEL_SPACE_START, PAGE_SIZE,
+};
+use core::ptr::addr_of_mut;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 const MAX_MEMORY: usize = 128 * 1024 * 1024;
@@ -16,6 +20,78 @@ static USED_FRAMES: AtomicUsize = AtomicUsize::new(0);
 static MEMORY_START: AtomicUsize = AtomicUsize::new(0);
 static MEMORY_END: AtomicUsize = AtomicUsize::new(0);
 
+pub const MAX_REGIONS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl MemoryRegion {
+    const fn empty() -> MemoryRegion {
+        MemoryRegion { start: 0, end: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+pub struct RegionList {
+    regions: [MemoryRegion; MAX_REGIONS],
+    count: usize,
+}
+
+impl RegionList {
+    pub const fn new() -> RegionList {
+        RegionList {
+            regions: [MemoryRegion::empty(); MAX_REGIONS],
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, start: usize, end: usize) -> bool {
+        if end <= start {
+            return true;
+        }
+        if self.count >= MAX_REGIONS {
+            return false;
+        }
+
+        self.regions[self.count] = MemoryRegion { start, end };
+        self.count += 1;
+        true
+    }
+
+    pub fn as_slice(&self) -> &[MemoryRegion] {
+        &self.regions[..self.count]
+    }
+
+    pub fn coalesce(&mut self) {
+        self.regions[..self.count].sort_unstable_by_key(|region| region.start);
+
+        let mut merged = 0;
+        for i in 0..self.count {
+            let region = self.regions[i];
+            if merged > 0 && region.start <= self.regions[merged - 1].end {
+                let last = &mut self.regions[merged - 1];
+                last.end = core::cmp::max(last.end, region.end);
+            } else {
+                self.regions[merged] = region;
+                merged += 1;
+            }
+        }
+        self.count = merged;
+    }
+}
+
+static mut REGIONS: RegionList = RegionList::new();
+
 #[repr(C, packed)]
 struct MultibootMmapEntry {
     size: u32,
@@ -50,17 +126,43 @@ pub fn init(multiboot_info_addr: u32) {
     } else {
         let start = 0x100000; // 1MB
         let end = 0x1000000; // 16MB
-        mark_region_free(start, end - start);
-        MEMORY_START.store(start, Ordering::SeqCst);
-        MEMORY_END.store(end, Ordering::SeqCst);
+        regions().push(start, end);
+    }
+
+    let regions = regions();
+    regions.coalesce();
+    for region in regions.as_slice() {
+        mark_region_free(region.start, region.len());
     }
 
-    mark_region_used(0x100000, 0x100000); // 1MB
+    if let (Some(first), Some(last)) = (regions.as_slice().first(), regions.as_slice().last()) {
+        MEMORY_START.store(first.start, Ordering::SeqCst);
+        MEMORY_END.store(last.end, Ordering::SeqCst);
+    }
+
+    mark_region_used(
+        KERNEL_SPACE_START,
+        KERNEL_HEAP_START + KERNEL_HEAP_SIZE - KERNEL_SPACE_START,
+    );
     mark_region_used(0, 0x100000);
     mark_region_used(0x800, PAGE_SIZE);
+
+    if let Some((start, len)) = crate::initrd::find_module(multiboot_info_addr) {
+        mark_region_used(start, len);
+    }
+}
+
+fn regions() -> &'static mut RegionList {
+    unsafe { &mut *addr_of_mut!(REGIONS) }
+}
+
+pub fn usable_regions() -> &'static [MemoryRegion] {
+    regions().as_slice()
 }
 
 fn parse_multiboot_mmap(multiboot_info_addr: u32) {
+    let regions = regions();
+
     unsafe {
         let info = &*(multiboot_info_addr as *const MultibootInfo);
 
@@ -69,41 +171,29 @@ fn parse_multiboot_mmap(multiboot_info_addr: u32) {
             let mmap_end = mmap_addr + info.mmap_length as usize;
             let mut addr = mmap_addr;
 
-            let mut mem_start = usize::MAX;
-            let mut mem_end = 0usize;
-
             while addr < mmap_end {
                 let entry = &*(addr as *const MultibootMmapEntry);
 
                 if entry.entry_type == 1 {
-                    let base = entry.base_addr as usize;
-                    let length = entry.length as usize;
-
-                    if base < MAX_MEMORY {
-                        let end = core::cmp::min(base + length, MAX_MEMORY);
-                        mark_region_free(base, end - base);
-
-                        if base < mem_start {
-                            mem_start = base;
-                        }
-                        if end > mem_end {
-                            mem_end = end;
+                    let base = entry.base_addr;
+                    let end = base.saturating_add(entry.length);
+
+                    if base < MAX_MEMORY as u64 {
+                        let end = core::cmp::min(end, MAX_MEMORY as u64);
+                        if !regions.push(base as usize, end as usize) {
+                            crate::panic::warn("Too many memory map entries, ignoring the rest");
+                            break;
                         }
                     }
                 }
                 addr += entry.size as usize + 4;
             }
-
-            MEMORY_START.store(mem_start, Ordering::SeqCst);
-            MEMORY_END.store(mem_end, Ordering::SeqCst);
         } else {
             let mem_upper_kb = info.mem_upper as usize;
             let total_mem = (mem_upper_kb + 1024) * 1024;
             let end = core::cmp::min(total_mem, MAX_MEMORY);
 
-            mark_region_free(0x100000, end - 0x100000);
-            MEMORY_START.store(0x100000, Ordering::SeqCst);
-            MEMORY_END.store(end, Ordering::SeqCst);
+            regions.push(0x100000, end);
         }
     }
 }
@@ -240,6 +330,34 @@ pub fn free_frames(addr: usize, count: usize) {
     }
 }
 
+pub fn free_regions(start: usize, end: usize) -> RegionList {
+    let mut list = RegionList::new();
+    if end <= start {
+        return list;
+    }
+
+    let mut run: Option<usize> = None;
+    let first = addr_to_frame(start);
+    let last = core::cmp::min(addr_to_frame(end - 1) + 1, MAX_FRAMES);
+
+    for frame in first..=last {
+        let free = frame < last && !test_frame_bit(frame);
+        match (run, free) {
+            (None, true) => run = Some(frame),
+            (Some(run_start), false) => {
+                let low = core::cmp::max(frame_to_addr(run_start), start);
+                let high = core::cmp::min(frame_to_addr(frame), end);
+                if !list.push(low, high) {
+                    break;
+                }
+                run = None;
+            }
+            _ => {}
+        }
+    }
+    list
+}
+
 pub fn get_total_memory() -> usize {
     TOTAL_FRAMES.load(Ordering::SeqCst) * PAGE_SIZE
 }
@@ -250,6 +368,12 @@ pub fn get_free_memory() -> usize {
     (total.saturating_sub(used)) * PAGE_SIZE
 }
 
+pub fn get_reserved_memory() -> usize {
+    let start = MEMORY_START.load(Ordering::SeqCst);
+    let end = MEMORY_END.load(Ordering::SeqCst);
+    end.saturating_sub(start).saturating_sub(get_total_memory())
+}
+
 pub fn get_used_memory() -> usize {
     USED_FRAMES.load(Ordering::SeqCst) * PAGE_SIZE
 }
@@ -1,5 +1,6 @@
 use super::{align_up, paging, pmm, PAGE_SIZE};
 use super::{USER_SPACE_END, USER_SPACE_START};
+use crate::kfs_lib::memzero_frame;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug, Clone, Copy)]
@@ -71,9 +72,7 @@ pub fn vmalloc(size: usize) -> Option<*mut u8> {
                 return None;
             }
 
-            unsafe {
-                core::ptr::write_bytes(page_vaddr as *mut u8, 0, PAGE_SIZE);
-            }
+            memzero_frame(page_vaddr);
         } else {
             for j in 0..i {
                 let cleanup_vaddr = vaddr + j * PAGE_SIZE;
@@ -0,0 +1,234 @@
+use crate::io::{inb, outb};
+use crate::keyboard::{
+    wait_input_clear, CONTROLLER_TIMEOUT, DATA_PORT, RESPONSE_ACK, STATUS_AUX_DATA,
+    STATUS_OUTPUT_FULL, STATUS_PORT,
+};
+use crate::pic;
+use crate::ringbuf::RingBuffer;
+use crate::sync::{without_interrupts, IrqSpinlock};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+const CONTROLLER_ENABLE_AUX: u8 = 0xA8;
+const CONTROLLER_READ_CONFIG: u8 = 0x20;
+const CONTROLLER_WRITE_CONFIG: u8 = 0x60;
+const CONTROLLER_WRITE_AUX: u8 = 0xD4;
+
+const CONFIG_AUX_INTERRUPT: u8 = 1 << 1;
+const CONFIG_AUX_CLOCK_DISABLED: u8 = 1 << 5;
+
+const COMMAND_SET_DEFAULTS: u8 = 0xF6;
+const COMMAND_ENABLE_REPORTING: u8 = 0xF4;
+
+const PACKET_ALWAYS_ONE: u8 = 1 << 3;
+const PACKET_X_SIGN: u8 = 1 << 4;
+const PACKET_Y_SIGN: u8 = 1 << 5;
+const PACKET_X_OVERFLOW: u8 = 1 << 6;
+const PACKET_Y_OVERFLOW: u8 = 1 << 7;
+const PACKET_BUTTONS: u8 = 0x07;
+
+pub const BUTTON_LEFT: u8 = 1 << 0;
+pub const BUTTON_RIGHT: u8 = 1 << 1;
+pub const BUTTON_MIDDLE: u8 = 1 << 2;
+
+pub const EVENT_BUFFER_SIZE: usize = 32;
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+static RESYNCS: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseError {
+    Timeout,
+    NoAck(u8),
+}
+
+impl MouseError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            MouseError::Timeout => "controller did not respond",
+            MouseError::NoAck(_) => "mouse did not acknowledge a command",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub dx: i16,
+    pub dy: i16,
+    pub buttons: u8,
+}
+
+impl MouseEvent {
+    pub fn left(&self) -> bool {
+        self.buttons & BUTTON_LEFT != 0
+    }
+
+    pub fn right(&self) -> bool {
+        self.buttons & BUTTON_RIGHT != 0
+    }
+
+    pub fn middle(&self) -> bool {
+        self.buttons & BUTTON_MIDDLE != 0
+    }
+}
+
+pub struct Mouse {
+    packet: [u8; 3],
+    received: usize,
+    events: RingBuffer<MouseEvent, EVENT_BUFFER_SIZE>,
+}
+
+impl Mouse {
+    pub const fn new() -> Mouse {
+        Mouse {
+            packet: [0; 3],
+            received: 0,
+            events: RingBuffer::new(MouseEvent {
+                dx: 0,
+                dy: 0,
+                buttons: 0,
+            }),
+        }
+    }
+
+    pub fn add_byte(&mut self, byte: u8) {
+        if self.received == 0 && byte & PACKET_ALWAYS_ONE == 0 {
+            RESYNCS.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+
+        self.packet[self.received] = byte;
+        self.received += 1;
+        if self.received < self.packet.len() {
+            return;
+        }
+
+        self.received = 0;
+        if !self.events.push(decode(self.packet)) {
+            DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub fn next_event(&mut self) -> Option<MouseEvent> {
+        self.events.pop()
+    }
+}
+
+pub fn decode(packet: [u8; 3]) -> MouseEvent {
+    let [flags, x, y] = packet;
+    let axis = |value: u8, negative: bool, overflow: bool| {
+        if overflow {
+            0
+        } else if negative {
+            value as i16 - 0x100
+        } else {
+            value as i16
+        }
+    };
+
+    MouseEvent {
+        dx: axis(
+            x,
+            flags & PACKET_X_SIGN != 0,
+            flags & PACKET_X_OVERFLOW != 0,
+        ),
+        dy: axis(
+            y,
+            flags & PACKET_Y_SIGN != 0,
+            flags & PACKET_Y_OVERFLOW != 0,
+        ),
+        buttons: flags & PACKET_BUTTONS,
+    }
+}
+
+static MOUSE: IrqSpinlock<Mouse> = IrqSpinlock::new(Mouse::new());
+
+fn write_controller(command: u8) -> Result<(), MouseError> {
+    if !wait_input_clear() {
+        return Err(MouseError::Timeout);
+    }
+    unsafe {
+        outb(STATUS_PORT, command);
+    }
+    Ok(())
+}
+
+fn write_data(byte: u8) -> Result<(), MouseError> {
+    if !wait_input_clear() {
+        return Err(MouseError::Timeout);
+    }
+    unsafe {
+        outb(DATA_PORT, byte);
+    }
+    Ok(())
+}
+
+fn read_data(aux: bool) -> Result<u8, MouseError> {
+    for _ in 0..CONTROLLER_TIMEOUT {
+        let status = unsafe { inb(STATUS_PORT) };
+        if status & STATUS_OUTPUT_FULL != 0 {
+            let data = unsafe { inb(DATA_PORT) };
+            if !aux || status & STATUS_AUX_DATA != 0 {
+                return Ok(data);
+            }
+        }
+        core::hint::spin_loop();
+    }
+    Err(MouseError::Timeout)
+}
+
+fn send_command(command: u8) -> Result<(), MouseError> {
+    write_controller(CONTROLLER_WRITE_AUX)?;
+    write_data(command)?;
+    match read_data(true)? {
+        RESPONSE_ACK => Ok(()),
+        response => Err(MouseError::NoAck(response)),
+    }
+}
+
+pub fn init() -> Result<(), MouseError> {
+    without_interrupts(|| {
+        write_controller(CONTROLLER_ENABLE_AUX)?;
+
+        write_controller(CONTROLLER_READ_CONFIG)?;
+        let config = read_data(false)?;
+        write_controller(CONTROLLER_WRITE_CONFIG)?;
+        write_data((config | CONFIG_AUX_INTERRUPT) & !CONFIG_AUX_CLOCK_DISABLED)?;
+
+        send_command(COMMAND_SET_DEFAULTS)?;
+        send_command(COMMAND_ENABLE_REPORTING)?;
+        Ok(())
+    })?;
+
+    INITIALIZED.store(true, Ordering::SeqCst);
+    pic::unmask_irq(pic::IRQ_MOUSE);
+    Ok(())
+}
+
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
+}
+
+pub fn handle_interrupt() {
+    unsafe {
+        let status = inb(STATUS_PORT);
+        if status & STATUS_OUTPUT_FULL == 0 || status & STATUS_AUX_DATA == 0 {
+            return;
+        }
+
+        let data = inb(DATA_PORT);
+        MOUSE.lock().add_byte(data);
+    }
+}
+
+pub fn poll() -> Option<MouseEvent> {
+    MOUSE.lock().next_event()
+}
+
+pub fn dropped_events() -> u32 {
+    DROPPED.load(Ordering::SeqCst)
+}
+
+pub fn resyncs() -> u32 {
+    RESYNCS.load(Ordering::SeqCst)
+}
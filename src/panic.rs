@@ -1,6 +1,9 @@
+use crate::early_console;
 use crate::printk;
-use crate::vga::Color;
+use crate::theme::{self, Role};
 use core::arch::asm;
+use core::fmt;
+use core::panic::Location;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PanicLevel {
@@ -11,62 +14,50 @@ pub enum PanicLevel {
 
 #[inline(never)]
 pub fn panic(message: &str) -> ! {
-    panic_impl(message, PanicLevel::Fatal, None)
+    panic_impl(&message, PanicLevel::Fatal, None)
 }
 
 #[inline(never)]
 pub fn panic_at(message: &str, file: &str, line: u32) -> ! {
-    unsafe {
-        asm!("cli", options(nomem, nostack));
-    }
-
-    printk::set_color(Color::White, Color::Red);
-    printk::print("\n\n");
-    printk::print(
-        "================================================================================",
-    );
-    printk::print("\n");
-    printk::print(
-        "                            !!! KERNEL PANIC !!!                               ",
-    );
-    printk::print("\n");
-    printk::print(
-        "================================================================================",
-    );
-    printk::set_color(Color::Red, Color::Black);
-    printk::print("\n\n");
-
-    printk::set_color(Color::Yellow, Color::Black);
-    printk::print("Message: ");
-    printk::reset_color();
-    printk::print(message);
-    printk::print("\n\n");
-
-    printk::set_color(Color::Yellow, Color::Black);
-    printk::print("Location: ");
-    printk::reset_color();
-    printk::print(file);
-    printk::print(":");
-    printk::print_dec(line);
-    printk::print("\n\n");
-
-    print_registers();
+    panic_impl(&message, PanicLevel::Fatal, Some((file, line)))
+}
 
-    printk::set_color(Color::White, Color::Red);
-    printk::print("\n");
-    printk::print("System halted. Please reboot.");
-    printk::print("\n");
-    printk::reset_color();
+#[track_caller]
+#[inline(never)]
+pub fn assert_failed(message: &str) -> ! {
+    let location = record_assert(message);
+    panic_at(message, location.file(), location.line())
+}
 
-    halt_loop()
+#[track_caller]
+pub fn record_assert(message: &str) -> &'static Location<'static> {
+    let location = Location::caller();
+    crate::klog!(
+        "assertion failed: {} at {}:{}",
+        message,
+        location.file(),
+        location.line()
+    );
+    location
 }
 
-fn panic_impl(message: &str, level: PanicLevel, location: Option<(&str, u32)>) -> ! {
+pub fn panic_impl(
+    message: &dyn fmt::Display,
+    level: PanicLevel,
+    location: Option<(&str, u32)>,
+) -> ! {
+    if !early_console::is_ready() {
+        early_console::panic(message, location);
+    }
+
     unsafe {
         asm!("cli", options(nomem, nostack));
+        printk::force_unlock();
+        theme::force_unlock();
     }
 
-    printk::set_color(Color::White, Color::Red);
+    theme::apply(Role::Panic);
+    printk::set_blink(true);
     printk::print("\n\n");
     printk::print(
         "================================================================================",
@@ -95,17 +86,17 @@ fn panic_impl(message: &str, level: PanicLevel, location: Option<(&str, u32)>) -
     printk::print(
         "================================================================================",
     );
-    printk::set_color(Color::Red, Color::Black);
+    theme::apply(Role::Error);
     printk::print("\n\n");
 
-    printk::set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     printk::print("Message: ");
     printk::reset_color();
-    printk::print(message);
+    crate::printk!("{}", message);
     printk::print("\n\n");
 
     if let Some((file, line)) = location {
-        printk::set_color(Color::Yellow, Color::Black);
+        theme::apply(Role::Label);
         printk::print("Location: ");
         printk::reset_color();
         printk::print(file);
@@ -115,8 +106,15 @@ fn panic_impl(message: &str, level: PanicLevel, location: Option<(&str, u32)>) -
     }
 
     print_registers();
+    crate::stack_guard::check_all();
 
-    printk::set_color(Color::White, Color::Red);
+    #[cfg(feature = "alloc-debug")]
+    {
+        printk::print("\n");
+        crate::memory::heap::print_recent_allocations();
+    }
+
+    theme::apply(Role::Panic);
     printk::print("\n");
     printk::print("System halted. Please reboot.");
     printk::print("\n");
@@ -136,27 +134,17 @@ fn print_registers() {
     let esp: u32;
 
     unsafe {
-        asm!(
-            "mov {0}, eax",
-            "mov {1}, ebx",
-            "mov {2}, ecx",
-            "mov {3}, edx",
-            "mov {4}, esi",
-            "mov {5}, edi",
-            "mov {6}, ebp",
-            "mov {7}, esp",
-            out(reg) eax,
-            out(reg) ebx,
-            out(reg) ecx,
-            out(reg) edx,
-            out(reg) esi,
-            out(reg) edi,
-            out(reg) ebp,
-            out(reg) esp,
-        );
+        asm!("mov {}, eax", out(reg) eax, options(nomem, nostack, preserves_flags));
+        asm!("mov {}, ebx", out(reg) ebx, options(nomem, nostack, preserves_flags));
+        asm!("mov {}, ecx", out(reg) ecx, options(nomem, nostack, preserves_flags));
+        asm!("mov {}, edx", out(reg) edx, options(nomem, nostack, preserves_flags));
+        asm!("mov {}, esi", out(reg) esi, options(nomem, nostack, preserves_flags));
+        asm!("mov {}, edi", out(reg) edi, options(nomem, nostack, preserves_flags));
+        asm!("mov {}, ebp", out(reg) ebp, options(nomem, nostack, preserves_flags));
+        asm!("mov {}, esp", out(reg) esp, options(nomem, nostack, preserves_flags));
     }
 
-    printk::set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     printk::print("CPU Registers:\n");
     printk::reset_color();
 
@@ -202,7 +190,7 @@ pub fn halt_loop() -> ! {
 }
 
 pub fn warn(message: &str) {
-    printk::set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Warning);
     printk::print("[WARN] ");
     printk::reset_color();
     printk::print(message);
@@ -210,7 +198,7 @@ pub fn warn(message: &str) {
 }
 
 pub fn error(message: &str) {
-    printk::set_color(Color::LightRed, Color::Black);
+    theme::apply(Role::Error);
     printk::print("[ERROR] ");
     printk::reset_color();
     printk::print(message);
@@ -221,16 +209,12 @@ pub fn error(message: &str) {
 macro_rules! kassert {
     ($cond:expr) => {
         if !$cond {
-            $crate::panic::panic_at(
-                concat!("Assertion failed: ", stringify!($cond)),
-                file!(),
-                line!(),
-            );
+            $crate::panic::assert_failed(concat!("Assertion failed: ", stringify!($cond)));
         }
     };
     ($cond:expr, $msg:expr) => {
         if !$cond {
-            $crate::panic::panic_at($msg, file!(), line!());
+            $crate::panic::assert_failed($msg);
         }
     };
 }
@@ -0,0 +1,116 @@
+use crate::io::{inb, io_wait, outb};
+
+pub const PIC1_OFFSET: u8 = 0x20;
+pub const PIC2_OFFSET: u8 = 0x28;
+
+const PIC1_COMMAND: u16 = 0x20;
+const PIC1_DATA: u16 = 0x21;
+const PIC2_COMMAND: u16 = 0xA0;
+const PIC2_DATA: u16 = 0xA1;
+
+const ICW1_INIT: u8 = 0x10;
+const ICW1_ICW4: u8 = 0x01;
+const ICW4_8086: u8 = 0x01;
+const PIC_EOI: u8 = 0x20;
+const OCW3_READ_ISR: u8 = 0x0B;
+
+pub const IRQ_TIMER: u8 = 0;
+pub const IRQ_KEYBOARD: u8 = 1;
+pub const IRQ_CASCADE: u8 = 2;
+pub const IRQ_SPURIOUS_MASTER: u8 = 7;
+pub const IRQ_MOUSE: u8 = 12;
+pub const IRQ_SPURIOUS_SLAVE: u8 = 15;
+pub const IRQ_COUNT: u8 = 16;
+
+pub fn init() {
+    unsafe {
+        outb(PIC1_COMMAND, ICW1_INIT | ICW1_ICW4);
+        io_wait();
+        outb(PIC2_COMMAND, ICW1_INIT | ICW1_ICW4);
+        io_wait();
+
+        outb(PIC1_DATA, PIC1_OFFSET);
+        io_wait();
+        outb(PIC2_DATA, PIC2_OFFSET);
+        io_wait();
+
+        outb(PIC1_DATA, 1 << IRQ_CASCADE);
+        io_wait();
+        outb(PIC2_DATA, 2);
+        io_wait();
+
+        outb(PIC1_DATA, ICW4_8086);
+        io_wait();
+        outb(PIC2_DATA, ICW4_8086);
+        io_wait();
+
+        outb(PIC1_DATA, 0xFF);
+        outb(PIC2_DATA, 0xFF);
+    }
+
+    unmask_irq(IRQ_CASCADE);
+}
+
+pub fn disable() {
+    unsafe {
+        outb(PIC1_DATA, 0xFF);
+        outb(PIC2_DATA, 0xFF);
+    }
+}
+
+pub fn mask_irq(irq: u8) {
+    let (port, bit) = irq_port(irq);
+    unsafe {
+        outb(port, inb(port) | (1 << bit));
+    }
+}
+
+pub fn unmask_irq(irq: u8) {
+    let (port, bit) = irq_port(irq);
+    unsafe {
+        outb(port, inb(port) & !(1 << bit));
+    }
+}
+
+pub fn send_eoi(irq: u8) {
+    unsafe {
+        if irq >= 8 {
+            outb(PIC2_COMMAND, PIC_EOI);
+        }
+        outb(PIC1_COMMAND, PIC_EOI);
+    }
+}
+
+pub fn irq_for_vector(vector: u8) -> Option<u8> {
+    vector
+        .checked_sub(PIC1_OFFSET)
+        .filter(|&irq| irq < IRQ_COUNT)
+}
+
+pub fn read_isr() -> u16 {
+    unsafe {
+        outb(PIC1_COMMAND, OCW3_READ_ISR);
+        outb(PIC2_COMMAND, OCW3_READ_ISR);
+        (inb(PIC2_COMMAND) as u16) << 8 | inb(PIC1_COMMAND) as u16
+    }
+}
+
+pub fn is_spurious(irq: u8) -> bool {
+    (irq == IRQ_SPURIOUS_MASTER || irq == IRQ_SPURIOUS_SLAVE) && read_isr() & (1 << irq) == 0
+}
+
+pub fn acknowledge_spurious(irq: u8) {
+    if irq == IRQ_SPURIOUS_SLAVE {
+        unsafe {
+            outb(PIC1_COMMAND, PIC_EOI);
+        }
+    }
+}
+
+fn irq_port(irq: u8) -> (u16, u8) {
+    if irq < 8 {
+        (PIC1_DATA, irq)
+    } else {
+        (PIC2_DATA, irq - 8)
+    }
+}
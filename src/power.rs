@@ -0,0 +1,99 @@
+use crate::acpi::{self, AcpiError};
+use crate::io::{inb, io_wait, io_wait_n, outb, outw};
+use crate::panic;
+use core::arch::asm;
+
+const KBC_STATUS_PORT: u16 = 0x64;
+const KBC_COMMAND_PORT: u16 = 0x64;
+const KBC_INPUT_FULL: u8 = 1 << 1;
+const KBC_PULSE_RESET: u8 = 0xFE;
+
+const QEMU_SHUTDOWN_PORT: u16 = 0x604;
+const BOCHS_SHUTDOWN_PORT: u16 = 0xB004;
+const VIRTUALBOX_SHUTDOWN_PORT: u16 = 0x4004;
+
+const KBC_WAIT_ATTEMPTS: u32 = 100_000;
+const SETTLE_IO_WAITS: u32 = 100_000; // ~100ms
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownError {
+    Acpi(AcpiError),
+    NoResponse,
+}
+
+impl ShutdownError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ShutdownError::Acpi(error) => error.description(),
+            ShutdownError::NoResponse => "machine did not power off after S5 request",
+        }
+    }
+}
+
+impl From<AcpiError> for ShutdownError {
+    fn from(error: AcpiError) -> ShutdownError {
+        ShutdownError::Acpi(error)
+    }
+}
+
+pub fn reboot() -> ! {
+    keyboard_controller_reset();
+    triple_fault()
+}
+
+pub fn acpi_shutdown() -> Result<!, ShutdownError> {
+    acpi::poweroff()?;
+    Err(ShutdownError::NoResponse)
+}
+
+pub fn shutdown() -> ! {
+    let Err(error) = acpi_shutdown();
+    crate::klog!("ACPI poweroff failed: {}", error.description());
+    try_emulator_shutdown();
+
+    crate::printk::println("Shutdown is not supported on this machine, halting instead.");
+    halt()
+}
+
+pub fn halt() -> ! {
+    panic::halt_loop()
+}
+
+fn keyboard_controller_reset() {
+    unsafe {
+        asm!("cli", options(nomem, nostack));
+
+        for _ in 0..KBC_WAIT_ATTEMPTS {
+            if inb(KBC_STATUS_PORT) & KBC_INPUT_FULL == 0 {
+                break;
+            }
+            io_wait();
+        }
+
+        outb(KBC_COMMAND_PORT, KBC_PULSE_RESET);
+        io_wait_n(SETTLE_IO_WAITS);
+    }
+}
+
+fn try_emulator_shutdown() {
+    unsafe {
+        outw(QEMU_SHUTDOWN_PORT, 0x2000);
+        outw(BOCHS_SHUTDOWN_PORT, 0x2000);
+        outw(VIRTUALBOX_SHUTDOWN_PORT, 0x3400);
+
+        io_wait_n(SETTLE_IO_WAITS);
+    }
+}
+
+fn triple_fault() -> ! {
+    let null_idt: [u16; 3] = [0; 3];
+    unsafe {
+        asm!(
+            "lidt [{}]",
+            "int3",
+            in(reg) &null_idt,
+            options(nostack)
+        );
+    }
+    halt()
+}
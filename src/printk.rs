@@ -1,43 +1,340 @@
-use crate::vga::{Color, ColorCode, Writer};
+use crate::sync::IrqSpinlock;
+use crate::theme::{self, Role};
+use crate::vga::{Color, ColorCode, RawWriter, Screens, Writer};
+use crate::{serial, timer};
 use core::fmt::{self, Write};
+use core::ptr::{addr_of, addr_of_mut};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
 
-static mut KERNEL_WRITER: Option<Writer> = None;
+static SCREENS: IrqSpinlock<Option<Screens>> = IrqSpinlock::new(None);
+
+static mut TEE_SINK: Option<fn(&[u8])> = None;
+
+static SERIAL_MIRROR: AtomicBool = AtomicBool::new(false);
+
+static OUTPUT_COUNT: AtomicU32 = AtomicU32::new(0);
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+static TIMESTAMPS: AtomicBool = AtomicBool::new(true);
+
+static mut RAW_WRITER: RawWriter = RawWriter::new(0, ColorCode::new(Color::White, Color::Red));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+pub const LOG_LEVELS: [LogLevel; 4] = [
+    LogLevel::Error,
+    LogLevel::Warn,
+    LogLevel::Info,
+    LogLevel::Debug,
+];
+
+impl LogLevel {
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        LOG_LEVELS
+            .iter()
+            .copied()
+            .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+
+    pub fn role(self) -> Role {
+        match self {
+            LogLevel::Error => Role::Error,
+            LogLevel::Warn => Role::Warning,
+            LogLevel::Info => Role::Normal,
+            LogLevel::Debug => Role::Muted,
+        }
+    }
+}
 
 pub fn init() {
-    unsafe {
-        KERNEL_WRITER = Some(Writer::new());
+    *SCREENS.lock() = Some(Screens::new());
+}
+
+fn with_screens<R>(f: impl FnOnce(&mut Screens) -> R) -> R {
+    let mut guard = SCREENS.lock();
+    let screens = guard.get_or_insert_with(Screens::new);
+    let result = f(screens);
+    screens.active().flush();
+    result
+}
+
+fn with_writer<R>(f: impl FnOnce(&mut Writer) -> R) -> R {
+    with_screens(|screens| f(screens.active()))
+}
+
+pub fn with_screen<R>(id: usize, f: impl FnOnce(&mut Writer) -> R) -> Option<R> {
+    with_screens(|screens| screens.get(id).map(f))
+}
+
+pub fn switch_screen(id: usize) -> bool {
+    with_screens(|screens| screens.switch_to(id))
+}
+
+pub fn active_screen() -> usize {
+    with_screens(|screens| screens.active_id())
+}
+
+pub fn is_locked() -> bool {
+    SCREENS.is_locked()
+}
+
+pub unsafe fn force_unlock() {
+    SCREENS.force_unlock();
+}
+
+pub fn set_tee(sink: Option<fn(&[u8])>) -> Option<fn(&[u8])> {
+    unsafe { core::mem::replace(&mut *addr_of_mut!(TEE_SINK), sink) }
+}
+
+pub fn is_teeing() -> bool {
+    unsafe { TEE_SINK }.is_some()
+}
+
+fn tee(bytes: &[u8]) {
+    if let Some(sink) = unsafe { TEE_SINK } {
+        sink(bytes);
     }
 }
 
-fn get_writer() -> &'static mut Writer {
-    unsafe {
-        if KERNEL_WRITER.is_none() {
-            KERNEL_WRITER = Some(Writer::new());
-        }
-        KERNEL_WRITER.as_mut().unwrap()
+pub fn set_serial_mirror(enabled: bool) -> bool {
+    SERIAL_MIRROR.swap(enabled, Ordering::SeqCst)
+}
+
+pub fn is_serial_mirroring() -> bool {
+    SERIAL_MIRROR.load(Ordering::SeqCst)
+}
+
+fn mirror(bytes: &[u8]) {
+    if is_serial_mirroring() {
+        serial::write_bytes(bytes);
     }
 }
 
+pub fn output_count() -> u32 {
+    OUTPUT_COUNT.load(Ordering::SeqCst)
+}
+
+fn put_byte(byte: u8) {
+    OUTPUT_COUNT.fetch_add(1, Ordering::SeqCst);
+    with_writer(|writer| {
+        writer.write_byte(byte);
+        mirror(&[byte]);
+    });
+    tee(&[byte]);
+}
+
 pub fn print(s: &str) {
-    get_writer().write_string(s);
+    OUTPUT_COUNT.fetch_add(1, Ordering::SeqCst);
+    with_writer(|writer| {
+        writer.write_string(s);
+        mirror(s.as_bytes());
+    });
+    tee(s.as_bytes());
 }
 
-pub fn println(s: &str) {
-    let writer = get_writer();
+pub fn try_print(s: &str) -> bool {
+    let mut screens = match SCREENS.try_lock() {
+        Some(screens) => screens,
+        None => return false,
+    };
+    OUTPUT_COUNT.fetch_add(1, Ordering::SeqCst);
+    let writer = screens.get_or_insert_with(Screens::new).active();
     writer.write_string(s);
-    writer.write_string("\n");
+    writer.flush();
+    mirror(s.as_bytes());
+    drop(screens);
+    tee(s.as_bytes());
+    true
+}
+
+pub fn print_raw(s: &str) {
+    OUTPUT_COUNT.fetch_add(1, Ordering::SeqCst);
+    unsafe { (*addr_of_mut!(RAW_WRITER)).write_string(s) };
+    mirror(s.as_bytes());
+}
+
+pub fn raw_row() -> usize {
+    unsafe { (*addr_of!(RAW_WRITER)).row() }
+}
+
+pub fn read_raw(x: usize, y: usize) -> Option<u8> {
+    unsafe { (*addr_of!(RAW_WRITER)).read_char_at(x, y) }
+}
+
+pub fn print_safe(s: &str) {
+    if !try_print(s) {
+        print_raw(s);
+    }
+}
+
+pub fn apply_safe(role: Role) {
+    if !is_locked() && !theme::is_locked() {
+        theme::apply(role);
+    }
+}
+
+pub fn reset_color_safe() {
+    apply_safe(Role::Normal);
+}
+
+pub fn print_bytes(bytes: &[u8]) {
+    for &byte in bytes {
+        put_byte(byte);
+    }
+}
+
+pub fn println(s: &str) {
+    print(s);
+    print("\n");
+}
+
+pub fn at_line_start() -> bool {
+    with_writer(|writer| writer.at_line_start())
+}
+
+pub fn export_history(out: &mut dyn FnMut(&str)) {
+    with_writer(|writer| writer.export_history(out));
+}
+
+pub fn scroll_up(lines: usize) {
+    with_writer(|writer| writer.scroll_up(lines));
+}
+
+pub fn scroll_down(lines: usize) {
+    with_writer(|writer| writer.scroll_down(lines));
+}
+
+pub fn page_height() -> usize {
+    with_writer(|writer| writer.page_height())
 }
 
 pub fn clear() {
-    get_writer().clear_screen();
+    with_writer(|writer| writer.clear_screen());
+}
+
+pub fn clear_line() {
+    with_writer(|writer| writer.clear_line());
+}
+
+pub fn clear_to_line_end() {
+    with_writer(|writer| writer.clear_to_line_end());
+}
+
+pub fn clear_all() {
+    with_writer(|writer| writer.clear_all());
+}
+
+pub fn set_scroll_region(top: usize, bottom: usize) -> bool {
+    with_screens(|screens| screens.set_scroll_region(top, bottom))
 }
 
 pub fn set_color(fg: Color, bg: Color) {
-    get_writer().set_color(ColorCode::new(fg, bg));
+    let bg = crate::vga::clamp_background(bg);
+    with_writer(|writer| writer.set_color(ColorCode::new(fg, bg)));
+}
+
+pub fn set_blink(blink: bool) {
+    with_writer(|writer| writer.set_blink(blink));
+}
+
+pub fn write_char_at(x: usize, y: usize, byte: u8, fg: Color, bg: Color) -> bool {
+    with_writer(|writer| writer.write_char_at(x, y, byte, ColorCode::new(fg, bg)))
+}
+
+pub fn write_str_at(x: usize, y: usize, s: &str, fg: Color, bg: Color) -> usize {
+    with_writer(|writer| writer.write_str_at(x, y, s, ColorCode::new(fg, bg)))
+}
+
+pub fn fill_rect(x: usize, y: usize, width: usize, height: usize, glyph: u8, fg: Color, bg: Color) {
+    with_writer(|writer| writer.fill_rect(x, y, width, height, glyph, ColorCode::new(fg, bg)));
+}
+
+pub fn draw_box(x: usize, y: usize, width: usize, height: usize, fg: Color, bg: Color) {
+    with_writer(|writer| writer.draw_box(x, y, width, height, ColorCode::new(fg, bg)));
+}
+
+pub fn set_cursor_position(x: usize, y: usize) -> bool {
+    with_writer(|writer| writer.set_cursor_position(x, y))
+}
+
+pub fn cursor_position() -> (usize, usize) {
+    with_writer(|writer| writer.get_cursor_position())
+}
+
+pub fn save_cursor() {
+    with_writer(|writer| writer.save_cursor());
+}
+
+pub fn restore_cursor() -> bool {
+    with_writer(|writer| writer.restore_cursor())
 }
 
 pub fn reset_color() {
-    get_writer().set_color(ColorCode::new(Color::White, Color::Black));
+    let (fg, bg) = theme::colors(Role::Normal);
+    set_color(fg, bg);
+}
+
+pub fn set_level(level: LogLevel) -> LogLevel {
+    LOG_LEVELS[LOG_LEVEL.swap(level as u8, Ordering::SeqCst) as usize]
+}
+
+pub fn level() -> LogLevel {
+    LOG_LEVELS[LOG_LEVEL.load(Ordering::SeqCst) as usize]
+}
+
+pub fn set_timestamps(enabled: bool) -> bool {
+    TIMESTAMPS.swap(enabled, Ordering::SeqCst)
+}
+
+pub fn timestamps_enabled() -> bool {
+    TIMESTAMPS.load(Ordering::SeqCst)
+}
+
+pub fn uptime_ms() -> u64 {
+    if timer::is_initialized() {
+        timer::uptime_ms()
+    } else {
+        0
+    }
+}
+
+pub struct Timestamp(pub u64);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:5}.{:03}]", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+pub fn log(level: LogLevel, args: fmt::Arguments) {
+    crate::klog::record_at(level, args);
+    if level > self::level() {
+        return;
+    }
+
+    theme::apply(level.role());
+    if timestamps_enabled() {
+        let _ = write!(KernelWriter, "{} ", Timestamp(uptime_ms()));
+    }
+    let _ = KernelWriter.write_fmt(args);
+    print("\n");
+    reset_color();
 }
 
 pub struct KernelWriter;
@@ -49,6 +346,15 @@ impl Write for KernelWriter {
     }
 }
 
+pub struct SafeWriter;
+
+impl Write for SafeWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        print_safe(s);
+        Ok(())
+    }
+}
+
 #[macro_export]
 macro_rules! printk {
     ($($arg:tt)*) => ({
@@ -67,9 +373,54 @@ macro_rules! printkln {
     });
 }
 
+#[macro_export]
+macro_rules! pr_error {
+    ($($arg:tt)*) => {
+        $crate::printk::log($crate::printk::LogLevel::Error, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! pr_warn {
+    ($($arg:tt)*) => {
+        $crate::printk::log($crate::printk::LogLevel::Warn, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! pr_info {
+    ($($arg:tt)*) => {
+        $crate::printk::log($crate::printk::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! pr_debug {
+    ($($arg:tt)*) => {
+        $crate::printk::log($crate::printk::LogLevel::Debug, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! printk_safe {
+    ($($arg:tt)*) => ({
+        use core::fmt::Write;
+        let _ = write!($crate::printk::SafeWriter, $($arg)*);
+    });
+}
+
+#[macro_export]
+macro_rules! printkln_safe {
+    () => ($crate::printk::print_safe("\n"));
+    ($($arg:tt)*) => ({
+        use core::fmt::Write;
+        let _ = write!($crate::printk::SafeWriter, $($arg)*);
+        $crate::printk::print_safe("\n");
+    });
+}
+
 pub fn print_hex(value: u32) {
-    let writer = get_writer();
-    writer.write_string("0x");
+    print("0x");
 
     let hex_chars: [u8; 16] = *b"0123456789ABCDEF";
     let mut buffer = [b'0'; 8];
@@ -86,28 +437,25 @@ pub fn print_hex(value: u32) {
             started = true;
         }
         if started {
-            writer.write_byte(buffer[i]);
+            put_byte(buffer[i]);
         }
     }
 }
 
 pub fn print_hex_padded(value: u32) {
-    let writer = get_writer();
-    writer.write_string("0x");
+    print("0x");
 
     let hex_chars: [u8; 16] = *b"0123456789ABCDEF";
 
     for i in (0..8).rev() {
         let nibble = ((value >> (i * 4)) & 0xF) as usize;
-        writer.write_byte(hex_chars[nibble]);
+        put_byte(hex_chars[nibble]);
     }
 }
 
 pub fn print_dec(value: u32) {
-    let writer = get_writer();
-
     if value == 0 {
-        writer.write_byte(b'0');
+        put_byte(b'0');
         return;
     }
 
@@ -124,13 +472,43 @@ pub fn print_dec(value: u32) {
     }
 
     for j in (i + 1)..10 {
-        writer.write_byte(buffer[j]);
+        put_byte(buffer[j]);
     }
 }
 
 pub fn print_byte_hex(value: u8) {
-    let writer = get_writer();
     let hex_chars: [u8; 16] = *b"0123456789ABCDEF";
-    writer.write_byte(hex_chars[(value >> 4) as usize]);
-    writer.write_byte(hex_chars[(value & 0xF) as usize]);
+    put_byte(hex_chars[(value >> 4) as usize]);
+    put_byte(hex_chars[(value & 0xF) as usize]);
+}
+
+pub fn print_hexdump_line(address: u32, bytes: &[u8]) {
+    theme::apply(Role::Muted);
+    print_hex_padded(address);
+    print(": ");
+    reset_color();
+
+    for i in 0..16 {
+        if i == 8 {
+            print(" ");
+        }
+        match bytes.get(i) {
+            Some(&byte) => print_byte_hex(byte),
+            None => print("  "),
+        }
+        print(" ");
+    }
+
+    theme::apply(Role::Muted);
+    print("|");
+    reset_color();
+    for &byte in bytes {
+        match byte {
+            0x20..=0x7e => put_byte(byte),
+            _ => put_byte(b'.'),
+        }
+    }
+    theme::apply(Role::Muted);
+    println("|");
+    reset_color();
 }
@@ -0,0 +1,125 @@
+use core::ptr::addr_of_mut;
+
+pub const MAX_FILES: usize = 16;
+pub const MAX_FILE_SIZE: usize = 4096;
+pub const MAX_NAME_LEN: usize = 32;
+
+#[derive(Clone, Copy)]
+struct RamFile {
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+    data: [u8; MAX_FILE_SIZE],
+    size: usize,
+    used: bool,
+}
+
+impl RamFile {
+    const fn empty() -> RamFile {
+        RamFile {
+            name: [0; MAX_NAME_LEN],
+            name_len: 0,
+            data: [0; MAX_FILE_SIZE],
+            size: 0,
+            used: false,
+        }
+    }
+
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("?")
+    }
+}
+
+static mut FILES: [RamFile; MAX_FILES] = [RamFile::empty(); MAX_FILES];
+
+fn get_files() -> &'static mut [RamFile; MAX_FILES] {
+    unsafe { &mut *addr_of_mut!(FILES) }
+}
+
+fn get_file(fd: usize) -> Option<&'static mut RamFile> {
+    get_files().get_mut(fd).filter(|file| file.used)
+}
+
+pub fn find(name: &str) -> Option<usize> {
+    get_files()
+        .iter()
+        .position(|file| file.used && file.name() == name)
+}
+
+pub fn create(name: &str) -> Option<usize> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return None;
+    }
+
+    if let Some(fd) = find(name) {
+        truncate(fd, 0);
+        return Some(fd);
+    }
+
+    let files = get_files();
+    let fd = files.iter().position(|file| !file.used)?;
+    let file = &mut files[fd];
+
+    file.name[..name.len()].copy_from_slice(name.as_bytes());
+    file.name_len = name.len();
+    file.size = 0;
+    file.used = true;
+
+    Some(fd)
+}
+
+pub fn append(fd: usize, data: &[u8]) -> usize {
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return 0,
+    };
+
+    let count = core::cmp::min(data.len(), MAX_FILE_SIZE - file.size);
+    file.data[file.size..file.size + count].copy_from_slice(&data[..count]);
+    file.size += count;
+
+    count
+}
+
+pub fn truncate(fd: usize, size: usize) {
+    if let Some(file) = get_file(fd) {
+        if size < file.size {
+            file.size = size;
+        }
+    }
+}
+
+pub fn remove(name: &str) -> bool {
+    match find(name) {
+        Some(fd) => {
+            get_files()[fd].used = false;
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn read(fd: usize) -> &'static [u8] {
+    match get_file(fd) {
+        Some(file) => &file.data[..file.size],
+        None => &[],
+    }
+}
+
+pub fn name(fd: usize) -> &'static str {
+    match get_file(fd) {
+        Some(file) => file.name(),
+        None => "",
+    }
+}
+
+pub fn size(fd: usize) -> usize {
+    get_file(fd).map_or(0, |file| file.size)
+}
+
+pub fn is_full(fd: usize) -> bool {
+    size(fd) >= MAX_FILE_SIZE
+}
+
+pub fn files() -> impl Iterator<Item = usize> {
+    (0..MAX_FILES).filter(|&fd| get_files()[fd].used)
+}
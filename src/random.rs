@@ -0,0 +1,131 @@
+use crate::sync::IrqSpinlock;
+use crate::{cpuid, timer};
+use core::arch::asm;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const RDRAND_RETRIES: u32 = 10;
+const XORSHIFT_MULTIPLIER: u64 = 0x2545_F491_4F6C_DD1D;
+const SEED_MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+
+const SOURCE_UNKNOWN: u8 = 0;
+const SOURCE_RDRAND: u8 = 1;
+const SOURCE_XORSHIFT: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Rdrand,
+    Xorshift,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Rdrand => "rdrand",
+            Source::Xorshift => "xorshift",
+        }
+    }
+}
+
+static SOURCE: AtomicU8 = AtomicU8::new(SOURCE_UNKNOWN);
+static XORSHIFT_STATE: IrqSpinlock<u64> = IrqSpinlock::new(0);
+
+pub fn source() -> Source {
+    let source = match SOURCE.load(Ordering::SeqCst) {
+        SOURCE_UNKNOWN => {
+            let source = if cpuid::has_rdrand() {
+                SOURCE_RDRAND
+            } else {
+                SOURCE_XORSHIFT
+            };
+            SOURCE.store(source, Ordering::SeqCst);
+            source
+        }
+        source => source,
+    };
+
+    if source == SOURCE_RDRAND {
+        Source::Rdrand
+    } else {
+        Source::Xorshift
+    }
+}
+
+fn rdrand_u32() -> Option<u32> {
+    for _ in 0..RDRAND_RETRIES {
+        let value: u32;
+        let ok: u8;
+        unsafe {
+            asm!(
+                "rdrand {value}",
+                "setc {ok}",
+                value = out(reg) value,
+                ok = out(reg_byte) ok,
+                options(nomem, nostack)
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+pub fn rdtsc() -> u64 {
+    let (low, high): (u32, u32);
+    unsafe {
+        asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack));
+    }
+    (high as u64) << 32 | low as u64
+}
+
+fn seed() -> u64 {
+    let entropy = if cpuid::has_tsc() {
+        rdtsc()
+    } else {
+        timer::uptime_ms()
+    };
+    match (entropy ^ SEED_MIX).wrapping_mul(XORSHIFT_MULTIPLIER) {
+        0 => SEED_MIX,
+        seed => seed,
+    }
+}
+
+fn xorshift_u64() -> u64 {
+    let mut state = XORSHIFT_STATE.lock();
+    if *state == 0 {
+        *state = seed();
+    }
+
+    let mut x = *state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    x.wrapping_mul(XORSHIFT_MULTIPLIER)
+}
+
+pub fn rand_u64() -> Option<u64> {
+    match source() {
+        Source::Rdrand => {
+            let high = rdrand_u32()?;
+            let low = rdrand_u32()?;
+            Some((high as u64) << 32 | low as u64)
+        }
+        Source::Xorshift => Some(xorshift_u64()),
+    }
+}
+
+pub fn rand_range(lo: u64, hi: u64) -> Option<u64> {
+    if lo >= hi {
+        return None;
+    }
+
+    let span = hi - lo;
+    let limit = u64::MAX - u64::MAX % span;
+    loop {
+        let value = rand_u64()?;
+        if value < limit {
+            return Some(lo + value % span);
+        }
+    }
+}
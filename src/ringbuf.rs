@@ -0,0 +1,61 @@
+pub struct RingBuffer<T: Copy, const N: usize> {
+    items: [T; N],
+    start: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    pub const fn new(fill: T) -> RingBuffer<T, N> {
+        RingBuffer {
+            items: [fill; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    pub const fn capacity() -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+
+    pub fn push(&mut self, item: T) -> bool {
+        if self.is_full() {
+            return false;
+        }
+
+        self.items[(self.start + self.len) % N] = item;
+        self.len += 1;
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let item = self.items[self.start];
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+        Some(item)
+    }
+
+    pub fn peek(&self) -> Option<T> {
+        (!self.is_empty()).then(|| self.items[self.start])
+    }
+}
@@ -0,0 +1,153 @@
+use crate::io::{inb, outb};
+use crate::sync::without_interrupts;
+use core::fmt;
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+const NMI_DISABLE: u8 = 0x80;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_CENTURY: u8 = 0x32;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const HOUR_PM: u8 = 0x80;
+
+const UPDATE_WAIT_ATTEMPTS: u32 = 100_000;
+const DEFAULT_CENTURY: u16 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+fn read_register(register: u8) -> u8 {
+    unsafe {
+        outb(CMOS_ADDRESS, NMI_DISABLE | register);
+        inb(CMOS_DATA)
+    }
+}
+
+pub fn read_cmos(register: u8) -> u8 {
+    without_interrupts(|| read_register(register))
+}
+
+pub fn write_cmos(register: u8, value: u8) {
+    without_interrupts(|| unsafe {
+        outb(CMOS_ADDRESS, NMI_DISABLE | register);
+        outb(CMOS_DATA, value);
+    });
+}
+
+fn wait_for_update() -> bool {
+    for _ in 0..UPDATE_WAIT_ATTEMPTS {
+        if read_register(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS == 0 {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+    false
+}
+
+fn read_raw() -> Option<[u8; 7]> {
+    if !wait_for_update() {
+        return None;
+    }
+
+    Some([
+        read_register(REG_SECONDS),
+        read_register(REG_MINUTES),
+        read_register(REG_HOURS),
+        read_register(REG_DAY),
+        read_register(REG_MONTH),
+        read_register(REG_YEAR),
+        read_register(REG_CENTURY),
+    ])
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+pub fn now() -> Option<DateTime> {
+    without_interrupts(|| {
+        let mut raw = read_raw()?;
+        loop {
+            let again = read_raw()?;
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+
+        let status_b = read_register(REG_STATUS_B);
+        let [mut second, mut minute, hour_raw, mut day, mut month, mut year, mut century] = raw;
+        let pm = hour_raw & HOUR_PM != 0;
+        let mut hour = hour_raw & !HOUR_PM;
+
+        if status_b & STATUS_B_BINARY == 0 {
+            second = bcd_to_binary(second);
+            minute = bcd_to_binary(minute);
+            hour = bcd_to_binary(hour);
+            day = bcd_to_binary(day);
+            month = bcd_to_binary(month);
+            year = bcd_to_binary(year);
+            century = bcd_to_binary(century);
+        }
+
+        let century = match century {
+            19..=21 => century as u16,
+            _ => DEFAULT_CENTURY,
+        };
+
+        if status_b & STATUS_B_24_HOUR == 0 {
+            hour %= 12;
+            if pm {
+                hour += 12;
+            }
+        }
+
+        let time = DateTime {
+            year: century * 100 + year as u16,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        time.is_valid().then_some(time)
+    })
+}
@@ -0,0 +1,198 @@
+use crate::keyboard::{self, KeyEvent};
+use crate::kfs_lib::FixedBuffer;
+use crate::printk;
+use crate::sync::IrqSpinlock;
+use crate::vga::{Writer, VGA_BUFFER_WIDTH};
+use core::fmt::Write;
+
+pub const CLIPBOARD_SIZE: usize = 4096;
+
+static CLIPBOARD: IrqSpinlock<FixedBuffer<CLIPBOARD_SIZE>> = IrqSpinlock::new(FixedBuffer::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SelectionMode {
+    Line,
+    Rectangle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Moving,
+    Selecting,
+    Selected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Selection {
+    anchor: (usize, usize),
+    cursor: (usize, usize),
+    mode: SelectionMode,
+    state: State,
+}
+
+impl Selection {
+    fn start(&self) -> (usize, usize) {
+        match self.state {
+            State::Moving => self.cursor,
+            _ => self.anchor,
+        }
+    }
+
+    fn rows(&self) -> (usize, usize) {
+        let (_, start) = self.start();
+        let (_, end) = self.cursor;
+        (start.min(end), start.max(end))
+    }
+
+    fn columns(&self, row: usize) -> (usize, usize) {
+        let (start_x, start_y) = self.start();
+        let (end_x, end_y) = self.cursor;
+        match self.mode {
+            SelectionMode::Rectangle => (start_x.min(end_x), start_x.max(end_x)),
+            SelectionMode::Line => {
+                let ((first_x, first_y), (last_x, last_y)) = if (start_y, start_x) <= (end_y, end_x)
+                {
+                    ((start_x, start_y), (end_x, end_y))
+                } else {
+                    ((end_x, end_y), (start_x, start_y))
+                };
+                let first = if row == first_y { first_x } else { 0 };
+                let last = if row == last_y {
+                    last_x
+                } else {
+                    VGA_BUFFER_WIDTH - 1
+                };
+                (first, last)
+            }
+        }
+    }
+
+    fn toggle_highlight(&self) {
+        printk::with_screen(printk::active_screen(), |writer| {
+            let (top, bottom) = self.rows();
+            for y in top..=bottom {
+                let (first, last) = self.columns(y);
+                for x in first..=last {
+                    writer.invert_at(x, y);
+                }
+            }
+        });
+    }
+
+    fn copy(&self) -> usize {
+        let mut clipboard = CLIPBOARD.lock();
+        clipboard.clear();
+        printk::with_screen(printk::active_screen(), |writer| {
+            let (top, bottom) = self.rows();
+            for y in top..=bottom {
+                let (first, last) = self.columns(y);
+                copy_row(writer, &mut clipboard, y, first, last);
+                if y != bottom {
+                    let _ = clipboard.write_char('\n');
+                }
+            }
+        });
+        clipboard.len()
+    }
+}
+
+fn copy_row(
+    writer: &Writer,
+    clipboard: &mut FixedBuffer<CLIPBOARD_SIZE>,
+    y: usize,
+    first: usize,
+    last: usize,
+) {
+    let mut line = [b' '; VGA_BUFFER_WIDTH];
+    let mut len = 0;
+    for x in first..=last {
+        line[len] = match writer.read_char_at(x, y) {
+            Some(byte @ 0x20..=0x7E) => byte,
+            Some(0) | None => b' ',
+            Some(_) => b'?',
+        };
+        len += 1;
+    }
+    while len > 0 && line[len - 1] == b' ' {
+        len -= 1;
+    }
+    if let Ok(text) = core::str::from_utf8(&line[..len]) {
+        let _ = clipboard.write_str(text);
+    }
+}
+
+pub fn run() -> Option<usize> {
+    let (top, bottom) =
+        printk::with_screen(printk::active_screen(), |writer| writer.get_scroll_region())?;
+    let (column, row) = printk::cursor_position();
+    let cursor = (column.min(VGA_BUFFER_WIDTH - 1), row.clamp(top, bottom - 1));
+
+    let mut selection = Selection {
+        anchor: cursor,
+        cursor,
+        mode: SelectionMode::Line,
+        state: State::Moving,
+    };
+    selection.toggle_highlight();
+
+    loop {
+        let event = keyboard::wait_for_event();
+        let mut next = selection;
+        let (x, y) = next.cursor;
+        let moved = match event {
+            KeyEvent::Left => Some((x.saturating_sub(1), y)),
+            KeyEvent::Right => Some(((x + 1).min(VGA_BUFFER_WIDTH - 1), y)),
+            KeyEvent::Up => Some((x, y.saturating_sub(1).max(top))),
+            KeyEvent::Down => Some((x, (y + 1).min(bottom - 1))),
+            KeyEvent::Home => Some((0, y)),
+            KeyEvent::End => Some((VGA_BUFFER_WIDTH - 1, y)),
+            _ => None,
+        };
+
+        match (event, moved) {
+            (_, Some(position)) => {
+                if next.state == State::Selected {
+                    next.state = State::Moving;
+                }
+                next.cursor = position;
+            }
+            (KeyEvent::Char(' '), _) => {
+                next.state = match next.state {
+                    State::Moving | State::Selected => State::Selecting,
+                    State::Selecting => State::Selected,
+                };
+                if next.state == State::Selecting {
+                    next.anchor = next.cursor;
+                }
+            }
+            (KeyEvent::Tab, _) => {
+                next.mode = match next.mode {
+                    SelectionMode::Line => SelectionMode::Rectangle,
+                    SelectionMode::Rectangle => SelectionMode::Line,
+                };
+            }
+            (KeyEvent::Enter, _) => {
+                selection.toggle_highlight();
+                return match selection.state {
+                    State::Moving => None,
+                    _ => Some(selection.copy()),
+                };
+            }
+            (KeyEvent::Escape, _) => {
+                selection.toggle_highlight();
+                return None;
+            }
+            _ => {}
+        }
+
+        if next != selection {
+            selection.toggle_highlight();
+            selection = next;
+            selection.toggle_highlight();
+        }
+    }
+}
+
+pub fn with_clipboard<R>(f: impl FnOnce(&str) -> R) -> R {
+    f(CLIPBOARD.lock().as_str())
+}
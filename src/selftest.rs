@@ -0,0 +1,2259 @@
+use crate::block::MemoryDisk;
+use crate::elf::{self, ElfError};
+use crate::fs::layout;
+use crate::fs::ops::{self, Fs, FsError};
+use crate::gdt::{self, access, granularity, GdtEntry};
+use crate::idle::{self, IdlePolicy};
+use crate::keyboard::{
+    self, scancodes, CommandError, CommandPort, KeyEvent, Keyboard, MagicAction,
+};
+use crate::kfs_lib::{self, strtol, strtoul, FixedBuffer, HumanSize};
+use crate::line_editor::{self, LineEditor};
+use crate::memory::pmm::{MemoryRegion, RegionList};
+use crate::memory::{self, heap, paging, pmm, PAGE_SIZE};
+use crate::mouse::{self, Mouse};
+use crate::printk::{self, print, reset_color};
+use crate::ringbuf::RingBuffer;
+use crate::shell::{self, Shell};
+use crate::speaker::{Note, Player};
+use crate::stack_guard::{self, Owner};
+use crate::sync::{without_interrupts, IrqSpinlock};
+use crate::syscall::{self, errno, SyscallFrame};
+use crate::tar::TarFs;
+use crate::theme::{self, Role};
+use crate::vga::{self, Color, ColorCode, SavedScreen, Writer};
+use crate::{acpi, config, idt, klog, panic, pic, ramfs, random, stack, statusbar, timer};
+use core::fmt::Write;
+use core::ptr::{addr_of, addr_of_mut};
+
+type TestResult = Result<(), &'static str>;
+
+const STRESS_MAX_PRINTS: u32 = 1_000_000;
+
+const CAPTURE_SIZE: usize = 512;
+const FS_TEST_BLOCKS: usize = 32;
+
+static mut CAPTURED: FixedBuffer<CAPTURE_SIZE> = FixedBuffer::new();
+static mut TEST_EDITOR: LineEditor = LineEditor::new();
+static mut SAVED_SCREEN: SavedScreen = SavedScreen::new();
+
+static USER_ELF: &[u8] = include_bytes!("fixtures/user.elf");
+static OVERLAP_ELF: &[u8] = include_bytes!("fixtures/overlap.elf");
+static BAD_ENTRY_ELF: &[u8] = include_bytes!("fixtures/bad_entry.elf");
+static TEST_TAR: &[u8] = include_bytes!("fixtures/test.tar");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Memory,
+    String,
+    Interrupts,
+    Vga,
+    Keyboard,
+    Gdt,
+    Shell,
+    Fs,
+    Acpi,
+}
+
+pub const CATEGORIES: [Category; 9] = [
+    Category::Memory,
+    Category::String,
+    Category::Interrupts,
+    Category::Vga,
+    Category::Keyboard,
+    Category::Gdt,
+    Category::Shell,
+    Category::Fs,
+    Category::Acpi,
+];
+
+impl Category {
+    pub fn from_name(name: &str) -> Option<Category> {
+        CATEGORIES
+            .iter()
+            .copied()
+            .find(|category| category.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Memory => "memory",
+            Category::String => "string",
+            Category::Interrupts => "interrupts",
+            Category::Vga => "vga",
+            Category::Keyboard => "keyboard",
+            Category::Gdt => "gdt",
+            Category::Shell => "shell",
+            Category::Fs => "fs",
+            Category::Acpi => "acpi",
+        }
+    }
+}
+
+pub enum TestFn {
+    Plain(fn() -> TestResult),
+    Shell(fn(&mut Shell) -> TestResult),
+}
+
+pub struct SelfTest {
+    pub name: &'static str,
+    pub category: Category,
+    run: TestFn,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+pub static TESTS: &[SelfTest] = &[
+    SelfTest {
+        name: "frame_alloc",
+        category: Category::Memory,
+        run: TestFn::Plain(frame_alloc),
+    },
+    SelfTest {
+        name: "free_regions_skip_used",
+        category: Category::Memory,
+        run: TestFn::Plain(free_regions_skip_used),
+    },
+    SelfTest {
+        name: "region_coalesce",
+        category: Category::Memory,
+        run: TestFn::Plain(region_coalesce),
+    },
+    SelfTest {
+        name: "memory_stats_delta",
+        category: Category::Memory,
+        run: TestFn::Plain(memory_stats_delta),
+    },
+    #[cfg(feature = "alloc-debug")]
+    SelfTest {
+        name: "alloc_history",
+        category: Category::Memory,
+        run: TestFn::Plain(alloc_history),
+    },
+    SelfTest {
+        name: "kmalloc_pattern",
+        category: Category::Memory,
+        run: TestFn::Plain(kmalloc_pattern),
+    },
+    SelfTest {
+        name: "krealloc_preserves",
+        category: Category::Memory,
+        run: TestFn::Plain(krealloc_preserves),
+    },
+    SelfTest {
+        name: "kcalloc_zeroed",
+        category: Category::Memory,
+        run: TestFn::Plain(kcalloc_zeroed),
+    },
+    SelfTest {
+        name: "memset_rep",
+        category: Category::Memory,
+        run: TestFn::Plain(memset_rep),
+    },
+    SelfTest {
+        name: "memcpy_rep",
+        category: Category::Memory,
+        run: TestFn::Plain(memcpy_rep),
+    },
+    SelfTest {
+        name: "memzero_frame",
+        category: Category::Memory,
+        run: TestFn::Plain(memzero_frame),
+    },
+    SelfTest {
+        name: "stack_guard_pages",
+        category: Category::Memory,
+        run: TestFn::Plain(stack_guard_pages),
+    },
+    SelfTest {
+        name: "stack_overflow_report",
+        category: Category::Memory,
+        run: TestFn::Plain(stack_overflow_report),
+    },
+    SelfTest {
+        name: "elf_fixture",
+        category: Category::Memory,
+        run: TestFn::Plain(elf_fixture),
+    },
+    SelfTest {
+        name: "elf_truncated",
+        category: Category::Memory,
+        run: TestFn::Plain(elf_truncated),
+    },
+    SelfTest {
+        name: "elf_overlap",
+        category: Category::Memory,
+        run: TestFn::Plain(elf_overlap),
+    },
+    SelfTest {
+        name: "elf_bad_entry",
+        category: Category::Memory,
+        run: TestFn::Plain(elf_bad_entry),
+    },
+    SelfTest {
+        name: "strtoul",
+        category: Category::String,
+        run: TestFn::Plain(parse_unsigned),
+    },
+    SelfTest {
+        name: "strtol",
+        category: Category::String,
+        run: TestFn::Plain(parse_signed),
+    },
+    SelfTest {
+        name: "fixed_buffer",
+        category: Category::String,
+        run: TestFn::Plain(fixed_buffer),
+    },
+    SelfTest {
+        name: "ringbuf_wraparound",
+        category: Category::String,
+        run: TestFn::Plain(ringbuf_wraparound),
+    },
+    SelfTest {
+        name: "ringbuf_full_empty",
+        category: Category::String,
+        run: TestFn::Plain(ringbuf_full_empty),
+    },
+    SelfTest {
+        name: "human_size",
+        category: Category::String,
+        run: TestFn::Plain(human_size),
+    },
+    SelfTest {
+        name: "idt_loaded",
+        category: Category::Interrupts,
+        run: TestFn::Plain(idt_loaded),
+    },
+    SelfTest {
+        name: "pic_vectors",
+        category: Category::Interrupts,
+        run: TestFn::Plain(pic_vectors),
+    },
+    SelfTest {
+        name: "timer_ticks",
+        category: Category::Interrupts,
+        run: TestFn::Plain(timer_ticks),
+    },
+    SelfTest {
+        name: "irq_guard_restore",
+        category: Category::Interrupts,
+        run: TestFn::Plain(irq_guard_restore),
+    },
+    SelfTest {
+        name: "print_irq_stress",
+        category: Category::Interrupts,
+        run: TestFn::Plain(print_irq_stress),
+    },
+    SelfTest {
+        name: "syscall_bad_pointer",
+        category: Category::Interrupts,
+        run: TestFn::Plain(syscall_bad_pointer),
+    },
+    SelfTest {
+        name: "syscall_unknown",
+        category: Category::Interrupts,
+        run: TestFn::Plain(syscall_unknown),
+    },
+    SelfTest {
+        name: "exception_fallback",
+        category: Category::Interrupts,
+        run: TestFn::Plain(exception_fallback),
+    },
+    SelfTest {
+        name: "page_fault_decode",
+        category: Category::Interrupts,
+        run: TestFn::Plain(page_fault_decode),
+    },
+    SelfTest {
+        name: "pattern_player",
+        category: Category::Interrupts,
+        run: TestFn::Plain(pattern_player),
+    },
+    SelfTest {
+        name: "assert_klog",
+        category: Category::Interrupts,
+        run: TestFn::Plain(assert_klog),
+    },
+    SelfTest {
+        name: "idle_policy_fallback",
+        category: Category::Interrupts,
+        run: TestFn::Plain(idle_policy_fallback),
+    },
+    SelfTest {
+        name: "attribute_byte",
+        category: Category::Vga,
+        run: TestFn::Plain(attribute_byte),
+    },
+    SelfTest {
+        name: "background_clamp",
+        category: Category::Vga,
+        run: TestFn::Plain(background_clamp),
+    },
+    SelfTest {
+        name: "tab_stops",
+        category: Category::Vga,
+        run: TestFn::Plain(tab_stops),
+    },
+    SelfTest {
+        name: "sgr_reset",
+        category: Category::Vga,
+        run: TestFn::Plain(sgr_reset),
+    },
+    SelfTest {
+        name: "clear_keeps_status_row",
+        category: Category::Vga,
+        run: TestFn::Plain(clear_keeps_status_row),
+    },
+    SelfTest {
+        name: "clear_fills_buffer",
+        category: Category::Vga,
+        run: TestFn::Plain(clear_fills_buffer),
+    },
+    SelfTest {
+        name: "line_start",
+        category: Category::Vga,
+        run: TestFn::Plain(line_start),
+    },
+    SelfTest {
+        name: "switch_preserves_content",
+        category: Category::Vga,
+        run: TestFn::Plain(switch_preserves_content),
+    },
+    SelfTest {
+        name: "with_screen_isolation",
+        category: Category::Vga,
+        run: TestFn::Plain(with_screen_isolation),
+    },
+    SelfTest {
+        name: "history_order",
+        category: Category::Vga,
+        run: TestFn::Plain(history_order),
+    },
+    SelfTest {
+        name: "cell_bounds",
+        category: Category::Vga,
+        run: TestFn::Plain(cell_bounds),
+    },
+    SelfTest {
+        name: "scancode_translation",
+        category: Category::Keyboard,
+        run: TestFn::Plain(scancode_translation),
+    },
+    SelfTest {
+        name: "editor_kills",
+        category: Category::Keyboard,
+        run: TestFn::Plain(editor_kills),
+    },
+    SelfTest {
+        name: "editor_yank_full",
+        category: Category::Keyboard,
+        run: TestFn::Plain(editor_yank_full),
+    },
+    SelfTest {
+        name: "magic_actions",
+        category: Category::Keyboard,
+        run: TestFn::Plain(magic_actions),
+    },
+    SelfTest {
+        name: "magic_from_interrupt",
+        category: Category::Keyboard,
+        run: TestFn::Plain(magic_from_interrupt),
+    },
+    SelfTest {
+        name: "command_resend_once",
+        category: Category::Keyboard,
+        run: TestFn::Plain(command_resend_once),
+    },
+    SelfTest {
+        name: "command_resend_limit",
+        category: Category::Keyboard,
+        run: TestFn::Plain(command_resend_limit),
+    },
+    SelfTest {
+        name: "mouse_packet",
+        category: Category::Keyboard,
+        run: TestFn::Plain(mouse_packet),
+    },
+    SelfTest {
+        name: "mouse_resync",
+        category: Category::Keyboard,
+        run: TestFn::Plain(mouse_resync),
+    },
+    SelfTest {
+        name: "buffer_overflow",
+        category: Category::Keyboard,
+        run: TestFn::Plain(buffer_overflow),
+    },
+    SelfTest {
+        name: "soft_repeat",
+        category: Category::Keyboard,
+        run: TestFn::Plain(soft_repeat),
+    },
+    SelfTest {
+        name: "typematic_encoding",
+        category: Category::Keyboard,
+        run: TestFn::Plain(typematic_encoding),
+    },
+    SelfTest {
+        name: "entry_roundtrip",
+        category: Category::Gdt,
+        run: TestFn::Plain(entry_roundtrip),
+    },
+    SelfTest {
+        name: "limit_nibble",
+        category: Category::Gdt,
+        run: TestFn::Plain(limit_nibble),
+    },
+    SelfTest {
+        name: "loaded_table",
+        category: Category::Gdt,
+        run: TestFn::Plain(loaded_table),
+    },
+    SelfTest {
+        name: "tss_loaded",
+        category: Category::Gdt,
+        run: TestFn::Plain(tss_loaded),
+    },
+    SelfTest {
+        name: "command_success",
+        category: Category::Shell,
+        run: TestFn::Shell(command_success),
+    },
+    SelfTest {
+        name: "command_failure",
+        category: Category::Shell,
+        run: TestFn::Shell(command_failure),
+    },
+    SelfTest {
+        name: "command_unknown",
+        category: Category::Shell,
+        run: TestFn::Shell(command_unknown),
+    },
+    SelfTest {
+        name: "status_variable",
+        category: Category::Shell,
+        run: TestFn::Shell(status_variable),
+    },
+    SelfTest {
+        name: "script_recording",
+        category: Category::Shell,
+        run: TestFn::Shell(script_recording),
+    },
+    SelfTest {
+        name: "keyrate_validation",
+        category: Category::Shell,
+        run: TestFn::Shell(keyrate_validation),
+    },
+    SelfTest {
+        name: "abbreviation_expand",
+        category: Category::Shell,
+        run: TestFn::Shell(abbreviation_expand),
+    },
+    SelfTest {
+        name: "screen_switching",
+        category: Category::Shell,
+        run: TestFn::Shell(screen_switching),
+    },
+    SelfTest {
+        name: "greeting_boundaries",
+        category: Category::Shell,
+        run: TestFn::Plain(greeting_boundaries),
+    },
+    SelfTest {
+        name: "conditional_branches",
+        category: Category::Shell,
+        run: TestFn::Shell(conditional_branches),
+    },
+    SelfTest {
+        name: "run_script_order",
+        category: Category::Shell,
+        run: TestFn::Shell(run_script_order),
+    },
+    SelfTest {
+        name: "config_decode",
+        category: Category::Shell,
+        run: TestFn::Plain(config_decode),
+    },
+    SelfTest {
+        name: "fs_create_remove",
+        category: Category::Fs,
+        run: TestFn::Plain(fs_create_remove),
+    },
+    SelfTest {
+        name: "fs_name_collision",
+        category: Category::Fs,
+        run: TestFn::Plain(fs_name_collision),
+    },
+    SelfTest {
+        name: "fs_partial_block",
+        category: Category::Fs,
+        run: TestFn::Plain(fs_partial_block),
+    },
+    SelfTest {
+        name: "tar_sizes",
+        category: Category::Fs,
+        run: TestFn::Plain(tar_sizes),
+    },
+    SelfTest {
+        name: "tar_long_name",
+        category: Category::Fs,
+        run: TestFn::Plain(tar_long_name),
+    },
+    SelfTest {
+        name: "acpi_checksum",
+        category: Category::Acpi,
+        run: TestFn::Plain(acpi_checksum),
+    },
+    SelfTest {
+        name: "acpi_rsdp_v1",
+        category: Category::Acpi,
+        run: TestFn::Plain(acpi_rsdp_v1),
+    },
+    SelfTest {
+        name: "acpi_rsdp_v2",
+        category: Category::Acpi,
+        run: TestFn::Plain(acpi_rsdp_v2),
+    },
+];
+
+fn check(condition: bool, message: &'static str) -> TestResult {
+    if condition {
+        Ok(())
+    } else {
+        Err(message)
+    }
+}
+
+fn frame_alloc() -> TestResult {
+    let free = pmm::get_free_frames();
+    let frame = pmm::alloc_frame().ok_or("no free frame")?;
+    let aligned = frame.is_multiple_of(PAGE_SIZE);
+    let taken = pmm::get_free_frames() == free - 1;
+    pmm::free_frame(frame);
+
+    check(aligned, "frame is not page aligned")?;
+    check(taken, "free count did not drop")?;
+    check(pmm::get_free_frames() == free, "free count not restored")
+}
+
+fn free_regions_skip_used() -> TestResult {
+    let frame = pmm::alloc_frame().ok_or("no free frame")?;
+    let used = pmm::free_regions(frame, frame + PAGE_SIZE)
+        .as_slice()
+        .is_empty();
+    pmm::free_frame(frame);
+    let freed = pmm::free_regions(frame, frame + PAGE_SIZE);
+
+    check(used, "allocated frame listed as free")?;
+    check(
+        freed.as_slice()
+            == [MemoryRegion {
+                start: frame,
+                end: frame + PAGE_SIZE,
+            }],
+        "freed frame not listed",
+    )?;
+    check(
+        pmm::free_regions(0, 0x100000).as_slice().is_empty(),
+        "low memory listed as free",
+    )
+}
+
+fn region_coalesce() -> TestResult {
+    let mut list = RegionList::new();
+    for (start, end) in [
+        (0x9000, 0xA000),
+        (0x1000, 0x2000),
+        (0x2000, 0x3000),
+        (0x5000, 0x7000),
+        (0x6000, 0x8000),
+    ] {
+        list.push(start, end);
+    }
+    list.coalesce();
+
+    let region = |start, end| MemoryRegion { start, end };
+    check(
+        list.as_slice()
+            == [
+                region(0x1000, 0x3000),
+                region(0x5000, 0x8000),
+                region(0x9000, 0xA000),
+            ],
+        "regions not merged, or gap not kept",
+    )
+}
+
+fn memory_stats_delta() -> TestResult {
+    const BYTES: usize = 1000;
+    const FRAMES: usize = 2;
+
+    let (before, during, after) = without_interrupts(|| {
+        let before = memory::get_stats();
+        let ptr = heap::kmalloc(BYTES)?;
+        let Some(frames) = pmm::alloc_frames(FRAMES) else {
+            heap::kfree(ptr);
+            return None;
+        };
+        let during = memory::get_stats();
+        pmm::free_frames(frames, FRAMES);
+        heap::kfree(ptr);
+        Some((before, during, memory::get_stats()))
+    })
+    .ok_or("allocation failed")?;
+
+    let heap_delta = during.heap_used - before.heap_used;
+    check(
+        heap_delta >= heap::block_size_for(BYTES) && heap_delta < heap::block_size_for(BYTES) * 2,
+        "heap used delta does not match the allocation",
+    )?;
+    check(
+        before.heap_free - during.heap_free == heap_delta,
+        "heap free delta differs from used delta",
+    )?;
+    check(
+        during.heap_allocations == before.heap_allocations + 1,
+        "allocation count did not grow by one",
+    )?;
+    check(
+        during.used_frames == before.used_frames + FRAMES,
+        "used frame delta wrong",
+    )?;
+    check(
+        before.free_memory - during.free_memory == FRAMES * PAGE_SIZE,
+        "free memory delta wrong",
+    )?;
+    check(
+        after.heap_used == before.heap_used && after.used_frames == before.used_frames,
+        "stats not restored after freeing",
+    )
+}
+
+#[cfg(feature = "alloc-debug")]
+fn alloc_history() -> TestResult {
+    const SIZES: [usize; 3] = [24, 48, 96];
+
+    let recorded = without_interrupts(|| {
+        let ptrs = SIZES.map(|size| heap::kmalloc(size).unwrap_or_default());
+        let mut recorded = [0; SIZES.len()];
+        for record in heap::recent_allocations() {
+            recorded.rotate_left(1);
+            recorded[SIZES.len() - 1] = record.size;
+        }
+        ptrs.into_iter().for_each(heap::kfree);
+        recorded
+    });
+
+    check(
+        recorded == SIZES,
+        "recent allocations not recorded in order",
+    )
+}
+
+fn kmalloc_pattern() -> TestResult {
+    let size = 512;
+    let ptr = heap::kmalloc(size).ok_or("kmalloc failed")?;
+    let block = unsafe { core::slice::from_raw_parts_mut(ptr, size) };
+    for (index, byte) in block.iter_mut().enumerate() {
+        *byte = index as u8 ^ 0x5A;
+    }
+    let intact = block
+        .iter()
+        .enumerate()
+        .all(|(index, &byte)| byte == index as u8 ^ 0x5A);
+    let fits = heap::ksize(ptr) >= size;
+    heap::kfree(ptr);
+
+    check(intact, "pattern corrupted")?;
+    check(fits, "ksize smaller than request")
+}
+
+fn krealloc_preserves() -> TestResult {
+    let ptr = heap::kmalloc(16).ok_or("kmalloc failed")?;
+    unsafe { core::ptr::copy_nonoverlapping(b"0123456789abcdef".as_ptr(), ptr, 16) };
+    let grown = match heap::krealloc(ptr, 256) {
+        Some(grown) => grown,
+        None => {
+            heap::kfree(ptr);
+            return Err("krealloc failed");
+        }
+    };
+    let kept = unsafe { core::slice::from_raw_parts(grown, 16) } == b"0123456789abcdef";
+    heap::kfree(grown);
+
+    check(kept, "contents lost on resize")
+}
+
+fn kcalloc_zeroed() -> TestResult {
+    let ptr = heap::kcalloc(32, 4).ok_or("kcalloc failed")?;
+    let zeroed = unsafe { core::slice::from_raw_parts(ptr, 128) }
+        .iter()
+        .all(|&byte| byte == 0);
+    heap::kfree(ptr);
+
+    check(zeroed, "memory not zeroed")
+}
+
+const MEM_TEST_BUFFER: usize = 512;
+const MEM_TEST_ROUNDS: usize = 64;
+
+pub fn byte_fill(dest: *mut u8, value: u8, len: usize) {
+    for index in 0..len {
+        unsafe { core::ptr::write_volatile(dest.add(index), value) };
+    }
+}
+
+pub fn byte_copy(dest: *mut u8, src: *const u8, len: usize) {
+    for index in 0..len {
+        unsafe { core::ptr::write_volatile(dest.add(index), *src.add(index)) };
+    }
+}
+
+fn mem_cases() -> impl Iterator<Item = (usize, usize, usize)> {
+    let small = (0..=16).flat_map(|len| (0..4).map(move |offset| (len, offset, 3 - offset)));
+    let random = (0..MEM_TEST_ROUNDS).map(|_| {
+        let pick = |hi| random::rand_range(0, hi).unwrap_or(0) as usize;
+        (pick(MEM_TEST_BUFFER as u64 - 8), pick(8), pick(8))
+    });
+    small.chain(random)
+}
+
+fn memset_rep() -> TestResult {
+    let mut fast = [0u8; MEM_TEST_BUFFER];
+    let mut slow = [0u8; MEM_TEST_BUFFER];
+
+    for (len, offset, _) in mem_cases() {
+        let value = (len ^ offset) as u8 | 1;
+        byte_fill(fast.as_mut_ptr(), 0xEE, MEM_TEST_BUFFER);
+        byte_fill(slow.as_mut_ptr(), 0xEE, MEM_TEST_BUFFER);
+        let result = unsafe { kfs_lib::memset(fast.as_mut_ptr().add(offset), value, len) };
+        byte_fill(slow.as_mut_ptr().wrapping_add(offset), value, len);
+
+        check(
+            result == fast.as_mut_ptr().wrapping_add(offset),
+            "wrong return pointer",
+        )?;
+        check(fast == slow, "differs from the byte loop")?;
+    }
+    Ok(())
+}
+
+fn memcpy_rep() -> TestResult {
+    let mut source = [0u8; MEM_TEST_BUFFER];
+    let mut fast = [0u8; MEM_TEST_BUFFER];
+    let mut slow = [0u8; MEM_TEST_BUFFER];
+    for (index, byte) in source.iter_mut().enumerate() {
+        *byte = (index * 31) as u8;
+    }
+
+    for (len, dest_offset, src_offset) in mem_cases() {
+        byte_fill(fast.as_mut_ptr(), 0xEE, MEM_TEST_BUFFER);
+        byte_fill(slow.as_mut_ptr(), 0xEE, MEM_TEST_BUFFER);
+        let dest = fast.as_mut_ptr().wrapping_add(dest_offset);
+        let src = source.as_ptr().wrapping_add(src_offset);
+        let result = unsafe { kfs_lib::memcpy(dest, src, len) };
+        byte_copy(slow.as_mut_ptr().wrapping_add(dest_offset), src, len);
+
+        check(result == dest, "wrong return pointer")?;
+        check(fast == slow, "differs from the byte loop")?;
+    }
+    Ok(())
+}
+
+fn memzero_frame() -> TestResult {
+    let frame = pmm::alloc_frame().ok_or("no free frame")?;
+    byte_fill(frame as *mut u8, 0xFF, PAGE_SIZE);
+    kfs_lib::memzero_frame(frame);
+    let zeroed = unsafe { core::slice::from_raw_parts(frame as *const u8, PAGE_SIZE) }
+        .iter()
+        .all(|&byte| byte == 0);
+    pmm::free_frame(frame);
+
+    check(zeroed, "frame not zeroed")
+}
+
+fn stack_guard_pages() -> TestResult {
+    for owner in [Owner::Boot, Owner::Syscall, Owner::Task(1)] {
+        let guard = stack_guard::guard_page(owner);
+        check(guard.is_multiple_of(PAGE_SIZE), "guard page not aligned")?;
+        check(!paging::is_mapped(guard), "guard page is mapped")?;
+        check(
+            paging::is_mapped(guard + PAGE_SIZE),
+            "stack bottom not mapped",
+        )?;
+        check(
+            stack_guard::guard_owner(guard + PAGE_SIZE - 4) == Some(owner),
+            "guard owner lookup",
+        )?;
+    }
+    check(
+        stack_guard::guard_owner(stack::get_esp() as usize).is_none(),
+        "live stack reported as guard",
+    )
+}
+
+fn stack_overflow_report() -> TestResult {
+    let guard = stack_guard::guard_page(Owner::Task(1));
+    let reported = capture(|| idt::report_stack_overflow((guard + PAGE_SIZE - 4) as u32));
+
+    check(reported, "guard page hit not reported")?;
+    check(
+        captured_contains("STACK OVERFLOW: task 1 stack hit its guard page"),
+        "overflow message missing",
+    )?;
+    check(
+        !capture(|| idt::report_stack_overflow(stack::get_esp())),
+        "live stack reported as overflow",
+    )
+}
+
+fn elf_fixture() -> TestResult {
+    let image = elf::parse(USER_ELF).map_err(|error| error.description())?;
+    let segments = image.segments();
+
+    check(segments.len() == 2, "expected text and data segments")?;
+    check(
+        image.entry == segments[0].vaddr,
+        "entry is not the start of text",
+    )?;
+    check(!segments[0].is_writable(), "text segment is writable")?;
+    check(segments[1].is_writable(), "data segment is read-only")
+}
+
+fn elf_truncated() -> TestResult {
+    let truncated = |len: usize| elf::parse(&USER_ELF[..len]).err() == Some(ElfError::Truncated);
+    let image = elf::parse(USER_ELF).map_err(|error| error.description())?;
+    let data = image.segments()[1];
+
+    check(truncated(40), "short header accepted")?;
+    check(truncated(64), "cut program headers accepted")?;
+    check(
+        truncated(data.offset + data.file_size - 1),
+        "cut segment data accepted",
+    )
+}
+
+fn elf_overlap() -> TestResult {
+    check(
+        elf::parse(OVERLAP_ELF).err() == Some(ElfError::OverlappingSegments),
+        "overlapping segments accepted",
+    )
+}
+
+fn elf_bad_entry() -> TestResult {
+    check(
+        elf::parse(BAD_ENTRY_ELF).err() == Some(ElfError::EntryOutsideSegment),
+        "entry outside segments accepted",
+    )
+}
+
+fn parse_unsigned() -> TestResult {
+    check(strtoul("42", 10) == Some(42), "decimal")?;
+    check(strtoul("0x1F", 0) == Some(0x1F), "hex prefix")?;
+    check(strtoul("0b101", 0) == Some(5), "binary prefix")?;
+    check(strtoul("1_000", 10) == Some(1000), "digit separator")?;
+    check(strtoul("12z", 10).is_none(), "trailing garbage")?;
+    check(strtoul("", 10).is_none(), "empty string")?;
+    check(
+        strtoul("18446744073709551616", 10).is_none(),
+        "overflow not detected",
+    )
+}
+
+fn parse_signed() -> TestResult {
+    check(strtol("-17", 10) == Some(-17), "negative")?;
+    check(strtol("+8", 10) == Some(8), "explicit plus")?;
+    check(
+        strtol("-9223372036854775808", 10) == Some(i64::MIN),
+        "minimum value",
+    )?;
+    check(
+        strtol("9223372036854775808", 10).is_none(),
+        "overflow not detected",
+    )
+}
+
+fn fixed_buffer() -> TestResult {
+    let mut buffer = FixedBuffer::<8>::new();
+    check(write!(buffer, "{}", 1234).is_ok(), "short write failed")?;
+    check(buffer.as_str() == "1234", "wrong contents")?;
+    check(write!(buffer, "56789").is_err(), "overflow not reported")?;
+    check(buffer.as_str() == "12345678", "not truncated at capacity")
+}
+
+fn ringbuf_wraparound() -> TestResult {
+    let mut ring = RingBuffer::<u8, 4>::new(0);
+    for round in 0..10u8 {
+        check(ring.push(round), "push failed with free space")?;
+        check(ring.push(round + 100), "second push failed")?;
+        check(ring.pop() == Some(round), "wrong order after wrap")?;
+        check(
+            ring.pop() == Some(round + 100),
+            "second item lost after wrap",
+        )?;
+    }
+    check(ring.is_empty(), "not empty after draining")
+}
+
+fn ringbuf_full_empty() -> TestResult {
+    let mut ring = RingBuffer::<u8, 4>::new(0);
+    check(ring.is_empty() && !ring.is_full(), "new buffer not empty")?;
+    check(ring.pop().is_none(), "pop from empty buffer")?;
+
+    for item in 0..4 {
+        check(ring.push(item), "buffer full too early")?;
+    }
+    check(ring.is_full(), "all slots used but not full")?;
+    check(ring.len() == 4, "full length wrong")?;
+    check(!ring.push(4), "push into full buffer accepted")?;
+    check(ring.peek() == Some(0), "oldest item overwritten")?;
+
+    for item in 0..4 {
+        check(ring.pop() == Some(item), "items out of order")?;
+    }
+    check(
+        ring.is_empty() && ring.pop().is_none(),
+        "not empty after draining",
+    )
+}
+
+fn human_size() -> TestResult {
+    let mut buffer = FixedBuffer::<16>::new();
+    let _ = write!(buffer, "{}", HumanSize(512));
+    check(buffer.as_str() == "512 B", "bytes")?;
+
+    buffer.clear();
+    let _ = write!(buffer, "{}", HumanSize(1536));
+    check(buffer.as_str() == "1.5 KiB", "kibibytes")?;
+
+    buffer.clear();
+    let _ = write!(buffer, "{}", HumanSize(3 * 1024 * 1024));
+    check(buffer.as_str() == "3.0 MiB", "mebibytes")
+}
+
+fn idt_loaded() -> TestResult {
+    let (base, limit) = idt::get_idt_info();
+    check(base != 0, "IDT base is null")?;
+    check(
+        limit as usize == idt::IDT_ENTRIES * 8 - 1,
+        "IDT limit does not cover 256 gates",
+    )
+}
+
+fn pic_vectors() -> TestResult {
+    check(
+        pic::irq_for_vector(pic::PIC1_OFFSET) == Some(pic::IRQ_TIMER),
+        "timer vector",
+    )?;
+    check(
+        pic::irq_for_vector(pic::PIC2_OFFSET + 7) == Some(pic::IRQ_SPURIOUS_SLAVE),
+        "slave vector",
+    )?;
+    check(
+        pic::irq_for_vector(pic::PIC1_OFFSET - 1).is_none(),
+        "exception vector mapped to an IRQ",
+    )
+}
+
+fn timer_ticks() -> TestResult {
+    check(timer::is_initialized(), "PIT not initialized")?;
+    check(idt::interrupts_enabled(), "interrupts disabled")?;
+
+    let start = timer::ticks();
+    timer::sleep_ms(20);
+    check(timer::ticks() != start, "timer did not tick")
+}
+
+fn irq_guard_restore() -> TestResult {
+    let enabled = idt::interrupts_enabled();
+    let outer = IrqSpinlock::new(0u32);
+    let inner = IrqSpinlock::new(0u32);
+
+    idt::disable_interrupts();
+    drop(outer.lock());
+    let stayed_off = !idt::interrupts_enabled();
+    without_interrupts(|| {});
+    let helper_stayed_off = !idt::interrupts_enabled();
+
+    idt::enable_interrupts();
+    let guard = outer.lock();
+    let masked = !idt::interrupts_enabled();
+    drop(inner.lock());
+    let nested_kept_off = !idt::interrupts_enabled();
+    drop(guard);
+    let restored = idt::interrupts_enabled();
+    let held = outer.lock();
+    let contended = outer.try_lock().is_none();
+    drop(held);
+    let try_restored = idt::interrupts_enabled();
+
+    if !enabled {
+        idt::disable_interrupts();
+    }
+    check(stayed_off, "guard enabled interrupts that were off")?;
+    check(helper_stayed_off, "without_interrupts enabled interrupts")?;
+    check(masked, "guard did not disable interrupts")?;
+    check(nested_kept_off, "inner guard re-enabled interrupts")?;
+    check(restored, "outer guard did not restore interrupts")?;
+    check(contended, "try_lock succeeded on a held lock")?;
+    check(try_restored, "failed try_lock left interrupts off")
+}
+
+fn print_irq_stress() -> TestResult {
+    check(idt::interrupts_enabled(), "interrupts disabled")?;
+    let statusbar_was_enabled = statusbar::is_enabled();
+    statusbar::set_enabled(true);
+
+    let start = timer::ticks();
+    let target = 3 * statusbar::REFRESH_TICKS;
+    let mut prints = 0u32;
+    borrow_spare(|spare| {
+        while timer::ticks().wrapping_sub(start) < target && prints < STRESS_MAX_PRINTS {
+            printk::with_screen(spare, |writer| writer.write_string("*\x08"));
+            prints += 1;
+        }
+    });
+
+    statusbar::set_enabled(statusbar_was_enabled);
+    check(
+        timer::ticks().wrapping_sub(start) >= target,
+        "timer stalled while printing",
+    )
+}
+
+fn syscall_result(eax: u32, ebx: u32, ecx: u32, edx: u32) -> i32 {
+    let mut frame = SyscallFrame {
+        eax,
+        ebx,
+        ecx,
+        edx,
+        ..SyscallFrame::default()
+    };
+    syscall::dispatch(&mut frame);
+    frame.eax as i32
+}
+
+fn syscall_bad_pointer() -> TestResult {
+    let (kernel_start, _) = memory::kernel_image();
+    let kernel = kernel_start as u32;
+
+    check(
+        syscall_result(syscall::SYS_WRITE, 1, kernel, 16) == -errno::EFAULT,
+        "write from kernel memory not rejected",
+    )?;
+    check(
+        syscall_result(syscall::SYS_READ, 0, kernel, 16) == -errno::EFAULT,
+        "read into kernel memory not rejected",
+    )?;
+    check(
+        syscall_result(syscall::SYS_WRITE, 1, 0xFFFF_FFF0, 32) == -errno::EFAULT,
+        "wrapping buffer not rejected",
+    )
+}
+
+fn syscall_unknown() -> TestResult {
+    check(
+        syscall_result(2, 0, 0, 0) == -errno::ENOSYS,
+        "unassigned number not rejected",
+    )?;
+    check(
+        syscall_result(u32::MAX, 0, 0, 0) == -errno::ENOSYS,
+        "out-of-table number not rejected",
+    )
+}
+
+fn exception_fallback() -> TestResult {
+    let active = printk::active_screen();
+    let frame = idt::InterruptStackFrame {
+        eip: 0x0010_1234,
+        cs: 0x08,
+        eflags: 0x202,
+    };
+
+    let reached = printk::with_screen(active, |writer| {
+        let row = (printk::raw_row() + 1) % vga::VGA_BUFFER_HEIGHT;
+        idt::print_exception("Selftest", &frame, None);
+        let expected = b"EXCEPTION: Selftest";
+        let reached = expected
+            .iter()
+            .enumerate()
+            .all(|(x, &byte)| printk::read_raw(x, row) == Some(byte));
+        writer.repaint();
+        reached
+    })
+    .ok_or("no active screen")?;
+
+    check(
+        reached,
+        "exception output lost while the console was locked",
+    )
+}
+
+fn pattern_player() -> TestResult {
+    static PATTERN: &[Note] = &[(440, 2), (880, 3)];
+
+    let mut player = Player::new();
+    let mut sounds = [u32::MAX; 4];
+    let mut count = 0;
+    let mut sound = |frequency| {
+        if let Some(slot) = sounds.get_mut(count) {
+            *slot = frequency;
+        }
+        count += 1;
+    };
+
+    check(player.push(PATTERN), "pattern not queued")?;
+    player.start_note(&mut sound);
+    let mut ticks = 0;
+    while player.is_playing() && ticks < 10 {
+        player.tick(&mut sound);
+        ticks += 1;
+    }
+
+    check(ticks == 5, "pattern did not last five ticks")?;
+    check(count == 3, "wrong number of tone changes")?;
+    check(sounds[..3] == [440, 880, 0], "notes played out of order")
+}
+
+fn assert_klog() -> TestResult {
+    let location = panic::record_assert("selftest assertion");
+    let mut expected = FixedBuffer::<96>::new();
+    let _ = write!(
+        expected,
+        "assertion failed: selftest assertion at {}:{}",
+        location.file(),
+        location.line()
+    );
+
+    check(
+        location.file().ends_with("selftest.rs"),
+        "location is not the caller",
+    )?;
+    check(
+        klog::tail(1).any(|line| line.as_str() == expected.as_str()),
+        "assertion not recorded in klog",
+    )
+}
+
+fn idle_policy_fallback() -> TestResult {
+    check(
+        idle::select_policy(IdlePolicy::Mwait, false) == IdlePolicy::Halt,
+        "mwait kept without CPU support",
+    )?;
+    check(
+        idle::select_policy(IdlePolicy::Mwait, true) == IdlePolicy::Mwait,
+        "mwait replaced despite CPU support",
+    )
+}
+
+fn page_fault_decode() -> TestResult {
+    check(
+        idt::describe_page_fault(0) == ["page not present", "read", "kernel mode", "", ""],
+        "error code 0 misdecoded",
+    )?;
+    check(
+        idt::describe_page_fault(0x1F)
+            == [
+                "protection violation",
+                "write",
+                "user mode",
+                "reserved bit set",
+                "instruction fetch",
+            ],
+        "error code 0x1F misdecoded",
+    )?;
+    check(
+        idt::fault_region(0x10) == Some("null page"),
+        "null page not annotated",
+    )?;
+    check(
+        idt::fault_region(memory::KERNEL_HEAP_START as u32) == Some("kernel heap"),
+        "heap not annotated",
+    )
+}
+
+fn attribute_byte() -> TestResult {
+    check(
+        ColorCode::new(Color::White, Color::Blue).as_u8() == 0x1F,
+        "fg/bg nibbles",
+    )?;
+    check(
+        ColorCode::with_blink(Color::Yellow, Color::Red, true).as_u8() == 0xCE,
+        "blink bit not set",
+    )?;
+    check(
+        ColorCode::with_blink(Color::Yellow, Color::Red, false).as_u8() == 0x4E,
+        "blink bit not cleared",
+    )
+}
+
+fn background_clamp() -> TestResult {
+    let expected = if vga::blink_mode() {
+        Color::Blue
+    } else {
+        Color::LightBlue
+    };
+    check(
+        vga::clamp_background(Color::LightBlue) == expected,
+        "bright background not handled for blink mode",
+    )?;
+    check(
+        vga::clamp_background(Color::Red) == Color::Red,
+        "dark background changed",
+    )
+}
+
+/// Lends the screen after the active one to `f`, putting back its
+/// contents and cursor afterwards so the user's screen is left alone.
+fn borrow_spare<R>(f: impl FnOnce(usize) -> R) -> R {
+    let spare = (printk::active_screen() + 1) % vga::MAX_SCREENS;
+    let saved = unsafe { &mut *addr_of_mut!(SAVED_SCREEN) };
+    printk::with_screen(spare, |writer| writer.save(saved));
+    let result = f(spare);
+    printk::with_screen(spare, |writer| writer.restore(saved));
+    result
+}
+
+fn spare_screen<R>(f: impl FnOnce(&mut Writer) -> R) -> Option<R> {
+    borrow_spare(|spare| printk::with_screen(spare, f))
+}
+
+fn clear_keeps_status_row() -> TestResult {
+    let status_row = vga::VGA_BUFFER_HEIGHT - 1;
+
+    spare_screen(|writer| {
+        let (top, bottom) = writer.scroll_region();
+        writer.set_scroll_region(0, status_row);
+        writer.write_str_at(0, status_row, "status", writer.get_color());
+        writer.write_string("body");
+        let (_, body_row) = writer.get_cursor_position();
+
+        writer.clear_screen();
+        let kept = writer.read_char_at(0, status_row) == Some(b's');
+        let cleared = writer.read_char_at(0, body_row) == Some(b' ')
+            && writer.get_cursor_position() == (0, 0);
+        writer.clear_all();
+        let wiped = writer.read_char_at(0, status_row) == Some(b' ');
+        writer.set_scroll_region(top, bottom);
+
+        check(kept, "clear erased the status row")?;
+        check(cleared, "clear left text in the scroll region")?;
+        check(wiped, "clear --all kept the status row")
+    })
+    .ok_or("no spare screen")?
+}
+
+fn clear_fills_buffer() -> TestResult {
+    let (width, height) = (vga::VGA_BUFFER_WIDTH, vga::VGA_BUFFER_HEIGHT);
+
+    spare_screen(|writer| {
+        let color = writer.get_color();
+        writer.fill_rect(0, 0, width, height, b'#', color);
+        writer.clear_all();
+        let blank =
+            (0..height).all(|y| (0..width).all(|x| writer.read_char_at(x, y) == Some(b' ')));
+        check(blank, "clear left cells unfilled")
+    })
+    .ok_or("no spare screen")?
+}
+
+fn line_start() -> TestResult {
+    spare_screen(|writer| {
+        writer.write_string("\n");
+        let after_newline = writer.at_line_start();
+        writer.write_string("x");
+        let after_text = writer.at_line_start();
+        writer.write_string("\r");
+        let after_return = writer.at_line_start();
+        writer.clear_line();
+
+        check(after_newline, "not at line start after a newline")?;
+        check(!after_text, "at line start after text")?;
+        check(after_return, "not at line start after a carriage return")
+    })
+    .ok_or("no spare screen")?
+}
+
+fn switch_preserves_content() -> TestResult {
+    borrow_spare(|other| {
+        let active = printk::active_screen();
+        let snapshot = |id| {
+            printk::with_screen(id, |writer| {
+                let (column, row) = writer.get_cursor_position();
+                let previous = column
+                    .checked_sub(1)
+                    .and_then(|x| writer.read_char_at(x, row));
+                (column, row, previous)
+            })
+        };
+
+        printk::with_screen(other, |writer| writer.write_string("\nkept"));
+        let (active_before, other_before) = (snapshot(active), snapshot(other));
+        printk::switch_screen(other);
+        printk::switch_screen(active);
+
+        check(
+            other_before.is_some_and(|(_, _, previous)| previous == Some(b't')),
+            "text not written to the other screen",
+        )?;
+        check(snapshot(other) == other_before, "other screen changed")?;
+        check(snapshot(active) == active_before, "active screen changed")
+    })
+}
+
+fn fingerprint(id: usize) -> Option<(u32, (usize, usize))> {
+    printk::with_screen(id, |writer| {
+        let (top, bottom) = writer.scroll_region();
+        let mut hash = 0x811C_9DC5u32;
+        for y in top..bottom {
+            for x in 0..vga::VGA_BUFFER_WIDTH {
+                let byte = writer.read_char_at(x, y).unwrap_or(0);
+                hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+            }
+        }
+        (hash, writer.get_cursor_position())
+    })
+}
+
+fn with_screen_isolation() -> TestResult {
+    borrow_spare(|other| {
+        let active = printk::active_screen();
+        let (visible_before, other_before) = (fingerprint(active), fingerprint(other));
+        printk::with_screen(other, |writer| writer.write_string("\nwritten off screen"));
+
+        check(
+            printk::active_screen() == active,
+            "with_screen changed the active screen",
+        )?;
+        check(
+            fingerprint(active) == visible_before,
+            "write leaked onto the visible screen",
+        )?;
+        check(
+            fingerprint(other) != other_before,
+            "other screen not written",
+        )
+    })
+}
+
+fn history_order() -> TestResult {
+    spare_screen(|writer| {
+        for letter in b'a'..=b'z' {
+            writer.write_string("history ");
+            writer.write_byte(letter);
+            writer.write_byte(b'\n');
+        }
+
+        let mut next = None;
+        writer.export_history(&mut |line| {
+            let letter = match line.strip_prefix("history ") {
+                Some(letter) if letter.len() == 1 => letter.as_bytes()[0],
+                _ => return,
+            };
+            next = match next {
+                _ if letter == b'a' => Some(b'b'),
+                Some(expected) if letter == expected => Some(expected + 1),
+                _ => Some(0),
+            };
+        });
+        check(next == Some(b'z' + 1), "history not exported oldest first")
+    })
+    .ok_or("no spare screen")?
+}
+
+fn cell_bounds() -> TestResult {
+    let (last_x, last_y) = (vga::VGA_BUFFER_WIDTH - 1, vga::VGA_BUFFER_HEIGHT - 1);
+
+    spare_screen(|writer| {
+        let inside = writer.invert_at(last_x, last_y) && writer.invert_at(last_x, last_y);
+        let past_row =
+            writer.invert_at(last_x + 1, 0) || writer.read_char_at(last_x + 1, 0).is_some();
+        let past_end =
+            writer.invert_at(0, last_y + 1) || writer.read_char_at(0, last_y + 1).is_some();
+
+        check(inside, "last cell rejected")?;
+        check(!past_row, "column past the row end accepted")?;
+        check(!past_end, "row past the buffer end accepted")
+    })
+    .ok_or("no spare screen")?
+}
+
+fn tab_stops() -> TestResult {
+    spare_screen(|writer| {
+        writer.clear_line();
+        writer.write_string("ab\tX");
+        let (column, row) = writer.get_cursor_position();
+        let tabbed = column == 9 && writer.read_char_at(8, row) == Some(b'X');
+        writer.write_string("\rY");
+        let returned =
+            writer.get_cursor_position() == (1, row) && writer.read_char_at(0, row) == Some(b'Y');
+        writer.clear_line();
+        check(tabbed, "tab did not stop at column 8")?;
+        check(returned, "carriage return did not reach column 0")
+    })
+    .ok_or("no spare screen")?
+}
+
+fn sgr_reset() -> TestResult {
+    let (fg, bg) = theme::colors(Role::Normal);
+    let normal = ColorCode::new(fg, bg).as_u8();
+    let (reset, foreground, background) = spare_screen(|writer| {
+        let saved = writer.get_color();
+        writer.set_color(ColorCode::new(Color::Red, Color::Green));
+        writer.write_string("\x1b[0m");
+        let reset = writer.get_color().as_u8();
+        writer.write_string("\x1b[31m\x1b[44m\x1b[39m");
+        let foreground = writer.get_color().as_u8();
+        writer.write_string("\x1b[49m");
+        let background = writer.get_color().as_u8();
+        writer.set_color(saved);
+        (reset, foreground, background)
+    })
+    .ok_or("no spare screen")?;
+
+    check(reset == normal, "ESC[0m did not restore the theme colors")?;
+    check(
+        foreground & 0x0F == normal & 0x0F,
+        "ESC[39m did not restore the theme foreground",
+    )?;
+    check(
+        background == normal,
+        "ESC[49m did not restore the theme background",
+    )
+}
+
+fn scancode_translation() -> TestResult {
+    let mut keyboard = Keyboard::<4>::new();
+    check(
+        keyboard.process_scancode(0x1E) == Some(KeyEvent::Char('a')),
+        "plain letter",
+    )?;
+    check(keyboard.process_scancode(0x9E).is_none(), "release event")?;
+
+    keyboard.process_scancode(scancodes::LEFT_SHIFT);
+    let shifted = keyboard.process_scancode(0x1E);
+    keyboard.process_scancode(scancodes::LEFT_SHIFT | 0x80);
+    check(shifted == Some(KeyEvent::Char('A')), "shifted letter")?;
+
+    keyboard.process_scancode(scancodes::CTRL);
+    let chord = keyboard.process_scancode(0x2E);
+    keyboard.process_scancode(scancodes::CTRL | 0x80);
+    check(chord == Some(KeyEvent::Ctrl('c')), "control chord")?;
+    check(
+        chord.and_then(KeyEvent::control_code) == Some(0x03),
+        "control code",
+    )?;
+
+    check(
+        keyboard.process_scancode(scancodes::ENTER) == Some(KeyEvent::Enter),
+        "enter",
+    )
+}
+
+fn mouse_packet() -> TestResult {
+    let event = mouse::decode([0x09, 5, 3]);
+    check(
+        event.dx == 5 && event.dy == 3 && event.left() && !event.right(),
+        "positive motion",
+    )?;
+    let event = mouse::decode([0x3A, 0xFB, 0xFE]);
+    check(
+        event.dx == -5 && event.dy == -2 && event.right() && !event.left(),
+        "negative motion",
+    )?;
+    let event = mouse::decode([0xC8, 0x80, 0x80]);
+    check(event.dx == 0 && event.dy == 0, "overflow not discarded")
+}
+
+fn mouse_resync() -> TestResult {
+    let mut mouse = Mouse::new();
+    let resyncs = mouse::resyncs();
+    for byte in [0x05, 0x02, 0x09, 5, 3] {
+        mouse.add_byte(byte);
+    }
+    check(
+        mouse::resyncs() == resyncs + 2,
+        "misaligned bytes not counted as resyncs",
+    )?;
+    let event = mouse.next_event().ok_or("no packet after resync")?;
+    check(
+        event.dx == 5 && event.dy == 3 && event.left(),
+        "packet decoded out of alignment",
+    )?;
+    check(mouse.next_event().is_none(), "extra packet decoded")
+}
+
+struct MockPort<'a> {
+    responses: &'a [u8],
+    writes: usize,
+}
+
+impl CommandPort for MockPort<'_> {
+    fn write(&mut self, _byte: u8) -> bool {
+        self.writes += 1;
+        true
+    }
+
+    fn read_response(&mut self) -> Option<u8> {
+        self.responses.get(self.writes - 1).copied()
+    }
+}
+
+fn command_resend_once() -> TestResult {
+    let mut port = MockPort {
+        responses: &[keyboard::RESPONSE_RESEND, keyboard::RESPONSE_ACK],
+        writes: 0,
+    };
+    let result = keyboard::send_command_to(&mut port, 0xF4);
+
+    check(result == Ok(()), "command not acknowledged")?;
+    check(port.writes == 2, "expected exactly one retry")
+}
+
+fn command_resend_limit() -> TestResult {
+    let mut port = MockPort {
+        responses: &[keyboard::RESPONSE_RESEND; keyboard::COMMAND_ATTEMPTS + 1],
+        writes: 0,
+    };
+    let result = keyboard::send_command_to(&mut port, 0xF4);
+
+    check(
+        result == Err(CommandError::ResendLimit),
+        "resend limit not reported",
+    )?;
+    check(
+        port.writes == keyboard::COMMAND_ATTEMPTS,
+        "wrong number of attempts",
+    )
+}
+
+fn edit(text: &str, keys: &[KeyEvent]) -> (&'static str, usize) {
+    let editor = unsafe { &mut *addr_of_mut!(TEST_EDITOR) };
+    capture(|| {
+        editor.reset();
+        editor.begin();
+        for c in text.chars() {
+            editor.handle_key(KeyEvent::Char(c));
+        }
+        for &key in keys {
+            editor.handle_key(key);
+        }
+    });
+    (editor.as_str(), editor.cursor())
+}
+
+fn editor_kills() -> TestResult {
+    use KeyEvent::{Ctrl, Left, Right};
+
+    check(
+        edit("one two  ", &[Ctrl('w')]) == ("one ", 4),
+        "Ctrl+W did not skip trailing spaces",
+    )?;
+    check(
+        edit(
+            "one two",
+            &[Ctrl('a'), Right, Right, Right, Right, Ctrl('u')],
+        ) == ("two", 0),
+        "Ctrl+U did not kill to the start",
+    )?;
+    check(
+        edit("one two", &[Left, Left, Left, Ctrl('k')]) == ("one ", 4),
+        "Ctrl+K did not kill to the end",
+    )?;
+    check(
+        edit(
+            "one two",
+            &[Ctrl('a'), Ctrl('k'), Ctrl('y'), Ctrl('a'), Ctrl('y')],
+        ) == ("one twoone two", 7),
+        "Ctrl+Y did not insert the killed text at the cursor",
+    )?;
+    check(
+        edit("one", &[Ctrl('a'), Ctrl('e')]) == ("one", 3),
+        "Ctrl+A/Ctrl+E did not move the cursor",
+    )
+}
+
+fn editor_yank_full() -> TestResult {
+    let filler = [b'x'; line_editor::MAX_LINE - 2];
+    let filler = core::str::from_utf8(&filler).unwrap_or("");
+
+    edit("word", &[KeyEvent::Ctrl('w')]);
+    let (line, cursor) = edit(filler, &[KeyEvent::Ctrl('y')]);
+    check(
+        line.len() == line_editor::MAX_LINE && line.ends_with("xwo"),
+        "yank not truncated to the free space",
+    )?;
+    check(
+        cursor == line_editor::MAX_LINE,
+        "cursor not after the yanked text",
+    )
+}
+
+fn magic_actions() -> TestResult {
+    let mut keyboard = Keyboard::<16>::new();
+    keyboard.receive(scancodes::CTRL);
+    keyboard.receive(scancodes::ALT);
+    check(
+        keyboard.receive(scancodes::DELETE) == Some(MagicAction::ConfirmReboot),
+        "first Ctrl+Alt+Del did not ask for confirmation",
+    )?;
+    check(
+        keyboard.receive(scancodes::DELETE) == Some(MagicAction::Reboot),
+        "second Ctrl+Alt+Del did not reboot",
+    )?;
+    keyboard.receive(scancodes::CTRL | 0x80);
+
+    check(
+        keyboard.receive(scancodes::SYSRQ) == Some(MagicAction::ArmSysRq),
+        "Alt+SysRq not recognized",
+    )?;
+    check(
+        keyboard.receive(0x32) == Some(MagicAction::SysRq('m')),
+        "SysRq key not turned into an action",
+    )?;
+    check(keyboard.buffered() == 3, "magic key scancodes were queued")
+}
+
+fn magic_from_interrupt() -> TestResult {
+    let before = keyboard::buffered();
+    capture(|| {
+        keyboard::receive_scancode(scancodes::ALT);
+        keyboard::receive_scancode(scancodes::SYSRQ);
+        keyboard::receive_scancode(0x23);
+        keyboard::receive_scancode(scancodes::ALT | 0x80);
+    });
+
+    check(
+        captured_contains("b=reboot"),
+        "Alt+SysRq did not run until input was read",
+    )?;
+    check(
+        keyboard::buffered() == before + 2,
+        "SysRq scancodes reached the input buffer",
+    )
+}
+
+fn buffer_overflow() -> TestResult {
+    let mut keyboard = Keyboard::<4>::new();
+    for scancode in 0..Keyboard::<4>::capacity() as u8 {
+        check(keyboard.add_to_buffer(scancode), "buffer full too early")?;
+    }
+    check(!keyboard.add_to_buffer(0xFF), "overflow not rejected")?;
+    check(
+        keyboard.get_from_buffer() == Some(0),
+        "oldest scancode lost",
+    )?;
+    check(keyboard.add_to_buffer(0xFF), "freed slot not reused")
+}
+
+fn soft_repeat() -> TestResult {
+    let later = timer::ticks().wrapping_add(timer::ms_to_ticks(2000));
+    let mut keyboard = Keyboard::<4>::new();
+
+    keyboard.process_scancode(0x1E);
+    check(
+        keyboard.poll_repeat(timer::ticks()).is_none(),
+        "repeated before the delay",
+    )?;
+    check(
+        keyboard.poll_repeat(later) == Some(KeyEvent::Char('a')),
+        "held key not repeated",
+    )?;
+
+    keyboard.process_scancode(0x9E);
+    check(
+        keyboard.poll_repeat(later).is_none(),
+        "release did not cancel",
+    )?;
+
+    keyboard.process_scancode(scancodes::LEFT_SHIFT);
+    check(
+        keyboard.poll_repeat(later).is_none(),
+        "modifier key repeated",
+    )?;
+    keyboard.process_scancode(scancodes::LEFT_SHIFT | 0x80);
+
+    keyboard.process_scancode(0x1E);
+    keyboard.process_scancode(0x9F);
+    check(
+        keyboard.poll_repeat(later) == Some(KeyEvent::Char('a')),
+        "other key release cancelled the repeat",
+    )
+}
+
+fn typematic_encoding() -> TestResult {
+    check(keyboard::typematic_delay_for_ms(250) == 0, "250 ms delay")?;
+    check(keyboard::typematic_delay_for_ms(500) == 1, "500 ms delay")?;
+    check(keyboard::typematic_delay_for_ms(1000) == 3, "1000 ms delay")?;
+    check(keyboard::typematic_rate_for_cps(30) == 0, "30 cps rate")?;
+    check(keyboard::typematic_rate_for_cps(2) == 0x1F, "2 cps rate")?;
+    check(keyboard::typematic_rate_for_cps(10) == 0x0C, "10 cps rate")
+}
+
+fn entry_roundtrip() -> TestResult {
+    let entry = GdtEntry::new(
+        0x1234_5678,
+        0xABCDE,
+        access::PRESENT | access::RING_3 | access::DESCRIPTOR | access::EXECUTABLE,
+        granularity::PAGE_GRAN | granularity::SIZE_32,
+    );
+    let decoded = entry.decode();
+    check(decoded.base == 0x1234_5678, "base")?;
+    check(decoded.limit == 0xABCDE, "limit")?;
+    check(decoded.dpl == 3, "privilege level")?;
+    check(decoded.present, "present bit")?;
+    check(!decoded.system, "descriptor type")?;
+    check(decoded.executable, "executable bit")?;
+    check(decoded.granularity_4k, "granularity bit")?;
+
+    let data = GdtEntry::new(0x000B_8000, 0x0FFF, access::PRESENT | access::DESCRIPTOR, 0).decode();
+    check(data.base == 0x000B_8000, "data base")?;
+    check(data.limit == 0x0FFF, "data limit")?;
+    check(data.dpl == 0 && !data.executable, "data access")?;
+    check(!data.granularity_4k, "byte granularity")?;
+
+    check(
+        GdtEntry::null().decode().kind() == "-",
+        "null entry present",
+    )
+}
+
+fn limit_nibble() -> TestResult {
+    let entry = GdtEntry::new(0, 0xFFFFF, access::PRESENT, 0);
+    check(entry.limit() == 0xFFFFF, "high limit nibble lost")?;
+    check(entry.flags() == 0, "limit leaked into the flags nibble")?;
+
+    let entry = GdtEntry::new(0, 0x1_0000, access::PRESENT, 0xFF);
+    check(entry.limit() == 0x1_0000, "flags leaked into the limit")?;
+    check(entry.flags() == 0xF0, "flags nibble not kept")?;
+
+    let entry = GdtEntry::new(0, 0x12_3456, access::PRESENT, 0);
+    check(entry.limit() == 0x2_3456, "limit above 20 bits not masked")
+}
+
+fn loaded_table() -> TestResult {
+    check(gdt::is_loaded(), "GDTR does not point at the kernel GDT")?;
+
+    let code = gdt::get_gdt().entries[gdt::selectors::KERNEL_CODE as usize / 8].decode();
+    check(
+        code.base == 0 && code.limit == 0xFFFFF,
+        "kernel code is not flat",
+    )?;
+    check(code.executable && code.dpl == 0, "kernel code access")?;
+
+    let user = gdt::get_gdt().entries[gdt::selectors::USER_DATA as usize / 8].decode();
+    check(!user.executable && user.dpl == 3, "user data access")
+}
+
+fn tss_loaded() -> TestResult {
+    check(
+        gdt::task_register() == gdt::selectors::TSS,
+        "task register is not the TSS selector",
+    )?;
+
+    let tss = gdt::get_gdt().entries[gdt::selectors::TSS as usize / 8].decode();
+    check(tss.present && tss.system, "TSS descriptor type")?;
+    check(tss.dpl == 0, "TSS privilege level")
+}
+
+fn capture_sink(bytes: &[u8]) {
+    let captured = unsafe { &mut *addr_of_mut!(CAPTURED) };
+    let _ = captured.write_str(core::str::from_utf8(bytes).unwrap_or(""));
+}
+
+fn capture<R>(f: impl FnOnce() -> R) -> R {
+    borrow_spare(|spare| {
+        let active = printk::active_screen();
+        unsafe { (*addr_of_mut!(CAPTURED)).clear() };
+        let previous_tee = printk::set_tee(Some(capture_sink));
+        printk::switch_screen(spare);
+        let result = f();
+        printk::switch_screen(active);
+        printk::set_tee(previous_tee);
+        result
+    })
+}
+
+fn captured_contains(text: &str) -> bool {
+    unsafe { (*addr_of!(CAPTURED)).as_str().contains(text) }
+}
+
+fn command_success(shell: &mut Shell) -> TestResult {
+    let status = capture(|| shell.handle_command("echo ok"));
+    check(status == shell::STATUS_SUCCESS, "echo did not succeed")?;
+    check(shell.last_status() == status, "last_status not updated")?;
+    check(captured_contains("ok\n"), "echo output missing")
+}
+
+fn command_failure(shell: &mut Shell) -> TestResult {
+    let status = capture(|| shell.handle_command("false"));
+    check(status == shell::STATUS_FAILURE, "false did not fail")?;
+    check(shell.last_status() == status, "last_status not updated")?;
+
+    let status = capture(|| shell.handle_command("sleep soon"));
+    check(
+        status == shell::STATUS_USAGE,
+        "bad arguments not a usage error",
+    )?;
+    check(shell.last_status() == status, "last_status not updated")?;
+    check(
+        captured_contains("sleep: usage: sleep <ms>\n"),
+        "usage not reported",
+    )
+}
+
+fn command_unknown(shell: &mut Shell) -> TestResult {
+    let status = capture(|| shell.handle_command("nosuchcommand"));
+    check(
+        status == shell::STATUS_UNKNOWN_COMMAND,
+        "unknown command status",
+    )?;
+    check(shell.last_status() == status, "last_status not updated")?;
+    check(
+        captured_contains("nosuchcommand: unknown command\n"),
+        "error not reported",
+    )
+}
+
+fn status_variable(shell: &mut Shell) -> TestResult {
+    capture(|| {
+        shell.handle_command("true");
+        shell.handle_command("echo [$?]")
+    });
+    check(captured_contains("[0]\n"), "$? not 0 after success")?;
+
+    capture(|| {
+        shell.handle_command("nosuchcommand");
+        shell.handle_command("echo [$?]")
+    });
+    check(
+        captured_contains("[127]\n"),
+        "$? not 127 after unknown command",
+    )?;
+
+    capture(|| shell.handle_command("if false then echo [$?]"));
+    check(
+        captured_contains("[1]\n"),
+        "$? not expanded per sub-command",
+    )
+}
+
+fn screen_contains(writer: &Writer, text: &str) -> bool {
+    let mut found = false;
+    writer.export_history(&mut |line| found |= line.contains(text));
+    found
+}
+
+fn script_recording(shell: &mut Shell) -> TestResult {
+    const FILE: &str = "selftest.script";
+
+    let on_screen = capture(|| {
+        shell.handle_command("script start selftest.script");
+        shell.handle_command("echo recorded by script");
+        shell.handle_command("script stop");
+        printk::with_screen(printk::active_screen(), |writer| {
+            screen_contains(writer, "recorded by script")
+        })
+    });
+    let recorded = ramfs::find(FILE).map(ramfs::read);
+    ramfs::remove(FILE);
+
+    check(recorded.is_some(), "script file not created")?;
+    check(
+        recorded.is_some_and(|data| data.ends_with(b"recorded by script\n")),
+        "output not recorded to the file",
+    )?;
+    check(on_screen == Some(true), "output not shown on screen")
+}
+
+fn keyrate_validation(shell: &mut Shell) -> TestResult {
+    let before = keyboard::get_typematic();
+
+    let status = capture(|| shell.handle_command("keyrate 4"));
+    check(status == shell::STATUS_FAILURE, "delay 4 accepted")?;
+    check(
+        captured_contains("keyrate: delay must be between 0 and 3\n"),
+        "delay error not reported",
+    )?;
+
+    for args in ["keyrate 1 32", "keyrate 1 fast"] {
+        let status = capture(|| shell.handle_command(args));
+        check(status == shell::STATUS_FAILURE, "bad rate accepted")?;
+        check(
+            captured_contains("keyrate: rate must be between 0 and 31"),
+            "rate error not reported",
+        )?;
+    }
+
+    check(
+        keyboard::set_typematic(4, 0) == Err(CommandError::InvalidArgument),
+        "set_typematic accepted delay 4",
+    )?;
+    check(
+        keyboard::get_typematic() == before,
+        "rejected arguments changed the settings",
+    )
+}
+
+fn abbreviation_expand(shell: &mut Shell) -> TestResult {
+    let defined = capture(|| shell.handle_command("abbr stgreet echo expanded"));
+    check(defined == shell::STATUS_SUCCESS, "abbreviation not defined")?;
+
+    capture(|| shell.handle_command("stgreet world"));
+    let expanded = captured_contains("expanded world\n");
+    capture(|| shell.handle_command("echo stgreet"));
+    let argument_kept = captured_contains("stgreet\n") && !captured_contains("expanded");
+    let unknown = capture(|| shell.handle_command("stgreeting")) == shell::STATUS_UNKNOWN_COMMAND
+        && captured_contains("stgreeting: unknown command");
+    capture(|| shell.handle_command("abbr stgreet"));
+
+    check(expanded, "abbreviation not expanded with arguments")?;
+    check(
+        argument_kept,
+        "abbreviation expanded outside command position",
+    )?;
+    check(unknown, "undefined token did not pass through")
+}
+
+fn screen_switching(shell: &mut Shell) -> TestResult {
+    let active = printk::active_screen();
+    let other = (active + 1) % vga::MAX_SCREENS;
+
+    let mut keyboard = Keyboard::<4>::new();
+    keyboard.process_scancode(scancodes::ALT);
+    let event = keyboard.process_scancode(0x03);
+    check(
+        event == Some(KeyEvent::SwitchScreen(1)),
+        "Alt+2 did not request screen 2",
+    )?;
+
+    let mut command = FixedBuffer::<16>::new();
+    let _ = write!(command, "screen {}", other + 1);
+    let status = shell.handle_command(command.as_str());
+    let switched = printk::active_screen();
+    command.clear();
+    let _ = write!(command, "screen {}", active + 1);
+    shell.handle_command(command.as_str());
+
+    check(status == shell::STATUS_SUCCESS, "screen command failed")?;
+    check(switched == other, "active screen id not updated")?;
+    check(printk::active_screen() == active, "did not switch back")?;
+    check(
+        !printk::switch_screen(vga::MAX_SCREENS) && printk::active_screen() == active,
+        "out-of-range screen accepted",
+    )
+}
+
+fn greeting_boundaries() -> TestResult {
+    for (hour, expected) in [
+        (4, "Good evening"),
+        (5, "Good morning"),
+        (11, "Good morning"),
+        (12, "Good afternoon"),
+        (17, "Good afternoon"),
+        (18, "Good evening"),
+        (0, "Good evening"),
+    ] {
+        check(
+            shell::greeting(hour) == expected,
+            "wrong greeting at a boundary",
+        )?;
+    }
+    Ok(())
+}
+
+fn conditional_branches(shell: &mut Shell) -> TestResult {
+    let status =
+        capture(|| shell.handle_command("if true then echo took-then else echo took-else"));
+    check(
+        status == shell::STATUS_SUCCESS && captured_contains("took-then\n"),
+        "true did not take the then branch",
+    )?;
+    check(
+        !captured_contains("took-else"),
+        "true also ran the else branch",
+    )?;
+
+    capture(|| shell.handle_command("if false then echo took-then else echo took-else"));
+    check(
+        captured_contains("took-else\n") && !captured_contains("took-then"),
+        "false did not take the else branch",
+    )?;
+
+    let status = capture(|| shell.handle_command("if false then echo took-then"));
+    check(
+        status == shell::STATUS_SUCCESS && !captured_contains("took-then"),
+        "false without else ran the then branch",
+    )?;
+
+    capture(|| shell.handle_command("if  true\tthen echo spaced"));
+    check(
+        captured_contains("spaced\n"),
+        "keyword not found next to a tab or double space",
+    )?;
+
+    let status = capture(|| shell.handle_command("if true then false"));
+    check(
+        status == shell::STATUS_FAILURE,
+        "branch status not propagated",
+    )
+}
+
+fn with_fs(f: impl FnOnce(&mut Fs<MemoryDisk>) -> TestResult) -> TestResult {
+    let disk = MemoryDisk::new(FS_TEST_BLOCKS).ok_or("no memory for disk")?;
+    ops::format(&disk).map_err(|error| error.description())?;
+    let mut fs = Fs::mount(&disk).map_err(|error| error.description())?;
+    f(&mut fs)
+}
+
+fn run_script_order(shell: &mut Shell) -> TestResult {
+    const FILE: &str = "selftest.run";
+
+    let fd = ramfs::create(FILE).ok_or("could not create script file")?;
+    ramfs::append(fd, b"echo first line\necho second line\n");
+    let status = capture(|| shell.handle_command("run selftest.run"));
+    ramfs::remove(FILE);
+
+    check(status == shell::STATUS_SUCCESS, "script did not succeed")?;
+    check(
+        captured_contains("first line\nkfs> echo second line\nsecond line\n"),
+        "lines not run in order",
+    )
+}
+
+fn config_decode() -> TestResult {
+    use printk::LogLevel;
+
+    let defaults = config::Config {
+        log_level: LogLevel::Info,
+        foreground: Color::LightGray,
+        background: Color::Black,
+    };
+    let saved = config::Config {
+        log_level: LogLevel::Debug,
+        foreground: Color::Yellow,
+        background: Color::Blue,
+    };
+    let mut bytes = config::encode(&saved);
+    check(
+        config::decode(bytes, defaults) == saved,
+        "saved config not restored",
+    )?;
+
+    bytes[1] ^= LogLevel::Debug as u8 ^ 9;
+    bytes[3] ^= LogLevel::Debug as u8 ^ 9;
+    let decoded = config::decode(bytes, defaults);
+    check(
+        decoded.log_level == LogLevel::Info && decoded.foreground == Color::Yellow,
+        "unknown log level not replaced by the default",
+    )?;
+
+    bytes[3] ^= 1;
+    check(
+        config::decode(bytes, defaults) == defaults,
+        "bad checksum accepted",
+    )
+}
+
+fn fs_create_remove() -> TestResult {
+    with_fs(|fs| {
+        fs.create("note", b"hello")
+            .map_err(|error| error.description())?;
+        let created = fs.find("note").map_err(|error| error.description())?;
+        fs.remove("note").map_err(|error| error.description())?;
+
+        check(created.size == 5, "wrong size recorded")?;
+        check(
+            fs.find("note") == Err(FsError::NotFound),
+            "removed file found",
+        )?;
+        check(fs.remove("note") == Err(FsError::NotFound), "removed twice")?;
+
+        fs.create("again", b"hello")
+            .map_err(|error| error.description())?;
+        let again = fs.find("again").map_err(|error| error.description())?;
+        check(
+            again.first_block == created.first_block,
+            "blocks not freed on remove",
+        )
+    })
+}
+
+fn fs_name_collision() -> TestResult {
+    with_fs(|fs| {
+        fs.create("note", b"one")
+            .map_err(|error| error.description())?;
+        check(
+            fs.create("note", b"two") == Err(FsError::AlreadyExists),
+            "duplicate name accepted",
+        )?;
+
+        fs.write("note", b"three")
+            .map_err(|error| error.description())?;
+        let entry = fs.find("note").map_err(|error| error.description())?;
+        let mut entries = 0;
+        fs.for_each(|_| entries += 1)
+            .map_err(|error| error.description())?;
+
+        check(entry.size == 5, "write did not replace the file")?;
+        check(entries == 1, "write left a duplicate entry")
+    })
+}
+
+fn fs_partial_block() -> TestResult {
+    let mut data = [0u8; layout::BLOCK_SIZE + 100];
+    for (index, byte) in data.iter_mut().enumerate() {
+        *byte = index as u8;
+    }
+
+    with_fs(|fs| {
+        fs.create("data", &data)
+            .map_err(|error| error.description())?;
+        let entry = fs.find("data").map_err(|error| error.description())?;
+        let mut read = 0;
+        let mut same = true;
+        fs.read(&entry, |chunk| {
+            same &= data.get(read..read + chunk.len()) == Some(chunk);
+            read += chunk.len();
+        })
+        .map_err(|error| error.description())?;
+
+        check(entry.block_count == 2, "wrong block count")?;
+        check(read == data.len(), "read length differs from file size")?;
+        check(same, "read data differs")
+    })
+}
+
+fn tar_sizes() -> TestResult {
+    let tar = TarFs::new(TEST_TAR);
+    let block = tar.open("block.txt").ok_or("block.txt not found")?;
+
+    check(block.len() == 600, "octal size misread")?;
+    check(
+        block.ends_with(b"line 13 of a file that spans"),
+        "wrong data",
+    )?;
+    check(
+        tar.open("/short.txt") == Some(b"hello\n"),
+        "entry after a partial block not found",
+    )?;
+    check(tar.list().count() == 3, "directory entry not skipped")
+}
+
+fn tar_long_name() -> TestResult {
+    let tar = TarFs::new(TEST_TAR);
+    let entry = tar
+        .list()
+        .find(|entry| entry.name().ends_with("/deep.txt"))
+        .ok_or("prefixed entry not found")?;
+
+    check(entry.name().len() > 100, "name not joined with prefix")?;
+    check(
+        entry.name().starts_with("nested/directories/"),
+        "prefix not normalized",
+    )?;
+    check(
+        entry.data == b"found via the prefix field\n",
+        "wrong data for prefixed entry",
+    )
+}
+
+fn rsdp_blob(revision: u8) -> [u8; 36] {
+    let mut blob = [0u8; 36];
+    blob[..8].copy_from_slice(b"RSD PTR ");
+    blob[9..15].copy_from_slice(b"KFSOEM");
+    blob[15] = revision;
+    blob[16..20].copy_from_slice(&0x000E_1000u32.to_le_bytes());
+    blob[20..24].copy_from_slice(&36u32.to_le_bytes());
+    blob[24..32].copy_from_slice(&0x000E_2000u64.to_le_bytes());
+
+    let sum = |bytes: &[u8]| bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+    blob[8] = 0u8.wrapping_sub(sum(&blob[..20]));
+    blob[32] = 0u8.wrapping_sub(sum(&blob));
+    blob
+}
+
+fn acpi_checksum() -> TestResult {
+    let mut blob = rsdp_blob(2);
+    check(acpi::checksum_ok(&blob), "valid checksum rejected")?;
+    check(acpi::checksum_ok(&[]), "empty table rejected")?;
+
+    blob[10] ^= 0x01;
+    check(!acpi::checksum_ok(&blob), "corrupt table accepted")?;
+    check(acpi::parse_rsdp(&blob).is_none(), "corrupt RSDP parsed")
+}
+
+fn acpi_rsdp_v1() -> TestResult {
+    let blob = rsdp_blob(0);
+    let rsdp = acpi::parse_rsdp(&blob[..20]).ok_or("revision 0 RSDP rejected")?;
+
+    check(rsdp.revision == 0, "wrong revision")?;
+    check(rsdp.rsdt == 0x000E_1000, "wrong RSDT address")?;
+    check(rsdp.xsdt == 0, "XSDT read from a revision 0 RSDP")?;
+
+    let mut blob = blob;
+    blob[..8].copy_from_slice(b"RSD PTR?");
+    check(acpi::parse_rsdp(&blob).is_none(), "bad signature accepted")
+}
+
+fn acpi_rsdp_v2() -> TestResult {
+    let mut blob = rsdp_blob(2);
+    let rsdp = acpi::parse_rsdp(&blob).ok_or("revision 2 RSDP rejected")?;
+
+    check(rsdp.revision == 2, "wrong revision")?;
+    check(rsdp.rsdt == 0x000E_1000, "wrong RSDT address")?;
+    check(rsdp.xsdt == 0x000E_2000, "wrong XSDT address")?;
+    check(
+        acpi::parse_rsdp(&blob[..20]).is_none(),
+        "truncated revision 2 RSDP accepted",
+    )?;
+
+    blob[28] ^= 0x01;
+    check(
+        acpi::parse_rsdp(&blob).is_none(),
+        "bad extended checksum accepted",
+    )
+}
+
+fn matches(test: &SelfTest, filter: &str) -> bool {
+    filter.is_empty() || test.name == filter || test.category.name() == filter
+}
+
+pub fn run(filter: &str, shell: &mut Shell) -> Option<Summary> {
+    let mut summary = Summary::default();
+
+    for test in TESTS.iter().filter(|test| matches(test, filter)) {
+        let result = match test.run {
+            TestFn::Plain(run) => run(),
+            TestFn::Shell(run) => run(shell),
+        };
+
+        match result {
+            Ok(()) => {
+                theme::apply(Role::Success);
+                print("  [PASS] ");
+                summary.passed += 1;
+            }
+            Err(_) => {
+                theme::apply(Role::Error);
+                print("  [FAIL] ");
+                summary.failed += 1;
+            }
+        }
+        reset_color();
+        crate::printk!("{}/{}", test.category.name(), test.name);
+        if let Err(message) = result {
+            theme::apply(Role::Muted);
+            crate::printk!(": {}", message);
+            reset_color();
+        }
+        printk::println("");
+    }
+
+    (summary.passed + summary.failed > 0).then_some(summary)
+}
@@ -0,0 +1,77 @@
+use crate::io::{inb, outb};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub const COM1: u16 = 0x3F8;
+
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+const LINE_CONTROL_DLAB: u8 = 0x80;
+const LINE_CONTROL_8N1: u8 = 0x03;
+const FIFO_ENABLE_CLEAR_14: u8 = 0xC7;
+const MODEM_LOOPBACK: u8 = 0x1E;
+const MODEM_NORMAL: u8 = 0x0F;
+const LINE_STATUS_TX_EMPTY: u8 = 1 << 5;
+
+const BAUD_DIVISOR: u16 = 3; // 38400 baud
+const LOOPBACK_TEST_BYTE: u8 = 0xAE;
+const TX_WAIT_ATTEMPTS: u32 = 100_000;
+
+static PRESENT: AtomicBool = AtomicBool::new(false);
+
+pub fn init() -> bool {
+    let present = unsafe {
+        outb(COM1 + INTERRUPT_ENABLE, 0x00);
+        outb(COM1 + LINE_CONTROL, LINE_CONTROL_DLAB);
+        outb(COM1 + DATA, (BAUD_DIVISOR & 0xFF) as u8);
+        outb(COM1 + INTERRUPT_ENABLE, (BAUD_DIVISOR >> 8) as u8);
+        outb(COM1 + LINE_CONTROL, LINE_CONTROL_8N1);
+        outb(COM1 + FIFO_CONTROL, FIFO_ENABLE_CLEAR_14);
+
+        outb(COM1 + MODEM_CONTROL, MODEM_LOOPBACK);
+        outb(COM1 + DATA, LOOPBACK_TEST_BYTE);
+        let present = inb(COM1 + DATA) == LOOPBACK_TEST_BYTE;
+
+        outb(COM1 + MODEM_CONTROL, MODEM_NORMAL);
+        present
+    };
+
+    PRESENT.store(present, Ordering::SeqCst);
+    present
+}
+
+pub fn is_present() -> bool {
+    PRESENT.load(Ordering::SeqCst)
+}
+
+fn transmit(byte: u8) {
+    unsafe {
+        for _ in 0..TX_WAIT_ATTEMPTS {
+            if inb(COM1 + LINE_STATUS) & LINE_STATUS_TX_EMPTY != 0 {
+                break;
+            }
+        }
+        outb(COM1 + DATA, byte);
+    }
+}
+
+pub fn write_byte(byte: u8) {
+    if !is_present() {
+        return;
+    }
+
+    if byte == b'\n' {
+        transmit(b'\r');
+    }
+    transmit(byte);
+}
+
+pub fn write_bytes(bytes: &[u8]) {
+    for &byte in bytes {
+        write_byte(byte);
+    }
+}
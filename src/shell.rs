@@ -0,0 +1,2800 @@
+use crate::ata;
+use crate::block::BlockDevice;
+use crate::fs;
+use crate::io::{inb, outb};
+use crate::keyboard::{self, KeyEvent};
+use crate::kfs_lib::{self, strtol, strtoul, FixedBuffer, HumanSize};
+use crate::line_editor::{self, LineEditor};
+use crate::memory::{self, paging, pmm, PAGE_SIZE};
+use crate::printk::{self, print, print_dec, println, reset_color, LogLevel};
+use crate::theme::{self, Role};
+use crate::vga::{self, Color};
+use crate::{
+    acpi, apic, banner, config, cpuid, early_console, elf, idle, idt, initrd, klog, mouse, panic,
+    pic, power, ramfs, random, rtc, selection, selftest, serial, speaker, stack, stack_guard,
+    statusbar, sync, syscall, task, timer, usermode, watchdog,
+};
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const MAX_INPUT: usize = line_editor::MAX_LINE;
+const PROMPT: &str = "kfs> ";
+
+const HEXDUMP_DEFAULT_LEN: usize = 64;
+const HEXDUMP_MAX_LEN: usize = 4096;
+
+const SUSPICIOUS_WRITE_LIMIT: usize = 0x1000;
+
+const MAX_ABBREVIATIONS: usize = 16;
+const ABBR_NAME_LEN: usize = 16;
+const ABBR_EXPANSION_LEN: usize = 64;
+
+const COUNTER_DEFAULT_TICKS: u32 = 100;
+const COUNTER_STEPS: u32 = 10;
+
+const BEEP_DEFAULT_FREQUENCY: u32 = 880;
+const BEEP_DEFAULT_MS: u32 = 200;
+const BEEP_MAX_MS: u32 = 10_000;
+
+const MEMBENCH_DEFAULT_KIB: u64 = 1024;
+const MEMBENCH_MAX_KIB: u64 = 4096;
+
+const MEMTEST_CONFIRM_BYTES: usize = 1024 * 1024;
+const MEMTEST_BLOCK_WORDS: usize = 64;
+
+pub const STATUS_SUCCESS: i32 = 0;
+pub const STATUS_FAILURE: i32 = 1;
+pub const STATUS_USAGE: i32 = 2;
+pub const STATUS_UNKNOWN_COMMAND: i32 = 127;
+pub const STATUS_INTERRUPTED: i32 = 130;
+
+const MAX_RUN_DEPTH: usize = 8;
+
+const ERROR_MESSAGE_LEN: usize = 96;
+
+const NESTING_COMMANDS: &[&str] = &["if", "repeat", "watch", "log"];
+
+static mut SCRIPT_FILE: Option<usize> = None;
+
+static mut SHELL: Shell = Shell::new();
+
+static mut HISTORY: FixedBuffer<{ vga::HISTORY_LINES * (vga::VGA_BUFFER_WIDTH + 1) }> =
+    FixedBuffer::new();
+
+static COUNTER_INTERVAL: AtomicU32 = AtomicU32::new(COUNTER_DEFAULT_TICKS);
+
+pub struct ShellError {
+    code: i32,
+    message: FixedBuffer<ERROR_MESSAGE_LEN>,
+}
+
+impl ShellError {
+    pub fn new(code: i32, message: impl fmt::Display) -> ShellError {
+        let mut buffer = FixedBuffer::new();
+        let _ = write!(buffer, "{}", message);
+        ShellError {
+            code,
+            message: buffer,
+        }
+    }
+
+    pub fn failure(message: impl fmt::Display) -> ShellError {
+        ShellError::new(STATUS_FAILURE, message)
+    }
+
+    pub fn usage() -> ShellError {
+        ShellError::new(STATUS_USAGE, "")
+    }
+
+    pub fn status(code: i32) -> ShellError {
+        ShellError::new(code, "")
+    }
+
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+type CommandResult = Result<(), ShellError>;
+
+struct Command {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    usage: &'static str,
+    help: &'static str,
+    handler: fn(&mut Shell, &str) -> CommandResult,
+}
+
+static COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        aliases: &[],
+        usage: "help [command]",
+        help: "Show this help, or the usage of one command",
+        handler: |shell, args| shell.help(args),
+    },
+    Command {
+        name: "echo",
+        aliases: &[],
+        usage: "echo [-e] [text]",
+        help: "Print text (-e: \\n \\t \\\\ escapes), $? is the last status",
+        handler: |shell, args| shell.echo(args),
+    },
+    Command {
+        name: "true",
+        aliases: &[],
+        usage: "true",
+        help: "Exit with status 0",
+        handler: |_, _| Ok(()),
+    },
+    Command {
+        name: "false",
+        aliases: &[],
+        usage: "false",
+        help: "Exit with status 1",
+        handler: |_, _| Err(ShellError::status(STATUS_FAILURE)),
+    },
+    Command {
+        name: "status",
+        aliases: &[],
+        usage: "status",
+        help: "Show the status of the last command",
+        handler: |shell, _| shell.status(),
+    },
+    Command {
+        name: "if",
+        aliases: &[],
+        usage: "if C then A [else B]",
+        help: "Run A if C succeeds, otherwise B",
+        handler: |shell, args| shell.conditional(args),
+    },
+    Command {
+        name: "date",
+        aliases: &[],
+        usage: "date",
+        help: "Show the date and time from the RTC",
+        handler: |shell, _| shell.date(),
+    },
+    Command {
+        name: "uptime",
+        aliases: &[],
+        usage: "uptime",
+        help: "Show the time since boot and the PIT tick count",
+        handler: |shell, _| shell.uptime(),
+    },
+    Command {
+        name: "sleep",
+        aliases: &[],
+        usage: "sleep <ms>",
+        help: "Wait for the given number of milliseconds",
+        handler: |shell, args| shell.sleep(args),
+    },
+    Command {
+        name: "repeat",
+        aliases: &[],
+        usage: "repeat <n> <command>",
+        help: "Run a command n times",
+        handler: |shell, args| shell.repeat(args),
+    },
+    Command {
+        name: "watch",
+        aliases: &[],
+        usage: "watch <ms> <command>",
+        help: "Re-run a command every ms until a key is pressed",
+        handler: |shell, args| shell.watch(args),
+    },
+    Command {
+        name: "clear",
+        aliases: &["cls"],
+        usage: "clear [--all]",
+        help: "Clear the screen, --all includes fixed rows",
+        handler: |shell, args| shell.clear(args),
+    },
+    Command {
+        name: "mem",
+        aliases: &[],
+        usage: "mem",
+        help: "Show memory information",
+        handler: |_, _| {
+            crate::print_memory_info();
+            Ok(())
+        },
+    },
+    Command {
+        name: "free",
+        aliases: &[],
+        usage: "free [-b]",
+        help: "Show memory usage, -b for raw bytes",
+        handler: |shell, args| shell.free(args),
+    },
+    Command {
+        name: "lsmem",
+        aliases: &[],
+        usage: "lsmem",
+        help: "List usable physical memory regions",
+        handler: |shell, _| shell.lsmem(),
+    },
+    Command {
+        name: "cpuinfo",
+        aliases: &[],
+        usage: "cpuinfo",
+        help: "Show the processor vendor, model and features",
+        handler: |shell, _| shell.cpuinfo(),
+    },
+    Command {
+        name: "paging",
+        aliases: &[],
+        usage: "paging",
+        help: "Show paging information",
+        handler: |_, _| {
+            crate::print_paging_info();
+            Ok(())
+        },
+    },
+    Command {
+        name: "gdt",
+        aliases: &[],
+        usage: "gdt",
+        help: "Show the Global Descriptor Table",
+        handler: |_, _| {
+            crate::print_gdt_info();
+            Ok(())
+        },
+    },
+    Command {
+        name: "stack",
+        aliases: &[],
+        usage: "stack",
+        help: "Dump the kernel stack",
+        handler: |_, _| {
+            stack::print_stack();
+            Ok(())
+        },
+    },
+    Command {
+        name: "stacks",
+        aliases: &[],
+        usage: "stacks",
+        help: "List known stacks with peak usage and canary status",
+        handler: |shell, _| shell.stacks(),
+    },
+    Command {
+        name: "trace",
+        aliases: &[],
+        usage: "trace",
+        help: "Print the EBP stack trace",
+        handler: |_, _| {
+            stack::print_stack_trace();
+            Ok(())
+        },
+    },
+    Command {
+        name: "hexdump",
+        aliases: &[],
+        usage: "hexdump <addr> [len]",
+        help: "Dump memory as hex and ASCII",
+        handler: |shell, args| shell.hexdump(args),
+    },
+    Command {
+        name: "peek",
+        aliases: &[],
+        usage: "peek <addr> [size]",
+        help: "Read a 1/2/4/8-byte value",
+        handler: |shell, args| shell.peek(args),
+    },
+    Command {
+        name: "poke",
+        aliases: &[],
+        usage: "poke <addr> <v> [sz]",
+        help: "Write a 1/2/4/8-byte value",
+        handler: |shell, args| shell.poke(args),
+    },
+    Command {
+        name: "inb",
+        aliases: &[],
+        usage: "inb <port>",
+        help: "Read a byte from an I/O port",
+        handler: |shell, args| shell.read_port(args),
+    },
+    Command {
+        name: "outb",
+        aliases: &[],
+        usage: "outb <port> <value>",
+        help: "Write a byte to an I/O port",
+        handler: |shell, args| shell.write_port(args),
+    },
+    Command {
+        name: "keyrate",
+        aliases: &[],
+        usage: "keyrate [dly] [rate]",
+        help: "Show or set the keyboard typematic rate",
+        handler: |shell, args| shell.keyrate(args),
+    },
+    Command {
+        name: "kbrate",
+        aliases: &[],
+        usage: "kbrate [cps] [ms]",
+        help: "Set repeat in chars/sec and ms, or 'soft on|off'",
+        handler: |shell, args| shell.kbrate(args),
+    },
+    Command {
+        name: "kbdstat",
+        aliases: &[],
+        usage: "kbdstat",
+        help: "Show keyboard buffer usage and dropped scancodes",
+        handler: |shell, _| shell.kbdstat(),
+    },
+    Command {
+        name: "mouse",
+        aliases: &[],
+        usage: "mouse",
+        help: "Print PS/2 mouse events until a key is pressed",
+        handler: |shell, _| shell.mouse(),
+    },
+    Command {
+        name: "showkeys",
+        aliases: &[],
+        usage: "showkeys",
+        help: "Print raw keyboard scancodes",
+        handler: |shell, _| shell.showkeys(),
+    },
+    Command {
+        name: "abbr",
+        aliases: &[],
+        usage: "abbr [name [cmd]]",
+        help: "List, define or remove abbreviations",
+        handler: |shell, args| shell.abbr(args),
+    },
+    Command {
+        name: "ls",
+        aliases: &[],
+        usage: "ls",
+        help: "List RAM files",
+        handler: |shell, _| shell.list_files(),
+    },
+    Command {
+        name: "cat",
+        aliases: &[],
+        usage: "cat <file>",
+        help: "Print a RAM file",
+        handler: |shell, args| shell.cat(args),
+    },
+    Command {
+        name: "rm",
+        aliases: &[],
+        usage: "rm <file>",
+        help: "Remove a RAM file",
+        handler: |shell, args| shell.remove(args),
+    },
+    Command {
+        name: "script",
+        aliases: &[],
+        usage: "script start|stop",
+        help: "Record shell output to a RAM file (start <file>), or stop",
+        handler: |shell, args| shell.script(args),
+    },
+    Command {
+        name: "run",
+        aliases: &["source"],
+        usage: "run [-e] <file>",
+        help: "Run each line of a file, -e stops on failure",
+        handler: |shell, args| shell.run_script(args),
+    },
+    Command {
+        name: "serial",
+        aliases: &[],
+        usage: "serial [on|off]",
+        help: "Show or set output mirroring to COM1",
+        handler: |shell, args| shell.serial(args),
+    },
+    Command {
+        name: "log",
+        aliases: &[],
+        usage: "log <command>",
+        help: "Run a command with output mirrored to COM1",
+        handler: |shell, args| shell.log(args),
+    },
+    Command {
+        name: "dmesg",
+        aliases: &[],
+        usage: "dmesg [n] [-l|-n lv]",
+        help: "Replay the last n log lines, -l filter, -n set console",
+        handler: |shell, args| shell.dmesg(args),
+    },
+    Command {
+        name: "spawn",
+        aliases: &[],
+        usage: "spawn [ticks]",
+        help: "Start a background counter task",
+        handler: |shell, args| shell.spawn(args),
+    },
+    Command {
+        name: "idle",
+        aliases: &[],
+        usage: "idle [mode]",
+        help: "Show or set the idle policy (hlt, spin, mwait)",
+        handler: |shell, args| shell.idle(args),
+    },
+    Command {
+        name: "irq",
+        aliases: &[],
+        usage: "irq",
+        help: "Show timer, spurious and unhandled interrupt counts",
+        handler: |shell, _| shell.irq(),
+    },
+    Command {
+        name: "screen",
+        aliases: &[],
+        usage: "screen [n]",
+        help: "Show or switch the virtual screen (also Alt+1-4)",
+        handler: |shell, args| shell.screen(args),
+    },
+    Command {
+        name: "scrollback",
+        aliases: &[],
+        usage: "scrollback",
+        help: "Reprint the screen and its scrollback history",
+        handler: |shell, _| shell.scrollback(),
+    },
+    Command {
+        name: "theme",
+        aliases: &[],
+        usage: "theme [set|load]",
+        help: "Show or change the console colors",
+        handler: |shell, args| shell.theme(args),
+    },
+    Command {
+        name: "color",
+        aliases: &[],
+        usage: "color <fg> <bg>",
+        help: "Set the shell text colors",
+        handler: |shell, args| shell.color(args),
+    },
+    Command {
+        name: "blink",
+        aliases: &[],
+        usage: "blink [on|off]",
+        help: "Show or set blinking text (off: bright backgrounds)",
+        handler: |shell, args| shell.blink(args),
+    },
+    Command {
+        name: "saveconfig",
+        aliases: &[],
+        usage: "saveconfig",
+        help: "Save the log level and default colors to CMOS",
+        handler: |shell, _| shell.save_config(),
+    },
+    Command {
+        name: "watchdog",
+        aliases: &[],
+        usage: "watchdog [opt] [val]",
+        help: "Show or set the lockup watchdog (timeout, mode)",
+        handler: |shell, args| shell.watchdog(args),
+    },
+    Command {
+        name: "banner",
+        aliases: &["motd"],
+        usage: "banner",
+        help: "Show the logo, message of the day and system summary",
+        handler: |_, _| {
+            banner::show();
+            Ok(())
+        },
+    },
+    Command {
+        name: "disks",
+        aliases: &[],
+        usage: "disks",
+        help: "List detected ATA drives",
+        handler: |shell, _| shell.disks(),
+    },
+    Command {
+        name: "acpi",
+        aliases: &[],
+        usage: "acpi",
+        help: "List ACPI tables and poweroff registers",
+        handler: |shell, _| shell.acpi(),
+    },
+    Command {
+        name: "readsector",
+        aliases: &[],
+        usage: "readsector <lba> [d]",
+        help: "Dump a sector from drive d (default 0)",
+        handler: |shell, args| shell.read_sector(args),
+    },
+    Command {
+        name: "fs",
+        aliases: &[],
+        usage: "fs <cmd> [args]",
+        help: "Disk files: format, ls, cat, write, rm",
+        handler: |shell, args| shell.fs(args),
+    },
+    Command {
+        name: "hang",
+        aliases: &[],
+        usage: "hang [seconds]",
+        help: "Busy-loop to test the watchdog",
+        handler: |shell, args| shell.hang(args),
+    },
+    Command {
+        name: "overflow",
+        aliases: &[],
+        usage: "overflow",
+        help: "Recurse until the stack guard page is hit",
+        handler: |shell, _| shell.overflow(),
+    },
+    Command {
+        name: "earlytest",
+        aliases: &[],
+        usage: "earlytest",
+        help: "Write a line through the early boot console",
+        handler: |shell, _| shell.earlytest(),
+    },
+    Command {
+        name: "membench",
+        aliases: &[],
+        usage: "membench [KiB]",
+        help: "Time byte loops against rep stos/movs (default 1 MiB)",
+        handler: |shell, args| shell.membench(args),
+    },
+    Command {
+        name: "memtest",
+        aliases: &[],
+        usage: "memtest <addr> <len>",
+        help: "Walking-ones and address test over a RAM region",
+        handler: |shell, args| shell.memtest(args),
+    },
+    Command {
+        name: "beep",
+        aliases: &[],
+        usage: "beep [freq] [ms]",
+        help: "Sound the PC speaker",
+        handler: |shell, args| shell.beep(args),
+    },
+    Command {
+        name: "usertest",
+        aliases: &[],
+        usage: "usertest",
+        help: "Run a ring 3 program that makes int 0x80 syscalls",
+        handler: |shell, _| shell.usertest(),
+    },
+    Command {
+        name: "test",
+        aliases: &[],
+        usage: "test [category|name]",
+        help: "Run the in-kernel self-tests",
+        handler: |shell, args| shell.self_test(args),
+    },
+    Command {
+        name: "exec",
+        aliases: &[],
+        usage: "exec <file>",
+        help: "Run an i386 ELF executable in ring 3",
+        handler: |shell, args| shell.exec(args),
+    },
+    Command {
+        name: "strace",
+        aliases: &[],
+        usage: "strace [on|off]",
+        help: "Log system calls to the kernel log",
+        handler: |shell, args| shell.strace(args),
+    },
+    Command {
+        name: "reboot",
+        aliases: &[],
+        usage: "reboot",
+        help: "Reboot the machine",
+        handler: |shell, _| shell.reboot(),
+    },
+    Command {
+        name: "halt",
+        aliases: &[],
+        usage: "halt",
+        help: "Halt the CPU",
+        handler: |shell, _| shell.halt(),
+    },
+    Command {
+        name: "shutdown",
+        aliases: &[],
+        usage: "shutdown",
+        help: "Power off the machine (ACPI, then emulator ports)",
+        handler: |shell, _| shell.shutdown(),
+    },
+];
+
+fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name || command.aliases.contains(&name))
+}
+
+#[derive(Clone, Copy)]
+struct Abbreviation {
+    name: [u8; ABBR_NAME_LEN],
+    name_len: usize,
+    expansion: [u8; ABBR_EXPANSION_LEN],
+    expansion_len: usize,
+}
+
+impl Abbreviation {
+    const fn empty() -> Abbreviation {
+        Abbreviation {
+            name: [0; ABBR_NAME_LEN],
+            name_len: 0,
+            expansion: [0; ABBR_EXPANSION_LEN],
+            expansion_len: 0,
+        }
+    }
+
+    fn is_used(&self) -> bool {
+        self.name_len > 0
+    }
+
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    fn expansion(&self) -> &str {
+        core::str::from_utf8(&self.expansion[..self.expansion_len]).unwrap_or("")
+    }
+}
+
+pub struct Shell {
+    editor: LineEditor,
+    pending: FixedBuffer<{ selection::CLIPBOARD_SIZE }>,
+    pending_offset: usize,
+    drawn_output: u32,
+    abbreviations: [Abbreviation; MAX_ABBREVIATIONS],
+    last_status: i32,
+    run_depth: usize,
+}
+
+impl Shell {
+    pub const fn new() -> Shell {
+        Shell {
+            editor: LineEditor::new(),
+            pending: FixedBuffer::new(),
+            pending_offset: 0,
+            drawn_output: 0,
+            abbreviations: [Abbreviation::empty(); MAX_ABBREVIATIONS],
+            last_status: STATUS_SUCCESS,
+            run_depth: 0,
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        self.show_banner();
+        self.show_prompt();
+
+        loop {
+            self.drawn_output = printk::output_count();
+            match self.next_event() {
+                KeyEvent::Enter => self.enter(),
+                KeyEvent::Ctrl('c') => {
+                    self.discard_pending();
+                    self.editor.move_end();
+                    println("^C");
+                    self.editor.reset();
+                    self.show_prompt();
+                }
+                KeyEvent::CtrlShift('m') => {
+                    selection::run();
+                }
+                KeyEvent::CtrlShift('v') => self.paste(),
+                KeyEvent::PageUp => printk::scroll_up(printk::page_height() / 2),
+                KeyEvent::PageDown => printk::scroll_down(printk::page_height() / 2),
+                KeyEvent::Ctrl('l') => {
+                    printk::clear();
+                    self.redraw_input_line();
+                }
+                KeyEvent::SwitchScreen(id) => {
+                    if switch_screen(id) {
+                        self.redraw_input_line();
+                    } else {
+                        speaker::play(speaker::ERROR_BUZZ, speaker::PlayMode::Replace);
+                    }
+                }
+                event => {
+                    self.editor.handle_key(event);
+                }
+            }
+        }
+    }
+
+    fn show_banner(&self) {
+        theme::apply(Role::Info);
+        if let Some(now) = rtc::now() {
+            print(greeting(now.hour));
+            print("! ");
+        }
+        println("KFS shell - type 'help' for a list of commands");
+        reset_color();
+    }
+
+    fn show_prompt(&mut self) {
+        if !printk::at_line_start() {
+            println("");
+        }
+        theme::apply(Role::Prompt);
+        print(PROMPT);
+        reset_color();
+        self.editor.begin();
+    }
+
+    fn next_event(&mut self) -> KeyEvent {
+        loop {
+            if let Some(event) = keyboard::read_event() {
+                return event;
+            }
+            if printk::output_count() != self.drawn_output {
+                self.redraw_input_line();
+            }
+            task::yield_now();
+            idle::idle();
+        }
+    }
+
+    pub fn redraw_input_line(&mut self) {
+        self.show_prompt();
+        self.editor.redisplay();
+        self.drawn_output = printk::output_count();
+    }
+
+    fn enter(&mut self) {
+        self.editor.move_end();
+        println("");
+        self.submit_line();
+        self.show_prompt();
+        self.type_pending();
+    }
+
+    fn paste(&mut self) {
+        self.pending.clear();
+        selection::with_clipboard(|text| {
+            let _ = self.pending.write_str(text);
+        });
+        self.pending_offset = 0;
+        if self.type_pending() {
+            self.enter();
+        }
+    }
+
+    fn type_pending(&mut self) -> bool {
+        while let Some(&byte) = self.pending.as_str().as_bytes().get(self.pending_offset) {
+            self.pending_offset += 1;
+            match byte {
+                b'\n' => return true,
+                b' '..=b'~' => {
+                    self.editor.insert(byte);
+                }
+                _ => {}
+            }
+        }
+        self.discard_pending();
+        false
+    }
+
+    fn discard_pending(&mut self) {
+        self.pending.clear();
+        self.pending_offset = 0;
+    }
+
+    fn submit_line(&mut self) {
+        let mut line = [0u8; MAX_INPUT];
+        let len = self.editor.len();
+        line[..len].copy_from_slice(self.editor.as_str().as_bytes());
+        self.editor.add_history();
+        self.editor.reset();
+
+        if let Ok(line) = core::str::from_utf8(&line[..len]) {
+            self.handle_command(line);
+        }
+    }
+
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    pub fn handle_command(&mut self, line: &str) -> i32 {
+        let line = line.trim();
+        if line.is_empty() {
+            return self.last_status;
+        }
+
+        let (command, args) = split_command(line);
+
+        let mut expanded = [0u8; MAX_INPUT];
+        let expanded_len = match self.find_abbreviation(command) {
+            Some(abbr) => {
+                let mut len = copy_truncated(&mut expanded, 0, abbr.expansion());
+                if !args.is_empty() {
+                    len = copy_truncated(&mut expanded, len, " ");
+                    len = copy_truncated(&mut expanded, len, args);
+                }
+                len
+            }
+            None => 0,
+        };
+
+        let status = if expanded_len > 0 {
+            match core::str::from_utf8(&expanded[..expanded_len]) {
+                Ok(line) => {
+                    let (command, args) = split_command(line);
+                    self.dispatch(command, args)
+                }
+                Err(_) => STATUS_FAILURE,
+            }
+        } else {
+            self.dispatch(command, args)
+        };
+
+        self.last_status = status;
+        status
+    }
+
+    fn dispatch(&mut self, command: &str, args: &str) -> i32 {
+        let Some(entry) = find_command(command) else {
+            print_error(command, "unknown command");
+            return STATUS_UNKNOWN_COMMAND;
+        };
+
+        let mut substituted = [0u8; MAX_INPUT];
+        let args = if NESTING_COMMANDS.contains(&entry.name) {
+            args
+        } else {
+            let len = expand_status(&mut substituted, args, self.last_status);
+            core::str::from_utf8(&substituted[..len]).unwrap_or("")
+        };
+
+        let Err(error) = (entry.handler)(self, args) else {
+            return STATUS_SUCCESS;
+        };
+        if error.code() == STATUS_USAGE && error.message().is_empty() {
+            print_error(entry.name, format_args!("usage: {}", entry.usage));
+        } else if !error.message().is_empty() {
+            print_error(entry.name, error.message());
+        }
+        error.code()
+    }
+
+    fn echo(&self, args: &str) -> CommandResult {
+        let text = match args.strip_prefix("-e") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+                rest.trim_start()
+            }
+            _ => {
+                println(args);
+                return Ok(());
+            }
+        };
+
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                crate::printk!("{}", c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => println(""),
+                Some('t') => print("\t"),
+                Some('\\') | None => print("\\"),
+                Some(other) => crate::printk!("\\{}", other),
+            }
+        }
+        println("");
+        Ok(())
+    }
+
+    fn status(&self) -> CommandResult {
+        let meaning = match self.last_status {
+            STATUS_SUCCESS => "success",
+            STATUS_UNKNOWN_COMMAND => "unknown command",
+            STATUS_INTERRUPTED => "interrupted",
+            _ => "failure",
+        };
+        crate::printkln!("{} ({})", self.last_status, meaning);
+        status_result(self.last_status)
+    }
+
+    fn help(&self, args: &str) -> CommandResult {
+        if args.is_empty() {
+            theme::apply(Role::Info);
+            println("Available commands:");
+            reset_color();
+            for command in COMMANDS.iter() {
+                crate::printkln!("  {:<20} {}", command.usage, command.help);
+            }
+            return Ok(());
+        }
+
+        let Some(command) = find_command(args) else {
+            return Err(ShellError::failure(format_args!(
+                "no such command: {}",
+                args
+            )));
+        };
+
+        theme::apply(Role::Label);
+        print("Usage:   ");
+        reset_color();
+        println(command.usage);
+        theme::apply(Role::Label);
+        print("         ");
+        reset_color();
+        println(command.help);
+        if !command.aliases.is_empty() {
+            theme::apply(Role::Label);
+            print("Aliases: ");
+            reset_color();
+            for (index, alias) in command.aliases.iter().enumerate() {
+                if index > 0 {
+                    print(", ");
+                }
+                print(alias);
+            }
+            println("");
+        }
+        Ok(())
+    }
+
+    fn conditional(&mut self, args: &str) -> CommandResult {
+        let (condition, branches) = match split_keyword(args, "then") {
+            Some((condition, branches)) if !condition.is_empty() => (condition, branches),
+            _ => return Err(ShellError::usage()),
+        };
+        let (then_branch, else_branch) = split_keyword(branches, "else").unwrap_or((branches, ""));
+        if then_branch.is_empty() {
+            return Err(ShellError::usage());
+        }
+
+        if self.handle_command(condition) == STATUS_SUCCESS {
+            status_result(self.handle_command(then_branch))
+        } else if !else_branch.is_empty() {
+            status_result(self.handle_command(else_branch))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn cpuinfo(&self) -> CommandResult {
+        if !cpuid::is_supported() {
+            return Err(ShellError::failure("CPUID instruction not supported"));
+        }
+
+        let info = cpuid::info();
+        let brand = if info.brand().is_empty() {
+            "unknown"
+        } else {
+            info.brand()
+        };
+
+        theme::apply(Role::Label);
+        print("Vendor:   ");
+        reset_color();
+        println(info.vendor());
+
+        theme::apply(Role::Label);
+        print("Brand:    ");
+        reset_color();
+        println(brand);
+
+        theme::apply(Role::Label);
+        print("Model:    ");
+        reset_color();
+        crate::printkln!(
+            "family {} model {} stepping {}",
+            info.family,
+            info.model,
+            info.stepping
+        );
+
+        theme::apply(Role::Label);
+        print("Features:");
+        reset_color();
+        let indent = "Features:".len();
+        let mut column = indent;
+        for feature in info.features() {
+            if column + 1 + feature.len() >= vga::VGA_BUFFER_WIDTH {
+                println("");
+                for _ in 0..indent {
+                    print(" ");
+                }
+                column = indent;
+            }
+            print(" ");
+            print(feature);
+            column += 1 + feature.len();
+        }
+        println("");
+        Ok(())
+    }
+
+    fn date(&self) -> CommandResult {
+        let now = rtc::now().ok_or_else(|| ShellError::failure("real-time clock not available"))?;
+        crate::printkln!("{}", now);
+        Ok(())
+    }
+
+    fn uptime(&self) -> CommandResult {
+        if !timer::is_initialized() {
+            return Err(ShellError::failure("timer not initialized"));
+        }
+
+        let ticks = timer::ticks();
+        let seconds = (timer::uptime_ms() + 500) / 1000;
+        crate::printkln!(
+            "{}h {}m {}s ({} ticks at {} Hz)",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            ticks,
+            timer::TICK_RATE_HZ
+        );
+        Ok(())
+    }
+
+    fn sleep(&self, args: &str) -> CommandResult {
+        let ms = match strtoul(args, 10) {
+            Some(ms) if ms <= u32::MAX as u64 => ms as u32,
+            _ => return Err(ShellError::usage()),
+        };
+        if !timer::is_initialized() {
+            return Err(ShellError::failure("timer not initialized"));
+        }
+
+        if wait_ms(ms, |event| event == KeyEvent::Ctrl('c')) {
+            Ok(())
+        } else {
+            println("^C");
+            Err(ShellError::status(STATUS_INTERRUPTED))
+        }
+    }
+
+    fn repeat(&mut self, args: &str) -> CommandResult {
+        let (count, command) = split_command(args);
+        let count = match strtoul(count, 10) {
+            Some(count) if !command.is_empty() => count,
+            _ => return Err(ShellError::usage()),
+        };
+
+        let mut status = STATUS_SUCCESS;
+        for _ in 0..count {
+            if interrupt_requested() {
+                println("^C");
+                return Err(ShellError::status(STATUS_INTERRUPTED));
+            }
+            status = self.handle_command(command);
+            if status == STATUS_INTERRUPTED {
+                break;
+            }
+        }
+        status_result(status)
+    }
+
+    fn watch(&mut self, args: &str) -> CommandResult {
+        let (interval, command) = split_command(args);
+        let interval = match strtoul(interval, 10) {
+            Some(ms) if ms > 0 && ms <= u32::MAX as u64 && !command.is_empty() => ms as u32,
+            _ => return Err(ShellError::usage()),
+        };
+        if !timer::is_initialized() {
+            return Err(ShellError::failure("timer not initialized"));
+        }
+
+        loop {
+            printk::clear();
+            theme::apply(Role::Muted);
+            crate::printkln!("Every {}ms: {} (press any key to stop)", interval, command);
+            reset_color();
+            println("");
+            self.handle_command(command);
+            if !wait_ms(interval, |_| true) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn clear(&self, args: &str) -> CommandResult {
+        match args {
+            "" => printk::clear(),
+            "--all" => printk::clear_all(),
+            _ => return Err(ShellError::usage()),
+        }
+        Ok(())
+    }
+
+    fn irq(&self) -> CommandResult {
+        let mode = if apic::is_active() { "APIC" } else { "PIC" };
+        crate::printkln!("Controller:      {}", mode);
+        crate::printkln!("Timer ticks:     {}", timer::ticks());
+        for source in idt::SPURIOUS_SOURCES {
+            crate::printkln!(
+                "Spurious {:<7} {}",
+                source.name(),
+                idt::spurious_count(source)
+            );
+        }
+        crate::printkln!("Unhandled:       {}", idt::unhandled_total());
+
+        for vector in 0..=u8::MAX {
+            let count = idt::unhandled_count(vector);
+            if count == 0 {
+                continue;
+            }
+            match pic::irq_for_vector(vector) {
+                Some(irq) => crate::printkln!("  vector {:>3} (IRQ {:>2}) {}", vector, irq, count),
+                None => crate::printkln!("  vector {:>3}          {}", vector, count),
+            }
+        }
+        Ok(())
+    }
+
+    fn free(&self, args: &str) -> CommandResult {
+        let raw = match args {
+            "" => false,
+            "-b" => true,
+            _ => return Err(ShellError::usage()),
+        };
+
+        let stats = memory::get_stats();
+        let row = |name: &str, total: usize, used: usize, free: usize| {
+            if raw {
+                crate::printkln!("{:<10}{:>12}{:>12}{:>12}", name, total, used, free);
+            } else {
+                crate::printkln!(
+                    "{:<10}{:>12}{:>12}{:>12}",
+                    name,
+                    HumanSize(total as u64),
+                    HumanSize(used as u64),
+                    HumanSize(free as u64)
+                );
+            }
+        };
+
+        theme::apply(Role::Label);
+        crate::printkln!("{:<10}{:>12}{:>12}{:>12}", "", "total", "used", "free");
+        reset_color();
+        row(
+            "Physical",
+            stats.total_memory,
+            stats.used_memory,
+            stats.free_memory,
+        );
+        row("Heap", stats.heap_size, stats.heap_used, stats.heap_free);
+        crate::printkln!(
+            "{:<10}{:>12}{:>12}{:>12}",
+            "Frames",
+            stats.total_frames,
+            stats.used_frames,
+            stats.free_frames
+        );
+        println("");
+
+        if raw {
+            crate::printkln!("Reserved:           {}", stats.reserved_memory);
+            crate::printkln!("Largest heap block: {}", stats.heap_largest_free);
+        } else {
+            crate::printkln!(
+                "Reserved:           {}",
+                HumanSize(stats.reserved_memory as u64)
+            );
+            crate::printkln!(
+                "Largest heap block: {}",
+                HumanSize(stats.heap_largest_free as u64)
+            );
+        }
+        crate::printkln!("Heap allocations:   {}", stats.heap_allocations);
+        crate::printkln!("Free heap blocks:   {}", stats.heap_free_blocks);
+        Ok(())
+    }
+
+    fn lsmem(&self) -> CommandResult {
+        theme::apply(Role::Label);
+        crate::printkln!("{:<12}{:<12}{:>12}", "start", "end", "size");
+        reset_color();
+
+        for region in pmm::usable_regions() {
+            crate::printkln!(
+                "{:#010x}  {:#010x}  {:>12}",
+                region.start,
+                region.end,
+                HumanSize(region.len() as u64)
+            );
+        }
+        Ok(())
+    }
+
+    fn hexdump(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+
+        let address = match parse_address(parts.next()) {
+            Some(address) => address,
+            None => return Err(ShellError::usage()),
+        };
+
+        let len = match parts.next() {
+            Some(arg) => match strtol(arg, 0) {
+                Some(len) if (1..=HEXDUMP_MAX_LEN as i64).contains(&len) => len as usize,
+                _ => {
+                    return Err(ShellError::failure(format_args!(
+                        "length must be between 1 and {}",
+                        HEXDUMP_MAX_LEN
+                    )))
+                }
+            },
+            None => HEXDUMP_DEFAULT_LEN,
+        };
+
+        if !paging::is_range_mapped(address, len) {
+            return Err(ShellError::failure("address range is not mapped"));
+        }
+
+        let mut offset = 0;
+        while offset < len {
+            let count = core::cmp::min(16, len - offset);
+            let mut line = [0u8; 16];
+            for (i, byte) in line.iter_mut().take(count).enumerate() {
+                *byte = unsafe { core::ptr::read_volatile((address + offset + i) as *const u8) };
+            }
+            printk::print_hexdump_line((address + offset) as u32, &line[..count]);
+            offset += count;
+        }
+        Ok(())
+    }
+
+    fn peek(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+
+        let address = match parse_address(parts.next()) {
+            Some(address) => address,
+            None => return Err(ShellError::usage()),
+        };
+
+        let size = match parse_access_size(parts.next()) {
+            Some(size) => size,
+            None => return Err(ShellError::failure("size must be 1, 2, 4 or 8")),
+        };
+
+        let value = match size {
+            1 => memory::peek::<u8>(address).map(|v| v as u64),
+            2 => memory::peek::<u16>(address).map(|v| v as u64),
+            4 => memory::peek::<u32>(address).map(|v| v as u64),
+            _ => memory::peek::<u64>(address),
+        };
+
+        let value = value.ok_or_else(|| ShellError::failure("address is not mapped"))?;
+        crate::printkln!(
+            "{:#010x}: {:#0width$x} ({})",
+            address,
+            value,
+            value,
+            width = size * 2 + 2
+        );
+        Ok(())
+    }
+
+    fn poke(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+
+        let address = parse_address(parts.next());
+        let value = parts.next().and_then(|arg| strtol(arg, 0));
+        let (address, value) = match (address, value) {
+            (Some(address), Some(value)) => (address, value),
+            _ => return Err(ShellError::usage()),
+        };
+
+        let size = match parse_access_size(parts.next()) {
+            Some(size) => size,
+            None => return Err(ShellError::failure("size must be 1, 2, 4 or 8")),
+        };
+
+        if !fits_in(value, size) {
+            return Err(ShellError::failure(format_args!(
+                "value does not fit in {} byte(s)",
+                size
+            )));
+        }
+
+        if address < SUSPICIOUS_WRITE_LIMIT {
+            panic::warn("poke: writing below 0x1000 (real-mode IVT/BDA/GDT area)");
+        }
+
+        let written = match size {
+            1 => memory::poke(address, value as u8),
+            2 => memory::poke(address, value as u16),
+            4 => memory::poke(address, value as u32),
+            _ => memory::poke(address, value as u64),
+        };
+
+        if !written {
+            return Err(ShellError::failure("address is not mapped"));
+        }
+        Ok(())
+    }
+
+    fn read_port(&self, args: &str) -> CommandResult {
+        let port = match parse_port(args.split_whitespace().next()) {
+            Some(port) => port,
+            None => return Err(ShellError::usage()),
+        };
+
+        let value = unsafe { inb(port) };
+        crate::printkln!("port {:#06x}: {:#04x} ({})", port, value, value);
+        Ok(())
+    }
+
+    fn write_port(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+
+        let port = parse_port(parts.next());
+        let value = parts.next().and_then(|arg| strtol(arg, 16));
+        let (port, value) = match (port, value) {
+            (Some(port), Some(value)) if (0..=u8::MAX as i64).contains(&value) => {
+                (port, value as u8)
+            }
+            _ => return Err(ShellError::usage()),
+        };
+
+        unsafe {
+            outb(port, value);
+        }
+        Ok(())
+    }
+
+    fn keyrate(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+        let (_, current_rate) = keyboard::get_typematic();
+
+        if let Some(arg) = parts.next() {
+            let delay = match strtoul(arg, 0) {
+                Some(delay) if delay <= keyboard::TYPEMATIC_MAX_DELAY as u64 => delay as u8,
+                _ => return Err(ShellError::failure("delay must be between 0 and 3")),
+            };
+
+            let rate = match parts.next().map(|arg| strtoul(arg, 0)) {
+                Some(Some(rate)) if rate <= keyboard::TYPEMATIC_MAX_RATE as u64 => rate as u8,
+                Some(_) => return Err(ShellError::failure("rate must be between 0 and 31 (0x1F)")),
+                None => current_rate,
+            };
+
+            keyboard::set_typematic(delay, rate)
+                .map_err(|error| ShellError::failure(error.description()))?;
+        }
+
+        self.print_typematic();
+        Ok(())
+    }
+
+    fn kbrate(&self, args: &str) -> CommandResult {
+        const USAGE: &str = "usage: kbrate [<2-30 cps> <250-1000 ms> | soft on|off]";
+        let mut parts = args.split_whitespace();
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => {}
+            (Some("soft"), Some(state @ ("on" | "off")), None) => {
+                keyboard::set_soft_repeat(state == "on");
+            }
+            (Some(cps), Some(delay_ms), None) => {
+                let (cps, delay_ms) = match (strtoul(cps, 10), strtoul(delay_ms, 10)) {
+                    (Some(cps), Some(delay_ms))
+                        if (2..=30).contains(&cps) && (250..=1000).contains(&delay_ms) =>
+                    {
+                        (cps as u32, delay_ms as u32)
+                    }
+                    _ => return Err(ShellError::new(STATUS_USAGE, USAGE)),
+                };
+
+                let delay = keyboard::typematic_delay_for_ms(delay_ms);
+                let rate = keyboard::typematic_rate_for_cps(cps);
+                if let Err(error) = keyboard::set_typematic(delay, rate) {
+                    theme::apply(Role::Warning);
+                    crate::printkln!("kbrate: {}, using software repeat", error.description());
+                    reset_color();
+                    keyboard::set_repeat(delay, rate);
+                    keyboard::set_soft_repeat(true);
+                }
+            }
+            _ => return Err(ShellError::new(STATUS_USAGE, USAGE)),
+        }
+
+        self.print_typematic();
+        Ok(())
+    }
+
+    fn print_typematic(&self) {
+        let (delay, rate) = keyboard::get_typematic();
+        let tenths = keyboard::typematic_rate_tenths(rate);
+
+        theme::apply(Role::Label);
+        print("Typematic delay: ");
+        reset_color();
+        print_dec(delay as u32);
+        print(" (");
+        print_dec(keyboard::typematic_delay_ms(delay));
+        println(" ms)");
+
+        theme::apply(Role::Label);
+        print("Typematic rate:  ");
+        reset_color();
+        print_dec(rate as u32);
+        print(" (");
+        print_dec(tenths / 10);
+        print(".");
+        print_dec(tenths % 10);
+        println(" chars/sec)");
+
+        theme::apply(Role::Label);
+        print("Software repeat: ");
+        reset_color();
+        println(if keyboard::is_soft_repeat() {
+            "on"
+        } else {
+            "off"
+        });
+    }
+
+    fn kbdstat(&self) -> CommandResult {
+        crate::printkln!(
+            "Buffered:     {}/{} scancodes",
+            keyboard::buffered(),
+            keyboard::Keyboard::<{ keyboard::BUFFER_SIZE }>::capacity()
+        );
+        crate::printkln!("Dropped:      {}", keyboard::dropped_scancodes());
+        let (caps_lock, num_lock) = keyboard::lock_state();
+        crate::printkln!("Caps Lock:    {}", if caps_lock { "on" } else { "off" });
+        crate::printkln!("Num Lock:     {}", if num_lock { "on" } else { "off" });
+        crate::printkln!(
+            "Soft repeat:  {}",
+            if keyboard::is_soft_repeat() {
+                "on"
+            } else {
+                "off"
+            }
+        );
+        Ok(())
+    }
+
+    fn mouse(&self) -> CommandResult {
+        if !mouse::is_initialized() {
+            return Err(ShellError::failure("no PS/2 mouse"));
+        }
+
+        theme::apply(Role::Info);
+        println("Move the mouse to see its events, press any key to stop.");
+        reset_color();
+
+        while keyboard::read_event().is_none() {
+            while let Some(event) = mouse::poll() {
+                let button = |pressed: bool, name: char| if pressed { name } else { '-' };
+                crate::printkln!(
+                    "dx {:4}  dy {:4}  {}{}{}",
+                    event.dx,
+                    event.dy,
+                    button(event.left(), 'L'),
+                    button(event.middle(), 'M'),
+                    button(event.right(), 'R')
+                );
+            }
+            task::yield_now();
+            idle::idle();
+        }
+
+        crate::printkln!(
+            "Dropped {} events, resynchronized {} times",
+            mouse::dropped_events(),
+            mouse::resyncs()
+        );
+        Ok(())
+    }
+
+    fn showkeys(&self) -> CommandResult {
+        theme::apply(Role::Info);
+        println("Press keys to see their scancodes, press Escape twice to exit.");
+        reset_color();
+
+        let previous_mode = keyboard::set_raw_mode(true);
+        let mut extended = false;
+        let mut escape_pressed = false;
+
+        loop {
+            let scancode = match keyboard::read_raw() {
+                Some(scancode) => scancode,
+                None => {
+                    task::yield_now();
+                    idle::idle();
+                    continue;
+                }
+            };
+
+            if keyboard::is_extended_prefix(scancode) {
+                print("0x");
+                printk::print_byte_hex(scancode);
+                print(" ");
+                extended = true;
+                continue;
+            }
+
+            let released = keyboard::is_release(scancode);
+
+            print("0x");
+            printk::print_byte_hex(scancode);
+            if released {
+                theme::apply(Role::Muted);
+                print("  release");
+            } else {
+                theme::apply(Role::Success);
+                print("  press");
+            }
+            if extended {
+                theme::apply(Role::Label);
+                print(" (extended)");
+            }
+            reset_color();
+            println("");
+
+            if !released {
+                if !extended && scancode == keyboard::scancodes::ESCAPE {
+                    if escape_pressed {
+                        break;
+                    }
+                    escape_pressed = true;
+                } else {
+                    escape_pressed = false;
+                }
+            }
+            extended = false;
+        }
+
+        while keyboard::read_raw().is_some() {}
+        keyboard::set_raw_mode(previous_mode);
+        Ok(())
+    }
+
+    fn find_abbreviation(&self, name: &str) -> Option<&Abbreviation> {
+        self.abbreviations
+            .iter()
+            .find(|abbr| abbr.is_used() && abbr.name() == name)
+    }
+
+    fn abbr(&mut self, args: &str) -> CommandResult {
+        let (name, expansion) = split_command(args);
+
+        if name.is_empty() {
+            let mut count = 0;
+            for abbr in self.abbreviations.iter().filter(|abbr| abbr.is_used()) {
+                print("  ");
+                print(abbr.name());
+                for _ in abbr.name().len()..ABBR_NAME_LEN {
+                    print(" ");
+                }
+                println(abbr.expansion());
+                count += 1;
+            }
+            if count == 0 {
+                theme::apply(Role::Muted);
+                println("  [No abbreviations]");
+                reset_color();
+            }
+            return Ok(());
+        }
+
+        let existing = self
+            .abbreviations
+            .iter()
+            .position(|abbr| abbr.is_used() && abbr.name() == name);
+
+        if expansion.is_empty() {
+            let index = existing.ok_or_else(|| {
+                ShellError::failure(format_args!("no such abbreviation: {}", name))
+            })?;
+            self.abbreviations[index] = Abbreviation::empty();
+            return Ok(());
+        }
+
+        if name.len() > ABBR_NAME_LEN || expansion.len() > ABBR_EXPANSION_LEN {
+            return Err(ShellError::failure("name or expansion too long"));
+        }
+
+        let index =
+            match existing.or_else(|| self.abbreviations.iter().position(|abbr| !abbr.is_used())) {
+                Some(index) => index,
+                None => return Err(ShellError::failure("abbreviation table is full")),
+            };
+
+        let abbr = &mut self.abbreviations[index];
+        *abbr = Abbreviation::empty();
+        abbr.name[..name.len()].copy_from_slice(name.as_bytes());
+        abbr.name_len = name.len();
+        abbr.expansion[..expansion.len()].copy_from_slice(expansion.as_bytes());
+        abbr.expansion_len = expansion.len();
+        Ok(())
+    }
+
+    fn list_files(&self) -> CommandResult {
+        let mut count = 0;
+
+        for fd in ramfs::files() {
+            print("  ");
+            print(ramfs::name(fd));
+            for _ in ramfs::name(fd).len()..ramfs::MAX_NAME_LEN {
+                print(" ");
+            }
+            print_dec(ramfs::size(fd) as u32);
+            println(" bytes");
+            count += 1;
+        }
+
+        if let Some(fs) = initrd::fs() {
+            for entry in fs.list() {
+                print("  ");
+                print(entry.name());
+                for _ in entry.name().len()..ramfs::MAX_NAME_LEN {
+                    print(" ");
+                }
+                print(" ");
+                print_dec(entry.data.len() as u32);
+                println(" bytes (ro)");
+                count += 1;
+            }
+        }
+
+        if count == 0 {
+            theme::apply(Role::Muted);
+            println("  [No files]");
+            reset_color();
+        }
+        Ok(())
+    }
+
+    fn cat(&self, args: &str) -> CommandResult {
+        let name = args.trim();
+        if name.is_empty() {
+            return Err(ShellError::usage());
+        }
+
+        let data = read_file(name)
+            .ok_or_else(|| ShellError::failure(format_args!("no such file: {}", name)))?;
+        printk::print_bytes(data);
+        if data.last() != Some(&b'\n') {
+            println("");
+        }
+        Ok(())
+    }
+
+    fn run_script(&mut self, args: &str) -> CommandResult {
+        let (exit_on_error, name) = match split_command(args) {
+            ("-e", name) => (true, name),
+            _ => (false, args),
+        };
+        if name.is_empty() {
+            return Err(ShellError::usage());
+        }
+        if read_file(name).is_none() {
+            return Err(ShellError::failure(format_args!("no such file: {}", name)));
+        }
+        if self.run_depth >= MAX_RUN_DEPTH {
+            return Err(ShellError::failure("scripts nested too deeply"));
+        }
+
+        self.run_depth += 1;
+        let mut status = STATUS_SUCCESS;
+        let mut offset = 0;
+        let mut line_number = 0;
+
+        while let Some(data) = read_file(name) {
+            if offset >= data.len() {
+                break;
+            }
+
+            let rest = &data[offset..];
+            let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            let mut line = [0u8; MAX_INPUT];
+            let line_len = core::cmp::min(len, MAX_INPUT);
+            line[..line_len].copy_from_slice(&rest[..line_len]);
+            offset += len + 1;
+            line_number += 1;
+
+            status = match core::str::from_utf8(&line[..line_len]) {
+                Ok(line) => {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    if interrupt_requested() {
+                        println("^C");
+                        status = STATUS_INTERRUPTED;
+                        break;
+                    }
+
+                    theme::apply(Role::Muted);
+                    print(PROMPT);
+                    println(line);
+                    reset_color();
+                    self.handle_command(line)
+                }
+                Err(_) => {
+                    print_error("run", format_args!("invalid text on line {}", line_number));
+                    STATUS_FAILURE
+                }
+            };
+            if status == STATUS_INTERRUPTED {
+                break;
+            }
+            if status != STATUS_SUCCESS && exit_on_error {
+                self.run_depth -= 1;
+                return Err(ShellError::new(
+                    status,
+                    format_args!("stopped at {}:{} (status {})", name, line_number, status),
+                ));
+            }
+        }
+
+        self.run_depth -= 1;
+        status_result(status)
+    }
+
+    fn remove(&self, args: &str) -> CommandResult {
+        let name = args.trim();
+        if name.is_empty() {
+            return Err(ShellError::usage());
+        }
+
+        if script_file().map(ramfs::name) == Some(name) {
+            return Err(ShellError::failure(format_args!(
+                "file is being recorded: {}",
+                name
+            )));
+        }
+        if !ramfs::remove(name) {
+            return Err(ShellError::failure(format_args!("no such file: {}", name)));
+        }
+        Ok(())
+    }
+
+    fn script(&self, args: &str) -> CommandResult {
+        let (action, name) = split_command(args);
+
+        match action {
+            "start" => {
+                if let Some(fd) = script_file() {
+                    return Err(ShellError::failure(format_args!(
+                        "already recording to {}",
+                        ramfs::name(fd)
+                    )));
+                }
+
+                if name.is_empty() {
+                    return Err(ShellError::usage());
+                }
+
+                let fd = ramfs::create(name).ok_or_else(|| {
+                    ShellError::failure(format_args!("cannot create file: {}", name))
+                })?;
+                unsafe {
+                    SCRIPT_FILE = Some(fd);
+                }
+                printk::set_tee(Some(script_sink));
+                print("Script started, output file is ");
+                println(name);
+            }
+            "stop" => {
+                let fd = script_file().ok_or_else(|| ShellError::failure("not recording"))?;
+                printk::set_tee(None);
+                unsafe {
+                    SCRIPT_FILE = None;
+                }
+                print("Script done, output file is ");
+                println(ramfs::name(fd));
+                if ramfs::is_full(fd) {
+                    theme::apply(Role::Warning);
+                    println("Warning: file size limit reached, output was truncated");
+                    reset_color();
+                }
+            }
+            _ => {
+                return Err(ShellError::new(
+                    STATUS_USAGE,
+                    "usage: script start <file> | script stop",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn serial(&self, args: &str) -> CommandResult {
+        let enabled = match args {
+            "" => {
+                print("Serial mirroring: ");
+                println(if printk::is_serial_mirroring() {
+                    "on"
+                } else {
+                    "off"
+                });
+                return Ok(());
+            }
+            "on" => true,
+            "off" => false,
+            _ => return Err(ShellError::usage()),
+        };
+
+        if enabled && !serial::is_present() {
+            return Err(ShellError::failure("no serial port detected"));
+        }
+        printk::set_serial_mirror(enabled);
+        Ok(())
+    }
+
+    fn log(&mut self, args: &str) -> CommandResult {
+        if args.is_empty() {
+            return Err(ShellError::usage());
+        }
+        if !serial::is_present() {
+            return Err(ShellError::failure("no serial port detected"));
+        }
+
+        let previous = printk::set_serial_mirror(true);
+        let status = self.handle_command(args);
+        printk::set_serial_mirror(previous);
+        status_result(status)
+    }
+
+    fn dmesg(&self, args: &str) -> CommandResult {
+        let mut count = klog::KLOG_LINES;
+        let mut level = LogLevel::Debug;
+        let mut console_level = None;
+
+        let mut parts = args.split_whitespace();
+        while let Some(arg) = parts.next() {
+            let valid = match arg {
+                "-l" | "-n" => match parts.next().and_then(LogLevel::from_name) {
+                    Some(parsed) if arg == "-l" => {
+                        level = parsed;
+                        true
+                    }
+                    Some(parsed) => {
+                        console_level = Some(parsed);
+                        true
+                    }
+                    None => false,
+                },
+                arg => match strtoul(arg, 10) {
+                    Some(n) => {
+                        count = n as usize;
+                        true
+                    }
+                    None => false,
+                },
+            };
+            if !valid {
+                return Err(ShellError::usage());
+            }
+        }
+
+        if let Some(console_level) = console_level {
+            printk::set_level(console_level);
+            return Ok(());
+        }
+
+        let mut empty = true;
+        for line in klog::tail(count).filter(|line| line.level() <= level) {
+            theme::apply(line.level().role());
+            if printk::timestamps_enabled() {
+                crate::printk!("{} ", printk::Timestamp(line.uptime_ms()));
+            }
+            crate::printkln!("[{:<5}] {}", line.level().name(), line.as_str());
+            empty = false;
+        }
+        reset_color();
+
+        if empty {
+            theme::apply(Role::Muted);
+            println("  [Kernel log is empty]");
+            reset_color();
+        }
+        Ok(())
+    }
+
+    fn spawn(&self, args: &str) -> CommandResult {
+        let interval = match args {
+            "" => COUNTER_DEFAULT_TICKS,
+            arg => match strtoul(arg, 0) {
+                Some(ticks) if ticks > 0 && ticks <= u32::MAX as u64 => ticks as u32,
+                _ => return Err(ShellError::usage()),
+            },
+        };
+
+        COUNTER_INTERVAL.store(interval, Ordering::SeqCst);
+        let id =
+            task::spawn(counter_task).ok_or_else(|| ShellError::failure("no free task slots"))?;
+        crate::printkln!("Started task {}", id);
+        Ok(())
+    }
+
+    fn idle(&self, args: &str) -> CommandResult {
+        if !args.is_empty() {
+            let requested = match idle::IdlePolicy::from_name(args) {
+                Some(policy) => policy,
+                None => return Err(ShellError::usage()),
+            };
+
+            if idle::set_policy(requested) != requested {
+                panic::warn("idle: MONITOR/MWAIT is not supported, using hlt");
+            }
+        }
+
+        print("Idle policy: ");
+        println(idle::policy().name());
+        Ok(())
+    }
+
+    fn screen(&self, args: &str) -> CommandResult {
+        if args.is_empty() {
+            crate::printkln!(
+                "Current screen: {} of {}",
+                printk::active_screen() + 1,
+                vga::MAX_SCREENS
+            );
+            return Ok(());
+        }
+
+        let id = match strtoul(args, 10) {
+            Some(n) if n >= 1 && n <= vga::MAX_SCREENS as u64 => n as usize - 1,
+            _ => return Err(ShellError::usage()),
+        };
+
+        if !switch_screen(id) {
+            return Err(ShellError::failure(format_args!(
+                "could not switch to screen {}",
+                id + 1
+            )));
+        }
+        Ok(())
+    }
+
+    fn scrollback(&self) -> CommandResult {
+        let history = unsafe { &mut *core::ptr::addr_of_mut!(HISTORY) };
+        history.clear();
+        printk::export_history(&mut |line| {
+            let _ = writeln!(history, "{}", line);
+        });
+        print(history.as_str());
+        Ok(())
+    }
+
+    fn blink(&self, args: &str) -> CommandResult {
+        let enabled = match args {
+            "" => {
+                print("Blink mode: ");
+                println(if vga::blink_mode() { "on" } else { "off" });
+                return Ok(());
+            }
+            "on" => true,
+            "off" => false,
+            _ => return Err(ShellError::usage()),
+        };
+
+        vga::set_blink_mode(enabled);
+        Ok(())
+    }
+
+    fn save_config(&self) -> CommandResult {
+        config::save();
+        let saved = config::load();
+        crate::printkln!(
+            "Saved to CMOS: log level {}, colors {} on {}",
+            saved.log_level.name(),
+            saved.foreground.name(),
+            saved.background.name()
+        );
+        Ok(())
+    }
+
+    fn theme(&self, args: &str) -> CommandResult {
+        let (action, rest) = split_command(args);
+
+        match action {
+            "" => self.show_theme(),
+            "set" => {
+                let mut parts = rest.split_whitespace();
+                let role = parts.next().and_then(Role::from_name);
+                let fg = parts.next().and_then(Color::from_name);
+                let bg = parts.next().map(Color::from_name);
+
+                let (role, fg) = match (role, fg, parts.next()) {
+                    (Some(role), Some(fg), None) => (role, fg),
+                    _ => return Err(ShellError::usage()),
+                };
+                let bg = match bg {
+                    Some(Some(bg)) => bg,
+                    Some(None) => return Err(ShellError::usage()),
+                    None => theme::colors(role).1,
+                };
+
+                theme::set_colors(role, fg, bg);
+            }
+            "load" => match theme::preset(rest) {
+                Some(preset) => theme::load(preset),
+                None => {
+                    return Err(ShellError::new(
+                        STATUS_USAGE,
+                        "usage: theme load <dark|light|amber>",
+                    ))
+                }
+            },
+            _ => {
+                return Err(ShellError::new(
+                    STATUS_USAGE,
+                    "usage: theme [set <role> <fg> [bg] | load <preset>]",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn color(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+        let (fg_name, bg_name) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(fg), Some(bg), None) => (fg, bg),
+            _ => return Err(ShellError::usage()),
+        };
+
+        match (Color::from_name(fg_name), Color::from_name(bg_name)) {
+            (Some(fg), Some(bg)) => {
+                theme::set_colors(Role::Normal, fg, bg);
+                reset_color();
+            }
+            (fg, _) => {
+                let unknown = if fg.is_none() { fg_name } else { bg_name };
+                print("Valid colors:");
+                for color in (0..16).filter_map(Color::from_index) {
+                    crate::printk!(" {}", color.name());
+                }
+                println("");
+                return Err(ShellError::failure(format_args!(
+                    "unknown color '{}'",
+                    unknown
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn show_theme(&self) {
+        let current = theme::current();
+
+        for role in theme::ROLES {
+            let (fg, bg) = current.get(role);
+            crate::printk!("  {:<10}", role.name());
+            theme::apply(role);
+            print(" Sample ");
+            reset_color();
+            crate::printkln!(" {} on {}", fg.name(), bg.name());
+        }
+    }
+
+    fn watchdog(&self, args: &str) -> CommandResult {
+        let (option, value) = split_command(args);
+
+        match option {
+            "" => {}
+            "timeout" => match strtoul(value, 10) {
+                Some(seconds) if seconds <= u32::MAX as u64 => {
+                    watchdog::set_timeout(seconds as u32)
+                }
+                _ => return Err(ShellError::usage()),
+            },
+            "mode" => match watchdog::WatchdogMode::from_name(value) {
+                Some(mode) => watchdog::set_mode(mode),
+                None => return Err(ShellError::usage()),
+            },
+            _ => return Err(ShellError::usage()),
+        }
+
+        if watchdog::is_enabled() {
+            crate::printkln!(
+                "Watchdog: timeout {}s, mode {}, last pet {}ms ago",
+                watchdog::timeout(),
+                watchdog::mode().name(),
+                watchdog::ms_since_pet()
+            );
+        } else {
+            println("Watchdog: disabled");
+        }
+        Ok(())
+    }
+
+    fn overflow(&self) -> CommandResult {
+        println("Recursing until the stack overflows...");
+        let depth = stack_guard::exhaust(0);
+        crate::printkln!("Recursion returned at depth {}", depth);
+        Ok(())
+    }
+
+    fn memtest(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+        let address = parse_address(parts.next());
+        let len = parts.next().and_then(|arg| strtoul(arg, 0));
+        let (start, len) = match (address, len, parts.next()) {
+            (Some(address), Some(len), None) if len > 0 && len <= u32::MAX as u64 => {
+                (address, len as usize)
+            }
+            _ => return Err(ShellError::usage()),
+        };
+        let Some(end) = start.checked_add(len) else {
+            return Err(ShellError::failure("range wraps around the address space"));
+        };
+        if !start.is_multiple_of(4) || !len.is_multiple_of(4) {
+            return Err(ShellError::failure(
+                "address and length must be 4-byte aligned",
+            ));
+        }
+
+        let regions = pmm::free_regions(start, end);
+        let regions = regions.as_slice();
+        let tested: usize = regions.iter().map(|region| region.len()).sum();
+        if tested == 0 {
+            return Err(ShellError::failure(
+                "no free frames in range (kernel, heap, page tables or stacks)",
+            ));
+        }
+
+        if tested > MEMTEST_CONFIRM_BYTES {
+            crate::printk!("memtest: test {}? [y/N] ", HumanSize(tested as u64));
+            let answer = keyboard::wait_for_char();
+            crate::printkln!("{}", answer);
+            if !answer.eq_ignore_ascii_case(&'y') {
+                return Err(ShellError::failure("cancelled"));
+            }
+        }
+
+        for region in regions {
+            crate::printkln!("Testing 0x{:08X}-0x{:08X}...", region.start, region.end - 1);
+            let mut block = region.start;
+            while block < region.end {
+                if interrupt_requested() {
+                    println("^C");
+                    return Err(ShellError::status(STATUS_INTERRUPTED));
+                }
+
+                let words = core::cmp::min(MEMTEST_BLOCK_WORDS, (region.end - block) / 4);
+                if let Err(failure) = sync::without_interrupts(|| memtest_block(block, words)) {
+                    return Err(match failure.actual {
+                        Some(actual) => ShellError::failure(format_args!(
+                            "mismatch at 0x{:08X}: wrote 0x{:08X}, read 0x{:08X}",
+                            failure.address, failure.expected, actual
+                        )),
+                        None => ShellError::failure(format_args!(
+                            "0x{:08X} is not mapped",
+                            failure.address
+                        )),
+                    });
+                }
+                block += words * 4;
+            }
+        }
+        if tested < len {
+            crate::printkln!("Skipped {} in use", HumanSize((len - tested) as u64));
+        }
+
+        theme::apply(Role::Success);
+        println("OK");
+        reset_color();
+        Ok(())
+    }
+
+    fn earlytest(&self) -> CommandResult {
+        println("");
+        let (_, row) = printk::cursor_position();
+        early_console::set_position(0, row.saturating_sub(1));
+        early_console::write_str("early console: raw VGA and COM1 output OK");
+        println("");
+        Ok(())
+    }
+
+    fn hang(&self, args: &str) -> CommandResult {
+        let seconds = match args {
+            "" => None,
+            _ => match strtoul(args, 10) {
+                Some(seconds) if seconds <= u32::MAX as u64 / 1000 => Some(seconds as u32),
+                _ => return Err(ShellError::usage()),
+            },
+        };
+
+        println("Hanging...");
+        let start = timer::ticks();
+        let duration = seconds.map(|seconds| timer::ms_to_ticks(seconds * 1000));
+        while duration.is_none_or(|duration| timer::ticks().wrapping_sub(start) < duration) {
+            core::hint::spin_loop();
+        }
+        Ok(())
+    }
+
+    fn exec(&self, args: &str) -> CommandResult {
+        let name = args.trim();
+        if name.is_empty() {
+            return Err(ShellError::usage());
+        }
+
+        let data = read_file(name)
+            .ok_or_else(|| ShellError::failure(format_args!("no such file: {}", name)))?;
+        let run = elf::exec(data).map_err(|error| ShellError::failure(error.description()))?;
+        crate::printkln!(
+            "{} exited with status {} after {} syscall(s)",
+            name,
+            run.status,
+            run.syscalls
+        );
+        Ok(())
+    }
+
+    fn strace(&self, args: &str) -> CommandResult {
+        let enabled = match args {
+            "" => {
+                print("Syscall tracing: ");
+                println(if syscall::is_tracing() { "on" } else { "off" });
+                return Ok(());
+            }
+            "on" => true,
+            "off" => false,
+            _ => return Err(ShellError::usage()),
+        };
+        syscall::set_trace(enabled);
+        Ok(())
+    }
+
+    fn membench(&self, args: &str) -> CommandResult {
+        let kib = match args {
+            "" => MEMBENCH_DEFAULT_KIB,
+            _ => match strtoul(args, 10) {
+                Some(kib) if (1..=MEMBENCH_MAX_KIB).contains(&kib) => kib,
+                _ => return Err(ShellError::usage()),
+            },
+        };
+        if !cpuid::has_tsc() {
+            return Err(ShellError::failure("no time stamp counter"));
+        }
+
+        let len = kib as usize * 1024;
+        let frames = len.div_ceil(PAGE_SIZE);
+        let Some(dest) = pmm::alloc_frames(frames) else {
+            return Err(ShellError::failure("out of memory"));
+        };
+        let Some(src) = pmm::alloc_frames(frames) else {
+            pmm::free_frames(dest, frames);
+            return Err(ShellError::failure("out of memory"));
+        };
+        let (dest, src) = (dest as *mut u8, src as *const u8);
+
+        let time = |operation: &dyn Fn()| {
+            let start = random::rdtsc();
+            operation();
+            random::rdtsc().wrapping_sub(start)
+        };
+        let results = [
+            (
+                "memset",
+                time(&|| selftest::byte_fill(dest, 0x5A, len)),
+                time(&|| unsafe {
+                    kfs_lib::memset(dest, 0x5A, len);
+                }),
+            ),
+            (
+                "memcpy",
+                time(&|| selftest::byte_copy(dest, src, len)),
+                time(&|| unsafe {
+                    kfs_lib::memcpy(dest, src, len);
+                }),
+            ),
+        ];
+        pmm::free_frames(dest as usize, frames);
+        pmm::free_frames(src as usize, frames);
+
+        crate::printkln!("{} KiB, cycles:", kib);
+        crate::printkln!(
+            "{:<8}{:>14}{:>14}{:>10}",
+            "",
+            "byte loop",
+            "rep string",
+            "speedup"
+        );
+        for (name, slow, fast) in results {
+            let ratio = slow * 10 / fast.max(1);
+            crate::printkln!(
+                "{:<8}{:>14}{:>14}{:>8}.{}x",
+                name,
+                slow,
+                fast,
+                ratio / 10,
+                ratio % 10
+            );
+        }
+        Ok(())
+    }
+
+    fn beep(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+
+        let frequency = match parts.next().map(|arg| strtoul(arg, 0)) {
+            None => BEEP_DEFAULT_FREQUENCY,
+            Some(Some(frequency))
+                if (speaker::MIN_FREQUENCY as u64..=speaker::MAX_FREQUENCY as u64)
+                    .contains(&frequency) =>
+            {
+                frequency as u32
+            }
+            Some(_) => {
+                return Err(ShellError::failure(
+                    "frequency must be between 20 and 20000 Hz",
+                ))
+            }
+        };
+
+        let duration = match parts.next().map(|arg| strtoul(arg, 0)) {
+            None => BEEP_DEFAULT_MS,
+            Some(Some(ms)) if ms > 0 && ms <= BEEP_MAX_MS as u64 => ms as u32,
+            Some(_) => {
+                return Err(ShellError::failure(
+                    "duration must be between 1 and 10000 ms",
+                ))
+            }
+        };
+
+        speaker::beep(frequency, duration);
+        Ok(())
+    }
+
+    fn self_test(&mut self, args: &str) -> CommandResult {
+        let summary = match selftest::run(args, self) {
+            Some(summary) => summary,
+            None => {
+                print("Categories:");
+                for category in selftest::CATEGORIES {
+                    print(" ");
+                    print(category.name());
+                }
+                println("");
+                return Err(ShellError::failure(format_args!(
+                    "no test or category named {}",
+                    args
+                )));
+            }
+        };
+
+        theme::apply(if summary.failed == 0 {
+            Role::Success
+        } else {
+            Role::Error
+        });
+        crate::printkln!("{} passed, {} failed", summary.passed, summary.failed);
+        reset_color();
+
+        if summary.failed == 0 {
+            Ok(())
+        } else {
+            Err(ShellError::status(STATUS_FAILURE))
+        }
+    }
+
+    fn usertest(&self) -> CommandResult {
+        println("Entering ring 3...");
+        let run = usermode::run().map_err(|error| ShellError::failure(error.description()))?;
+        crate::printkln!(
+            "Back in ring 0: exit status {}, {} syscall(s)",
+            run.status,
+            run.syscalls
+        );
+        Ok(())
+    }
+
+    fn stacks(&self) -> CommandResult {
+        theme::apply(Role::Muted);
+        println("  Owner    Bottom      Size   Peak   Canary");
+        reset_color();
+
+        for stack in stack_guard::stacks() {
+            crate::printk!(
+                "  {:<8} {:#010x}  {:>5}  {:>5}  ",
+                stack.owner,
+                stack.bottom,
+                stack.size,
+                stack.high_water()
+            );
+            if stack.canary_intact() {
+                theme::apply(Role::Success);
+                println("ok");
+            } else {
+                theme::apply(Role::Error);
+                println("CORRUPTED");
+            }
+            reset_color();
+        }
+        Ok(())
+    }
+
+    fn disks(&self) -> CommandResult {
+        let mut found = false;
+        for (index, drive) in ata::drives() {
+            crate::printkln!(
+                "  {} {}  {:<40} {:>10}  ({} sectors)",
+                index,
+                drive.name(),
+                drive.model(),
+                HumanSize(drive.num_blocks() * drive.block_size() as u64),
+                drive.num_blocks()
+            );
+            found = true;
+        }
+
+        if !found {
+            theme::apply(Role::Muted);
+            println("  [No drives]");
+            reset_color();
+        }
+        Ok(())
+    }
+
+    fn acpi(&self) -> CommandResult {
+        let initialized = acpi::init();
+
+        if let Some((address, rsdp)) = acpi::rsdp() {
+            theme::apply(Role::Label);
+            print("RSDP:  ");
+            reset_color();
+            crate::printkln!("revision {} at {:#010x}", rsdp.revision, address);
+        }
+
+        for table in acpi::tables() {
+            crate::printkln!(
+                "  {}  {:#010x}  {} bytes",
+                table.name(),
+                table.address,
+                table.length
+            );
+        }
+
+        if let Some(power) = acpi::power_info() {
+            theme::apply(Role::Label);
+            print("Power: ");
+            reset_color();
+            crate::printkln!(
+                "PM1a {:#x} PM1b {:#x} SLP_TYP {}/{}",
+                power.pm1a_control,
+                power.pm1b_control,
+                power.slp_typ_a,
+                power.slp_typ_b
+            );
+        }
+        initialized.map_err(|error| ShellError::failure(error.description()))
+    }
+
+    fn read_sector(&self, args: &str) -> CommandResult {
+        let mut parts = args.split_whitespace();
+        let lba = parts.next().and_then(|arg| strtoul(arg, 0));
+        let index = match parts.next() {
+            Some(arg) => strtoul(arg, 10).map(|index| index as usize),
+            None => Some(0),
+        };
+
+        let (lba, index) = match (lba, index) {
+            (Some(lba), Some(index)) => (lba, index),
+            _ => return Err(ShellError::usage()),
+        };
+
+        let drive = match ata::drive(index) {
+            Some(drive) => drive,
+            None => return Err(ShellError::failure("no such drive")),
+        };
+
+        if lba > ata::MAX_LBA28 {
+            return Err(ShellError::failure(
+                "LBA exceeds the 28-bit addressing limit",
+            ));
+        }
+
+        let mut sector = [0u8; ata::SECTOR_SIZE];
+        drive
+            .read_block(lba, &mut sector)
+            .map_err(|error| ShellError::failure(error.description()))?;
+
+        for (i, line) in sector.chunks(16).enumerate() {
+            printk::print_hexdump_line((i * 16) as u32, line);
+        }
+        Ok(())
+    }
+
+    fn fs(&self, args: &str) -> CommandResult {
+        let (action, rest) = split_command(args);
+        let (name, text) = split_command(rest);
+
+        let drive = match ata::drive(0) {
+            Some(drive) => drive,
+            None => return Err(ShellError::failure("no disk found")),
+        };
+
+        let fs_error = |error: fs::ops::FsError| ShellError::failure(error.description());
+
+        if action == "format" {
+            fs::ops::format(drive).map_err(fs_error)?;
+            println("Disk formatted");
+            return Ok(());
+        }
+
+        let mut filesystem = fs::ops::Fs::mount(drive).map_err(fs_error)?;
+
+        let result = match (action, name.is_empty()) {
+            ("ls", _) => filesystem.for_each(|entry| {
+                crate::printkln!("  {:<32} {:>8}", entry.name(), entry.size);
+            }),
+            ("cat", false) => filesystem
+                .find(name)
+                .and_then(|entry| filesystem.read(&entry, printk::print_bytes)),
+            ("write", false) => filesystem.write(name, text.as_bytes()),
+            ("rm", false) => filesystem.remove(name),
+            _ => {
+                return Err(ShellError::new(
+                    STATUS_USAGE,
+                    "usage: fs format | ls | cat <name> | write <name> <text> | rm <name>",
+                ))
+            }
+        };
+
+        result.map_err(fs_error)
+    }
+
+    fn reboot(&self) -> CommandResult {
+        println("Rebooting...");
+        power::reboot()
+    }
+
+    fn halt(&self) -> CommandResult {
+        theme::apply(Role::Muted);
+        println("System halted.");
+        reset_color();
+        power::halt()
+    }
+
+    fn shutdown(&self) -> CommandResult {
+        println("Powering off...");
+        power::shutdown()
+    }
+}
+
+fn counter_task() {
+    let id = task::current();
+    let interval = COUNTER_INTERVAL.load(Ordering::SeqCst);
+    let mut last = timer::ticks();
+
+    for step in 1..=COUNTER_STEPS {
+        while timer::ticks().wrapping_sub(last) < interval {
+            task::yield_now();
+        }
+        last = timer::ticks();
+        crate::printkln!("[task {}] {}/{}", id, step, COUNTER_STEPS);
+    }
+}
+
+pub fn greeting(hour: u8) -> &'static str {
+    match hour {
+        5..=11 => "Good morning",
+        12..=17 => "Good afternoon",
+        _ => "Good evening",
+    }
+}
+
+fn switch_screen(id: usize) -> bool {
+    if !printk::switch_screen(id) {
+        return false;
+    }
+    statusbar::refresh();
+    true
+}
+
+fn interrupt_requested() -> bool {
+    let mut interrupted = false;
+    while let Some(event) = keyboard::read_event() {
+        interrupted |= event == KeyEvent::Ctrl('c');
+    }
+    interrupted
+}
+
+struct MemtestFailure {
+    address: usize,
+    expected: u32,
+    actual: Option<u32>,
+}
+
+fn memtest_block(start: usize, words: usize) -> Result<(), MemtestFailure> {
+    let mut saved = [0u32; MEMTEST_BLOCK_WORDS];
+    for (index, word) in saved[..words].iter_mut().enumerate() {
+        let address = start + index * 4;
+        *word = memory::peek::<u32>(address).ok_or(MemtestFailure {
+            address,
+            expected: 0,
+            actual: None,
+        })?;
+    }
+
+    let result = memtest_patterns(start, words);
+    for (index, &word) in saved[..words].iter().enumerate() {
+        memory::poke(start + index * 4, word);
+    }
+    result
+}
+
+fn memtest_patterns(start: usize, words: usize) -> Result<(), MemtestFailure> {
+    let check = |address: usize, expected: u32| match memory::peek::<u32>(address) {
+        Some(actual) if actual == expected => Ok(()),
+        actual => Err(MemtestFailure {
+            address,
+            expected,
+            actual,
+        }),
+    };
+    let addresses = || (start..start + words * 4).step_by(4);
+
+    for address in addresses() {
+        for bit in 0..32 {
+            memory::poke(address, 1u32 << bit);
+            check(address, 1 << bit)?;
+        }
+    }
+
+    for address in addresses() {
+        memory::poke(address, address as u32);
+    }
+    for address in addresses() {
+        check(address, address as u32)?;
+    }
+    Ok(())
+}
+
+fn wait_ms(ms: u32, stop: impl Fn(KeyEvent) -> bool) -> bool {
+    let start = timer::ticks();
+    let duration = timer::ms_to_ticks(ms);
+    while timer::ticks().wrapping_sub(start) < duration {
+        while let Some(event) = keyboard::read_event() {
+            match event {
+                KeyEvent::SwitchScreen(id) => {
+                    switch_screen(id);
+                }
+                event if stop(event) => return false,
+                _ => {}
+            }
+        }
+        task::yield_now();
+        idle::idle();
+    }
+    true
+}
+
+fn read_file(name: &str) -> Option<&'static [u8]> {
+    match ramfs::find(name) {
+        Some(fd) => Some(ramfs::read(fd)),
+        None => initrd::fs().and_then(|fs| fs.open(name)),
+    }
+}
+
+fn script_file() -> Option<usize> {
+    unsafe { SCRIPT_FILE }
+}
+
+fn script_sink(bytes: &[u8]) {
+    let fd = match script_file() {
+        Some(fd) => fd,
+        None => return,
+    };
+
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if byte == 0x08 {
+            ramfs::append(fd, &bytes[start..i]);
+            let size = ramfs::size(fd);
+            if size > 0 {
+                ramfs::truncate(fd, size - 1);
+            }
+            start = i + 1;
+        }
+    }
+    ramfs::append(fd, &bytes[start..]);
+}
+
+fn parse_address(arg: Option<&str>) -> Option<usize> {
+    match strtol(arg?, 16)? {
+        address if (0..=u32::MAX as i64).contains(&address) => Some(address as usize),
+        _ => None,
+    }
+}
+
+fn parse_port(arg: Option<&str>) -> Option<u16> {
+    match strtol(arg?, 16)? {
+        port if (0..=u16::MAX as i64).contains(&port) => Some(port as u16),
+        _ => None,
+    }
+}
+
+fn fits_in(value: i64, size: usize) -> bool {
+    let bits = size as u32 * 8;
+    bits >= i64::BITS || (-(1i64 << (bits - 1))..1i64 << bits).contains(&value)
+}
+
+fn parse_access_size(arg: Option<&str>) -> Option<usize> {
+    match arg {
+        None => Some(1),
+        Some(arg) => match strtoul(arg, 10)? {
+            size @ (1 | 2 | 4 | 8) => Some(size as usize),
+            _ => None,
+        },
+    }
+}
+
+fn expand_status(dest: &mut [u8], line: &str, status: i32) -> usize {
+    let mut status_text = FixedBuffer::<12>::new();
+    let _ = write!(status_text, "{}", status);
+
+    let mut len = 0;
+    let mut parts = line.split("$?");
+    if let Some(first) = parts.next() {
+        len = copy_truncated(dest, len, first);
+    }
+    for part in parts {
+        len = copy_truncated(dest, len, status_text.as_str());
+        len = copy_truncated(dest, len, part);
+    }
+    len
+}
+
+fn copy_truncated(dest: &mut [u8], offset: usize, s: &str) -> usize {
+    let count = core::cmp::min(s.len(), dest.len().saturating_sub(offset));
+    dest[offset..offset + count].copy_from_slice(&s.as_bytes()[..count]);
+    offset + count
+}
+
+fn split_command(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    match line.find(char::is_whitespace) {
+        Some(pos) => (&line[..pos], line[pos..].trim()),
+        None => (line, ""),
+    }
+}
+
+fn split_keyword<'a>(line: &'a str, keyword: &str) -> Option<(&'a str, &'a str)> {
+    let mut word_start = None;
+    let ends = line
+        .char_indices()
+        .chain(core::iter::once((line.len(), ' ')));
+    for (index, c) in ends {
+        if !c.is_whitespace() {
+            word_start.get_or_insert(index);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            if &line[start..index] == keyword {
+                return Some((line[..start].trim(), line[index..].trim()));
+            }
+        }
+    }
+    None
+}
+
+fn status_result(status: i32) -> CommandResult {
+    if status == STATUS_SUCCESS {
+        Ok(())
+    } else {
+        Err(ShellError::status(status))
+    }
+}
+
+fn print_error(command: &str, message: impl fmt::Display) {
+    speaker::play(speaker::ERROR_BUZZ, speaker::PlayMode::Replace);
+    theme::apply(Role::Error);
+    crate::printkln!("{}: {}", command, message);
+    reset_color();
+}
+
+pub fn run() -> ! {
+    unsafe { (*core::ptr::addr_of_mut!(SHELL)).run() }
+}
@@ -0,0 +1,155 @@
+use crate::io::{inb, outb};
+use crate::sync::without_interrupts;
+use crate::timer::{self, PIT_COMMAND, PIT_FREQUENCY};
+use core::ptr::addr_of_mut;
+
+const PIT_CHANNEL2: u16 = 0x42;
+const PIT_MODE_SQUARE_WAVE_CHANNEL2: u8 = 0xB6;
+const SPEAKER_PORT: u16 = 0x61;
+const SPEAKER_GATE: u8 = 0x03;
+
+const MAX_QUEUED_PATTERNS: usize = 4;
+
+pub const MIN_FREQUENCY: u32 = 20;
+pub const MAX_FREQUENCY: u32 = 20_000;
+
+pub type Note = (u32, u32); // (frequency in Hz, duration in ticks), 0 Hz is a rest
+
+pub const BOOT_CHIME: &[Note] = &[(523, 8), (659, 8), (784, 16)];
+pub const ERROR_BUZZ: &[Note] = &[(110, 12), (0, 4), (110, 12)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    Queue,
+    Replace,
+}
+
+pub struct Player {
+    queue: [&'static [Note]; MAX_QUEUED_PATTERNS],
+    queue_start: usize,
+    queue_len: usize,
+    note: usize,
+    remaining: u32,
+}
+
+static mut PLAYER: Player = Player::new();
+
+impl Player {
+    pub const fn new() -> Player {
+        Player {
+            queue: [&[]; MAX_QUEUED_PATTERNS],
+            queue_start: 0,
+            queue_len: 0,
+            note: 0,
+            remaining: 0,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.queue_len > 0
+    }
+
+    pub fn clear(&mut self) {
+        self.queue_start = 0;
+        self.queue_len = 0;
+        self.note = 0;
+        self.remaining = 0;
+    }
+
+    pub fn push(&mut self, pattern: &'static [Note]) -> bool {
+        if self.queue_len >= MAX_QUEUED_PATTERNS {
+            return false;
+        }
+
+        let index = (self.queue_start + self.queue_len) % MAX_QUEUED_PATTERNS;
+        self.queue[index] = pattern;
+        self.queue_len += 1;
+        true
+    }
+
+    pub fn start_note(&mut self, sound: &mut impl FnMut(u32)) {
+        while self.queue_len > 0 {
+            let pattern = self.queue[self.queue_start];
+            if let Some(&(frequency, duration)) = pattern.get(self.note) {
+                sound(frequency);
+                self.remaining = core::cmp::max(duration, 1);
+                return;
+            }
+
+            self.queue_start = (self.queue_start + 1) % MAX_QUEUED_PATTERNS;
+            self.queue_len -= 1;
+            self.note = 0;
+        }
+
+        sound(0);
+    }
+
+    pub fn tick(&mut self, sound: &mut impl FnMut(u32)) {
+        if self.queue_len == 0 {
+            return;
+        }
+
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.note += 1;
+            self.start_note(sound);
+        }
+    }
+}
+
+fn player() -> &'static mut Player {
+    unsafe { &mut *addr_of_mut!(PLAYER) }
+}
+
+pub fn tone(frequency: u32) {
+    if frequency == 0 {
+        stop();
+        return;
+    }
+
+    let divisor = core::cmp::min(PIT_FREQUENCY / frequency, 0xFFFF);
+
+    unsafe {
+        outb(PIT_COMMAND, PIT_MODE_SQUARE_WAVE_CHANNEL2);
+        outb(PIT_CHANNEL2, (divisor & 0xFF) as u8);
+        outb(PIT_CHANNEL2, ((divisor >> 8) & 0xFF) as u8);
+        outb(SPEAKER_PORT, inb(SPEAKER_PORT) | SPEAKER_GATE);
+    }
+}
+
+pub fn stop() {
+    unsafe {
+        outb(SPEAKER_PORT, inb(SPEAKER_PORT) & !SPEAKER_GATE);
+    }
+}
+
+pub fn play(pattern: &'static [Note], mode: PlayMode) -> bool {
+    without_interrupts(|| {
+        let player = player();
+        if mode == PlayMode::Replace {
+            player.clear();
+        }
+
+        let was_idle = player.queue_len == 0;
+        let queued = player.push(pattern);
+        if queued && was_idle {
+            player.start_note(&mut tone);
+        }
+        queued
+    })
+}
+
+pub fn beep(frequency: u32, ms: u32) {
+    without_interrupts(|| player().clear());
+    tone(frequency.clamp(MIN_FREQUENCY, MAX_FREQUENCY));
+    timer::sleep_ms(ms);
+    stop();
+}
+
+pub fn is_playing() -> bool {
+    player().is_playing()
+}
+
+pub fn on_tick() {
+    player().tick(&mut tone);
+}
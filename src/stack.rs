@@ -1,5 +1,5 @@
-use crate::printk::{print, print_dec, print_hex_padded, println, reset_color, set_color};
-use crate::vga::Color;
+use crate::printk::{print, print_dec, print_hex_padded, println, reset_color};
+use crate::theme::{self, Role};
 use core::arch::asm;
 
 extern "C" {
@@ -42,7 +42,7 @@ pub fn get_stack_used() -> u32 {
 }
 
 fn print_stack_header() {
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     println("         KERNEL STACK DUMP             ");
     println("========================================");
@@ -50,19 +50,19 @@ fn print_stack_header() {
 }
 
 fn print_stack_boundaries() {
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Stack Top:    ");
     reset_color();
     print_hex_padded(get_stack_top());
     println("");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Stack Bottom: ");
     reset_color();
     print_hex_padded(get_stack_bottom());
     println("");
 
-    set_color(Color::Yellow, Color::Black);
+    theme::apply(Role::Label);
     print("Stack Size:   ");
     reset_color();
     print_dec(get_stack_size());
@@ -75,19 +75,19 @@ fn print_stack_pointers() {
     let esp = get_esp();
     let ebp = get_ebp();
 
-    set_color(Color::LightGreen, Color::Black);
+    theme::apply(Role::Success);
     print("ESP (Stack Pointer): ");
     reset_color();
     print_hex_padded(esp);
     println("");
 
-    set_color(Color::LightGreen, Color::Black);
+    theme::apply(Role::Success);
     print("EBP (Base Pointer):  ");
     reset_color();
     print_hex_padded(ebp);
     println("");
 
-    set_color(Color::LightGreen, Color::Black);
+    theme::apply(Role::Success);
     print("Stack Used:          ");
     reset_color();
     print_dec(get_stack_used());
@@ -100,10 +100,10 @@ pub fn print_stack_contents(num_entries: usize) {
     let esp = get_esp();
     let top = get_stack_top();
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("Stack Contents (from ESP upward):");
     println("----------------------------------");
-    set_color(Color::DarkGray, Color::Black);
+    theme::apply(Role::Muted);
     println("  Address    | Value      | Offset");
     reset_color();
 
@@ -113,7 +113,7 @@ pub fn print_stack_contents(num_entries: usize) {
     while addr < top && count < num_entries {
         let value = unsafe { *(addr as *const u32) };
 
-        set_color(Color::DarkGray, Color::Black);
+        theme::apply(Role::Muted);
         print("  ");
         print_hex_padded(addr);
         print(" | ");
@@ -122,12 +122,12 @@ pub fn print_stack_contents(num_entries: usize) {
         print_hex_padded(value);
         print(" | ");
 
-        set_color(Color::DarkGray, Color::Black);
+        theme::apply(Role::Muted);
         print("+");
         print_dec((addr - esp) as u32);
 
         if addr == esp {
-            set_color(Color::LightGreen, Color::Black);
+            theme::apply(Role::Success);
             print(" <- ESP");
         }
 
@@ -139,7 +139,7 @@ pub fn print_stack_contents(num_entries: usize) {
     }
 
     if addr >= top {
-        set_color(Color::Yellow, Color::Black);
+        theme::apply(Role::Label);
         println("  [End of stack reached]");
         reset_color();
     }
@@ -151,13 +151,13 @@ pub fn print_stack() {
     print_stack_pointers();
     print_stack_contents(16);
 
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("========================================");
     reset_color();
 }
 
 pub fn print_stack_summary() {
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     print("[Stack] ");
     reset_color();
 
@@ -173,7 +173,7 @@ pub fn print_stack_summary() {
 }
 
 pub fn print_stack_trace() {
-    set_color(Color::LightCyan, Color::Black);
+    theme::apply(Role::Info);
     println("Stack Trace (EBP chain):");
     println("------------------------");
     reset_color();
@@ -187,7 +187,7 @@ pub fn print_stack_trace() {
         let saved_ebp = unsafe { *(ebp as *const u32) };
         let return_addr = unsafe { *((ebp + 4) as *const u32) };
 
-        set_color(Color::Yellow, Color::Black);
+        theme::apply(Role::Label);
         print("  Frame ");
         print_dec(frame);
         reset_color();
@@ -206,7 +206,7 @@ pub fn print_stack_trace() {
     }
 
     if frame == 0 {
-        set_color(Color::DarkGray, Color::Black);
+        theme::apply(Role::Muted);
         println("  [No valid stack frames found]");
         reset_color();
     }
@@ -0,0 +1,202 @@
+use crate::kfs_lib::FixedBuffer;
+use crate::memory::{paging, PAGE_SIZE};
+use crate::{stack, task, usermode};
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub const CANARY: u32 = 0xDEADC0DE;
+const CANARY_WORDS: usize = 4;
+const CANARY_BYTES: usize = CANARY_WORDS * 4;
+pub const POISON: u8 = 0xA5;
+const BOOT_PAINT_MARGIN: usize = 512;
+
+pub const CHECK_TICKS: u32 = 50;
+
+static REPORTED: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    Boot,
+    Syscall,
+    Task(usize),
+}
+
+impl Owner {
+    fn slot(&self) -> usize {
+        match self {
+            Owner::Boot => 0,
+            Owner::Syscall => 1,
+            Owner::Task(id) => 2 + id,
+        }
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Owner::Boot => f.pad("boot"),
+            Owner::Syscall => f.pad("syscall"),
+            Owner::Task(id) => {
+                let mut name = FixedBuffer::<8>::new();
+                let _ = write!(name, "task {}", id);
+                f.pad(name.as_str())
+            }
+        }
+    }
+}
+
+#[repr(C, align(4096))]
+pub struct StackArea<const N: usize> {
+    guard: [u8; PAGE_SIZE],
+    stack: [u8; N],
+}
+
+impl<const N: usize> StackArea<N> {
+    pub const fn new() -> StackArea<N> {
+        StackArea {
+            guard: [0; PAGE_SIZE],
+            stack: [0; N],
+        }
+    }
+
+    pub fn bottom(&self) -> usize {
+        self.stack.as_ptr() as usize
+    }
+
+    pub fn top(&self) -> usize {
+        self.bottom() + N
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GuardedStack {
+    pub owner: Owner,
+    pub bottom: usize,
+    pub size: usize,
+}
+
+impl GuardedStack {
+    fn canary(&self) -> *mut u32 {
+        self.bottom as *mut u32
+    }
+
+    pub fn canary_intact(&self) -> bool {
+        (0..CANARY_WORDS)
+            .all(|word| unsafe { core::ptr::read_volatile(self.canary().add(word)) } == CANARY)
+    }
+
+    pub fn high_water(&self) -> usize {
+        let start = self.bottom + CANARY_BYTES;
+        let end = self.bottom + self.size;
+        let touched = (start..end)
+            .find(|&address| unsafe { core::ptr::read_volatile(address as *const u8) } != POISON)
+            .unwrap_or(end);
+        end - touched
+    }
+}
+
+pub fn paint(bottom: usize, size: usize, end: usize, owner: Owner) {
+    unsafe {
+        for word in 0..CANARY_WORDS {
+            core::ptr::write_volatile((bottom as *mut u32).add(word), CANARY);
+        }
+        let start = bottom + CANARY_BYTES;
+        let end = core::cmp::min(end, bottom + size);
+        if end > start {
+            core::ptr::write_bytes(start as *mut u8, POISON, end - start);
+        }
+    }
+    REPORTED.fetch_and(!(1 << owner.slot()), Ordering::SeqCst);
+}
+
+pub fn init() {
+    let bottom = stack::get_stack_bottom() as usize;
+    let end = (stack::get_esp() as usize).saturating_sub(BOOT_PAINT_MARGIN);
+    paint(bottom, stack::get_stack_size() as usize, end, Owner::Boot);
+
+    let (bottom, size) = usermode::syscall_stack();
+    paint(bottom, size, bottom + size, Owner::Syscall);
+}
+
+fn owners() -> impl Iterator<Item = Owner> {
+    [Owner::Boot, Owner::Syscall]
+        .into_iter()
+        .chain((0..task::MAX_TASKS).map(Owner::Task))
+}
+
+pub fn guard_page(owner: Owner) -> usize {
+    let bottom = match owner {
+        Owner::Boot => stack::get_stack_bottom() as usize,
+        Owner::Syscall => usermode::syscall_stack().0,
+        Owner::Task(id) => task::stack_bottom(id),
+    };
+    bottom - PAGE_SIZE
+}
+
+pub fn guard_owner(address: usize) -> Option<Owner> {
+    owners().find(|&owner| {
+        let guard = guard_page(owner);
+        (guard..guard + PAGE_SIZE).contains(&address)
+    })
+}
+
+pub fn install_guard_pages() {
+    for owner in owners() {
+        paging::unmap_page(guard_page(owner));
+    }
+}
+
+pub fn exhaust(depth: u32) -> u32 {
+    let frame = core::hint::black_box([depth; 16]);
+    if depth == u32::MAX {
+        return depth;
+    }
+    exhaust(depth + 1).wrapping_add(frame[15])
+}
+
+pub fn stacks() -> impl Iterator<Item = GuardedStack> {
+    let boot = GuardedStack {
+        owner: Owner::Boot,
+        bottom: stack::get_stack_bottom() as usize,
+        size: stack::get_stack_size() as usize,
+    };
+    let (bottom, size) = usermode::syscall_stack();
+    let syscall = GuardedStack {
+        owner: Owner::Syscall,
+        bottom,
+        size,
+    };
+    let tasks = (0..task::MAX_TASKS).filter_map(|id| {
+        task::stack_region(id).map(|(bottom, size)| GuardedStack {
+            owner: Owner::Task(id),
+            bottom,
+            size,
+        })
+    });
+
+    [boot, syscall].into_iter().chain(tasks)
+}
+
+pub fn check_all() -> usize {
+    let mut corrupted = 0;
+
+    for stack in stacks().filter(|stack| !stack.canary_intact()) {
+        corrupted += 1;
+        let bit = 1 << stack.owner.slot();
+        if REPORTED.fetch_or(bit, Ordering::SeqCst) & bit == 0 {
+            crate::pr_error!(
+                "stack guard: canary overwritten on {} stack at 0x{:08X}",
+                stack.owner,
+                stack.bottom
+            );
+        }
+    }
+
+    corrupted
+}
+
+pub fn on_tick(ticks: u32) {
+    if ticks.is_multiple_of(CHECK_TICKS) {
+        check_all();
+    }
+}
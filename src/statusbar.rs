@@ -0,0 +1,83 @@
+use crate::kfs_lib::FixedBuffer;
+use crate::printk;
+use crate::sync::IrqSpinlock;
+use crate::theme::{self, Role};
+use crate::vga::{VGA_BUFFER_HEIGHT, VGA_BUFFER_WIDTH};
+use crate::{keyboard, timer};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const STATUS_ROW: usize = VGA_BUFFER_HEIGHT - 1;
+const MAX_STATUS_TEXT: usize = 40;
+
+pub const REFRESH_TICKS: u32 = 10;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static STATUS_TEXT: IrqSpinlock<FixedBuffer<MAX_STATUS_TEXT>> =
+    IrqSpinlock::new(FixedBuffer::new());
+
+pub fn set_enabled(enabled: bool) {
+    if ENABLED.swap(enabled, Ordering::SeqCst) == enabled {
+        return;
+    }
+
+    if enabled {
+        printk::set_scroll_region(0, STATUS_ROW);
+        refresh();
+    } else {
+        printk::set_scroll_region(0, VGA_BUFFER_HEIGHT);
+        let blank = [b' '; VGA_BUFFER_WIDTH];
+        if let Ok(blank) = core::str::from_utf8(&blank) {
+            let (fg, bg) = theme::colors(Role::Normal);
+            printk::write_str_at(0, STATUS_ROW, blank, fg, bg);
+        }
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+pub fn set_status(text: &str) {
+    {
+        let mut status = STATUS_TEXT.lock();
+        status.clear();
+        let _ = status.write_str(text);
+    }
+    refresh();
+}
+
+pub fn refresh() {
+    if !is_enabled() {
+        return;
+    }
+
+    let mut line = FixedBuffer::<VGA_BUFFER_WIDTH>::new();
+    let seconds = timer::uptime_ms() / 1000;
+    let (caps_lock, num_lock) = keyboard::lock_state();
+
+    let _ = write!(
+        line,
+        " Screen {} | up {:02}:{:02}:{:02} | {} {} ",
+        printk::active_screen() + 1,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        if caps_lock { "CAPS" } else { "    " },
+        if num_lock { "NUM" } else { "   " },
+    );
+
+    let status = STATUS_TEXT.lock();
+    if !status.is_empty() {
+        let _ = write!(line, "| {}", status.as_str());
+    }
+    drop(status);
+
+    while line.len() < VGA_BUFFER_WIDTH {
+        let _ = line.write_str(" ");
+    }
+
+    let (fg, bg) = theme::colors(Role::Status);
+    printk::write_str_at(0, STATUS_ROW, line.as_str(), fg, bg);
+}
@@ -0,0 +1,110 @@
+use crate::idt;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub struct IrqSpinlock<T> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for IrqSpinlock<T> {}
+
+pub struct IrqSpinlockGuard<'a, T> {
+    lock: &'a IrqSpinlock<T>,
+    interrupts_were_enabled: bool,
+}
+
+impl<T> IrqSpinlock<T> {
+    pub const fn new(data: T) -> IrqSpinlock<T> {
+        IrqSpinlock {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> IrqSpinlockGuard<'_, T> {
+        let interrupts_were_enabled = idt::interrupts_enabled();
+        idt::disable_interrupts();
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        IrqSpinlockGuard {
+            lock: self,
+            interrupts_were_enabled,
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<IrqSpinlockGuard<'_, T>> {
+        let interrupts_were_enabled = idt::interrupts_enabled();
+        idt::disable_interrupts();
+
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            if interrupts_were_enabled {
+                idt::enable_interrupts();
+            }
+            return None;
+        }
+
+        Some(IrqSpinlockGuard {
+            lock: self,
+            interrupts_were_enabled,
+        })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    pub unsafe fn force_unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<T> Deref for IrqSpinlockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for IrqSpinlockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for IrqSpinlockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        if self.interrupts_were_enabled {
+            idt::enable_interrupts();
+        }
+    }
+}
+
+pub fn without_interrupts<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let interrupts_were_enabled = idt::interrupts_enabled();
+    idt::disable_interrupts();
+
+    let result = f();
+
+    if interrupts_were_enabled {
+        idt::enable_interrupts();
+    }
+    result
+}
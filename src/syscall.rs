@@ -0,0 +1,275 @@
+use crate::gdt::selectors;
+use crate::keyboard::{self, KeyEvent};
+use crate::memory::paging;
+use crate::printk::{print, print_bytes};
+use crate::{task, timer, usermode};
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+pub const VECTOR: u8 = 0x80;
+
+pub const SYS_READ: u32 = 0;
+pub const SYS_WRITE: u32 = 1;
+pub const SYS_SLEEP_MS: u32 = 35;
+pub const SYS_GETPID: u32 = 39;
+pub const SYS_EXIT: u32 = 60;
+const SYSCALL_COUNT: usize = 61;
+
+const STDIN: u32 = 0;
+const STDOUT: u32 = 1;
+const STDERR: u32 = 2;
+
+pub mod errno {
+    pub const EBADF: i32 = 9;
+    pub const EFAULT: i32 = 14;
+    pub const EINVAL: i32 = 22;
+    pub const ENOSYS: i32 = 38;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyscallFrame {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub ebp: u32,
+}
+
+impl SyscallFrame {
+    pub fn number(&self) -> u32 {
+        self.eax
+    }
+
+    pub fn args(&self) -> [u32; 6] {
+        [self.ebx, self.ecx, self.edx, self.esi, self.edi, self.ebp]
+    }
+}
+
+type Handler = fn(&SyscallFrame) -> i32;
+
+#[derive(Clone, Copy)]
+struct Syscall {
+    name: &'static str,
+    handler: Handler,
+}
+
+static TABLE: [Option<Syscall>; SYSCALL_COUNT] = {
+    let mut table = [None; SYSCALL_COUNT];
+    table[SYS_READ as usize] = Some(Syscall {
+        name: "read",
+        handler: sys_read,
+    });
+    table[SYS_WRITE as usize] = Some(Syscall {
+        name: "write",
+        handler: sys_write,
+    });
+    table[SYS_SLEEP_MS as usize] = Some(Syscall {
+        name: "sleep_ms",
+        handler: sys_sleep_ms,
+    });
+    table[SYS_GETPID as usize] = Some(Syscall {
+        name: "getpid",
+        handler: sys_getpid,
+    });
+    table[SYS_EXIT as usize] = Some(Syscall {
+        name: "exit",
+        handler: sys_exit,
+    });
+    table
+};
+
+static TRACE: AtomicBool = AtomicBool::new(false);
+static CALLS: AtomicU32 = AtomicU32::new(0);
+static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+static EXIT_STATUS: AtomicU32 = AtomicU32::new(0);
+
+global_asm!(
+    ".global syscall_entry",
+    "syscall_entry:",
+    "push ebp",
+    "push edi",
+    "push esi",
+    "push edx",
+    "push ecx",
+    "push ebx",
+    "push eax",
+    "mov dx, {kernel_data}",
+    "mov ds, dx",
+    "mov es, dx",
+    "cld",
+    "sti",
+    "mov ebx, esp",
+    "and esp, -16",
+    "sub esp, 12",
+    "push ebx",
+    "call {dispatch}",
+    "mov esp, ebx",
+    "cli",
+    "mov dx, {user_data}",
+    "mov ds, dx",
+    "mov es, dx",
+    "pop eax",
+    "pop ebx",
+    "pop ecx",
+    "pop edx",
+    "pop esi",
+    "pop edi",
+    "pop ebp",
+    "iretd",
+    dispatch = sym syscall_dispatch,
+    kernel_data = const selectors::KERNEL_DATA,
+    user_data = const selectors::USER_DATA | 3,
+);
+
+extern "C" {
+    fn syscall_entry();
+}
+
+pub fn entry_address() -> u32 {
+    syscall_entry as *const () as u32
+}
+
+pub fn set_trace(enabled: bool) -> bool {
+    TRACE.swap(enabled, Ordering::SeqCst)
+}
+
+pub fn is_tracing() -> bool {
+    TRACE.load(Ordering::SeqCst)
+}
+
+pub fn reset_count() {
+    CALLS.store(0, Ordering::SeqCst);
+}
+
+pub fn count() -> u32 {
+    CALLS.load(Ordering::SeqCst)
+}
+
+pub fn name(number: u32) -> Option<&'static str> {
+    TABLE
+        .get(number as usize)
+        .copied()
+        .flatten()
+        .map(|syscall| syscall.name)
+}
+
+pub fn dispatch(frame: &mut SyscallFrame) {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+
+    let syscall = TABLE.get(frame.number() as usize).copied().flatten();
+    let result = match syscall {
+        Some(syscall) => (syscall.handler)(frame),
+        None => -errno::ENOSYS,
+    };
+
+    if is_tracing() {
+        let [a, b, c, ..] = frame.args();
+        crate::klog!(
+            "syscall {}({:#x}, {:#x}, {:#x}) = {}",
+            syscall.map_or("unknown", |syscall| syscall.name),
+            a,
+            b,
+            c,
+            result
+        );
+    }
+    frame.eax = result as u32;
+}
+
+extern "C" fn syscall_dispatch(frame: &mut SyscallFrame) {
+    dispatch(frame);
+    if EXIT_REQUESTED.swap(false, Ordering::SeqCst) {
+        usermode::exit(EXIT_STATUS.load(Ordering::SeqCst));
+    }
+}
+
+fn user_buffer(address: u32, len: u32, write: bool) -> Result<&'static mut [u8], i32> {
+    let (address, len) = (address as usize, len as usize);
+    if len > 0 && !paging::is_user_range(address, len, write) {
+        return Err(-errno::EFAULT);
+    }
+    Ok(unsafe { core::slice::from_raw_parts_mut(address as *mut u8, len) })
+}
+
+fn sys_read(frame: &SyscallFrame) -> i32 {
+    let [fd, address, len, ..] = frame.args();
+    if fd != STDIN {
+        return -errno::EBADF;
+    }
+    if len > i32::MAX as u32 {
+        return -errno::EINVAL;
+    }
+    let buffer = match user_buffer(address, len, true) {
+        Ok(buffer) => buffer,
+        Err(error) => return error,
+    };
+
+    let mut count = 0;
+    while count < buffer.len() {
+        let byte = match keyboard::wait_for_event() {
+            KeyEvent::Char(c) if c.is_ascii() => c as u8,
+            KeyEvent::Tab => b'\t',
+            KeyEvent::Enter => b'\n',
+            KeyEvent::Backspace => {
+                if count > 0 {
+                    count -= 1;
+                    print("\x08");
+                }
+                continue;
+            }
+            KeyEvent::Ctrl('d') => break,
+            event => match event.control_code() {
+                Some(code) => {
+                    print_bytes(&[b'^', code + b'@']);
+                    buffer[count] = code;
+                    count += 1;
+                    continue;
+                }
+                None => continue,
+            },
+        };
+
+        print_bytes(&[byte]);
+        buffer[count] = byte;
+        count += 1;
+        if byte == b'\n' {
+            break;
+        }
+    }
+    count as i32
+}
+
+fn sys_write(frame: &SyscallFrame) -> i32 {
+    let [fd, address, len, ..] = frame.args();
+    if fd != STDOUT && fd != STDERR {
+        return -errno::EBADF;
+    }
+    if len > i32::MAX as u32 {
+        return -errno::EINVAL;
+    }
+    match user_buffer(address, len, false) {
+        Ok(bytes) => {
+            print_bytes(bytes);
+            len as i32
+        }
+        Err(error) => error,
+    }
+}
+
+fn sys_sleep_ms(frame: &SyscallFrame) -> i32 {
+    timer::sleep_ms(frame.ebx);
+    0
+}
+
+fn sys_getpid(_frame: &SyscallFrame) -> i32 {
+    task::current() as i32
+}
+
+fn sys_exit(frame: &SyscallFrame) -> i32 {
+    EXIT_STATUS.store(frame.ebx, Ordering::SeqCst);
+    EXIT_REQUESTED.store(true, Ordering::SeqCst);
+    0
+}
@@ -0,0 +1,138 @@
+const BLOCK_SIZE: usize = 512;
+
+const NAME_OFFSET: usize = 0;
+const NAME_LEN: usize = 100;
+const SIZE_OFFSET: usize = 124;
+const SIZE_LEN: usize = 12;
+const TYPE_OFFSET: usize = 156;
+const MAGIC_OFFSET: usize = 257;
+const PREFIX_OFFSET: usize = 345;
+const PREFIX_LEN: usize = 155;
+
+const MAX_PATH_LEN: usize = PREFIX_LEN + 1 + NAME_LEN;
+
+const TYPE_REGULAR: u8 = b'0';
+const TYPE_REGULAR_OLD: u8 = 0;
+
+pub struct TarEntry {
+    path: [u8; MAX_PATH_LEN],
+    path_len: usize,
+    pub data: &'static [u8],
+}
+
+impl TarEntry {
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.path[..self.path_len]).unwrap_or("")
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TarFs {
+    data: &'static [u8],
+}
+
+pub struct TarIter {
+    data: &'static [u8],
+    offset: usize,
+}
+
+impl TarFs {
+    pub fn new(data: &'static [u8]) -> TarFs {
+        TarFs { data }
+    }
+
+    pub fn list(&self) -> TarIter {
+        TarIter {
+            data: self.data,
+            offset: 0,
+        }
+    }
+
+    pub fn open(&self, path: &str) -> Option<&'static [u8]> {
+        let path = path.trim_start_matches('/');
+        self.list()
+            .find(|entry| entry.name() == path)
+            .map(|entry| entry.data)
+    }
+}
+
+impl Iterator for TarIter {
+    type Item = TarEntry;
+
+    fn next(&mut self) -> Option<TarEntry> {
+        loop {
+            let header = self.data.get(self.offset..self.offset + BLOCK_SIZE)?;
+            if header[NAME_OFFSET] == 0 || &header[MAGIC_OFFSET..MAGIC_OFFSET + 5] != b"ustar" {
+                return None;
+            }
+
+            let size = parse_octal(&header[SIZE_OFFSET..SIZE_OFFSET + SIZE_LEN])?;
+            let data_start = self.offset + BLOCK_SIZE;
+            let data = self.data.get(data_start..data_start.checked_add(size)?)?;
+            self.offset = data_start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+
+            match header[TYPE_OFFSET] {
+                TYPE_REGULAR | TYPE_REGULAR_OLD => {}
+                _ => continue,
+            }
+
+            let mut entry = TarEntry {
+                path: [0; MAX_PATH_LEN],
+                path_len: 0,
+                data,
+            };
+
+            let prefix = field_str(&header[PREFIX_OFFSET..PREFIX_OFFSET + PREFIX_LEN]);
+            let name = field_str(&header[NAME_OFFSET..NAME_OFFSET + NAME_LEN]);
+            if !prefix.is_empty() {
+                entry.push(prefix);
+                entry.push(b"/");
+            }
+            entry.push(name);
+            entry.normalize();
+
+            return Some(entry);
+        }
+    }
+}
+
+impl TarEntry {
+    fn push(&mut self, bytes: &[u8]) {
+        let end = self.path_len + bytes.len();
+        self.path[self.path_len..end].copy_from_slice(bytes);
+        self.path_len = end;
+    }
+
+    fn normalize(&mut self) {
+        let mut skip = 0;
+        while self.path[skip..self.path_len].starts_with(b"./") {
+            skip += 2;
+        }
+        self.path.copy_within(skip..self.path_len, 0);
+        self.path_len -= skip;
+    }
+}
+
+fn field_str(field: &[u8]) -> &[u8] {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    &field[..len]
+}
+
+fn parse_octal(field: &[u8]) -> Option<usize> {
+    let mut value: usize = 0;
+    let mut seen_digit = false;
+
+    for &byte in field {
+        match byte {
+            b'0'..=b'7' => {
+                value = value.checked_mul(8)?.checked_add((byte - b'0') as usize)?;
+                seen_digit = true;
+            }
+            b' ' if !seen_digit => {}
+            0 | b' ' => break,
+            _ => return None,
+        }
+    }
+
+    Some(value)
+}
@@ -0,0 +1,172 @@
+use crate::panic;
+use crate::stack_guard::{self, Owner, StackArea};
+use core::arch::global_asm;
+use core::ptr::{addr_of, addr_of_mut};
+
+pub const MAX_TASKS: usize = 8;
+const TASK_STACK_SIZE: usize = 4096;
+
+const KERNEL_TASK: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Free,
+    Ready,
+}
+
+#[derive(Clone, Copy)]
+struct Task {
+    state: TaskState,
+    esp: u32,
+    entry: Option<fn()>,
+}
+
+impl Task {
+    const fn empty() -> Task {
+        Task {
+            state: TaskState::Free,
+            esp: 0,
+            entry: None,
+        }
+    }
+}
+
+static mut TASKS: [Task; MAX_TASKS] = {
+    let mut tasks = [Task::empty(); MAX_TASKS];
+    tasks[KERNEL_TASK].state = TaskState::Ready;
+    tasks
+};
+
+static mut STACKS: [StackArea<TASK_STACK_SIZE>; MAX_TASKS] =
+    [const { StackArea::new() }; MAX_TASKS];
+
+static mut CURRENT: usize = KERNEL_TASK;
+
+global_asm!(
+    ".global task_switch",
+    "task_switch:",
+    "push ebp",
+    "push ebx",
+    "push esi",
+    "push edi",
+    "mov eax, [esp + 20]",
+    "mov [eax], esp",
+    "mov esp, [esp + 24]",
+    "pop edi",
+    "pop esi",
+    "pop ebx",
+    "pop ebp",
+    "ret",
+);
+
+extern "C" {
+    fn task_switch(old_esp: *mut u32, new_esp: u32);
+}
+
+fn tasks() -> &'static mut [Task; MAX_TASKS] {
+    unsafe { &mut *addr_of_mut!(TASKS) }
+}
+
+pub fn current() -> usize {
+    unsafe { CURRENT }
+}
+
+pub fn spawn(entry: fn()) -> Option<usize> {
+    let tasks = tasks();
+    let id = tasks
+        .iter()
+        .position(|task| task.state == TaskState::Free)?;
+
+    let bottom = stack_bottom(id);
+    stack_guard::paint(
+        bottom,
+        TASK_STACK_SIZE,
+        bottom + TASK_STACK_SIZE,
+        Owner::Task(id),
+    );
+    let top = bottom as u32 + TASK_STACK_SIZE as u32;
+    let frame = [0, 0, 0, 0, task_trampoline as *const () as u32, 0]; // edi, esi, ebx, ebp, eip, return
+    let esp = top - core::mem::size_of_val(&frame) as u32;
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(frame.as_ptr(), esp as *mut u32, frame.len());
+    }
+
+    tasks[id] = Task {
+        state: TaskState::Ready,
+        esp,
+        entry: Some(entry),
+    };
+    Some(id)
+}
+
+pub fn yield_now() {
+    let previous = current();
+    let next = match next_ready(previous) {
+        Some(next) => next,
+        None => return,
+    };
+
+    switch_to(previous, next);
+}
+
+pub fn exit() -> ! {
+    let id = current();
+    if id == KERNEL_TASK {
+        panic::panic("The kernel task cannot exit");
+    }
+
+    tasks()[id] = Task::empty();
+
+    let next = next_ready(id).unwrap_or(KERNEL_TASK);
+    switch_to(id, next);
+    unreachable!()
+}
+
+pub fn stack_owner(address: usize) -> Option<usize> {
+    (0..MAX_TASKS).position(|id| {
+        let start = stack_bottom(id);
+        (start..start + TASK_STACK_SIZE).contains(&address)
+    })
+}
+
+pub fn stack_bottom(id: usize) -> usize {
+    unsafe { (*addr_of!(STACKS))[id].bottom() }
+}
+
+pub fn stack_region(id: usize) -> Option<(usize, usize)> {
+    if id == KERNEL_TASK || tasks().get(id)?.state == TaskState::Free {
+        return None;
+    }
+    Some((stack_bottom(id), TASK_STACK_SIZE))
+}
+
+pub fn count() -> usize {
+    tasks()
+        .iter()
+        .filter(|task| task.state != TaskState::Free)
+        .count()
+}
+
+fn next_ready(from: usize) -> Option<usize> {
+    let tasks = tasks();
+    (1..MAX_TASKS)
+        .map(|offset| (from + offset) % MAX_TASKS)
+        .find(|&id| tasks[id].state == TaskState::Ready)
+}
+
+fn switch_to(previous: usize, next: usize) {
+    let tasks = tasks();
+    let next_esp = tasks[next].esp;
+    unsafe {
+        CURRENT = next;
+        task_switch(&mut tasks[previous].esp, next_esp);
+    }
+}
+
+extern "C" fn task_trampoline() -> ! {
+    if let Some(entry) = tasks()[current()].entry {
+        entry();
+    }
+    exit()
+}
@@ -0,0 +1,154 @@
+use crate::printk;
+use crate::sync::IrqSpinlock;
+use crate::vga::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Normal,
+    Prompt,
+    Error,
+    Warning,
+    Info,
+    Success,
+    Label,
+    Muted,
+    Panic,
+    Status,
+}
+
+pub const ROLES: [Role; 10] = [
+    Role::Normal,
+    Role::Prompt,
+    Role::Error,
+    Role::Warning,
+    Role::Info,
+    Role::Success,
+    Role::Label,
+    Role::Muted,
+    Role::Panic,
+    Role::Status,
+];
+
+impl Role {
+    pub fn from_name(name: &str) -> Option<Role> {
+        ROLES
+            .iter()
+            .find(|role| role.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Normal => "normal",
+            Role::Prompt => "prompt",
+            Role::Error => "error",
+            Role::Warning => "warning",
+            Role::Info => "info",
+            Role::Success => "success",
+            Role::Label => "label",
+            Role::Muted => "muted",
+            Role::Panic => "panic",
+            Role::Status => "status",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    colors: [(Color, Color); ROLES.len()],
+}
+
+impl Theme {
+    pub fn get(&self, role: Role) -> (Color, Color) {
+        self.colors[role as usize]
+    }
+
+    pub fn set(&mut self, role: Role, fg: Color, bg: Color) {
+        self.colors[role as usize] = (fg, bg);
+    }
+}
+
+pub const DARK: Theme = Theme {
+    colors: [
+        (Color::White, Color::Black),
+        (Color::LightGreen, Color::Black),
+        (Color::LightRed, Color::Black),
+        (Color::Yellow, Color::Black),
+        (Color::LightCyan, Color::Black),
+        (Color::LightGreen, Color::Black),
+        (Color::Yellow, Color::Black),
+        (Color::DarkGray, Color::Black),
+        (Color::White, Color::Red),
+        (Color::Black, Color::LightGray),
+    ],
+};
+
+pub const LIGHT: Theme = Theme {
+    colors: [
+        (Color::Black, Color::LightGray),
+        (Color::Blue, Color::LightGray),
+        (Color::Red, Color::LightGray),
+        (Color::Brown, Color::LightGray),
+        (Color::Cyan, Color::LightGray),
+        (Color::Green, Color::LightGray),
+        (Color::Magenta, Color::LightGray),
+        (Color::DarkGray, Color::LightGray),
+        (Color::White, Color::Red),
+        (Color::LightGray, Color::Blue),
+    ],
+};
+
+pub const AMBER: Theme = Theme {
+    colors: [
+        (Color::Yellow, Color::Black),
+        (Color::Yellow, Color::Black),
+        (Color::LightRed, Color::Black),
+        (Color::Yellow, Color::Black),
+        (Color::Brown, Color::Black),
+        (Color::Yellow, Color::Black),
+        (Color::Brown, Color::Black),
+        (Color::Brown, Color::Black),
+        (Color::Black, Color::Brown),
+        (Color::Black, Color::Brown),
+    ],
+};
+
+pub const PRESETS: [(&str, Theme); 3] = [("dark", DARK), ("light", LIGHT), ("amber", AMBER)];
+
+static THEME: IrqSpinlock<Theme> = IrqSpinlock::new(DARK);
+
+pub fn preset(name: &str) -> Option<Theme> {
+    PRESETS
+        .iter()
+        .find(|(preset_name, _)| preset_name.eq_ignore_ascii_case(name))
+        .map(|&(_, theme)| theme)
+}
+
+pub fn current() -> Theme {
+    *THEME.lock()
+}
+
+pub fn load(theme: Theme) {
+    *THEME.lock() = theme;
+}
+
+pub fn colors(role: Role) -> (Color, Color) {
+    THEME.lock().get(role)
+}
+
+pub fn set_colors(role: Role, fg: Color, bg: Color) {
+    THEME.lock().set(role, fg, bg);
+}
+
+pub fn apply(role: Role) {
+    let (fg, bg) = colors(role);
+    printk::set_color(fg, bg);
+}
+
+pub fn is_locked() -> bool {
+    THEME.is_locked()
+}
+
+pub unsafe fn force_unlock() {
+    THEME.force_unlock();
+}
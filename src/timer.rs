@@ -0,0 +1,66 @@
+use crate::io::outb;
+use crate::{pic, speaker, stack_guard, statusbar};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+pub const PIT_FREQUENCY: u32 = 1_193_182;
+const PIT_CHANNEL0: u16 = 0x40;
+pub const PIT_COMMAND: u16 = 0x43;
+const PIT_MODE_RATE_GENERATOR: u8 = 0x36;
+
+pub const TICK_RATE_HZ: u32 = 100;
+
+static TICKS: AtomicU32 = AtomicU32::new(0);
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+pub fn init() {
+    let divisor = PIT_FREQUENCY / TICK_RATE_HZ;
+
+    unsafe {
+        outb(PIT_COMMAND, PIT_MODE_RATE_GENERATOR);
+        outb(PIT_CHANNEL0, (divisor & 0xFF) as u8);
+        outb(PIT_CHANNEL0, ((divisor >> 8) & 0xFF) as u8);
+    }
+
+    pic::unmask_irq(pic::IRQ_TIMER);
+    INITIALIZED.store(true, Ordering::SeqCst);
+}
+
+pub fn on_tick() {
+    let ticks = TICKS.fetch_add(1, Ordering::SeqCst) + 1;
+    speaker::on_tick();
+    stack_guard::on_tick(ticks);
+
+    if ticks.is_multiple_of(statusbar::REFRESH_TICKS) {
+        statusbar::refresh();
+    }
+}
+
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
+}
+
+pub fn ticks() -> u32 {
+    TICKS.load(Ordering::SeqCst)
+}
+
+pub fn ms_to_ticks(ms: u32) -> u32 {
+    (ms as u64 * TICK_RATE_HZ as u64).div_ceil(1000) as u32
+}
+
+pub fn uptime_ms() -> u64 {
+    ticks() as u64 * 1000 / TICK_RATE_HZ as u64
+}
+
+pub fn sleep_ms(ms: u32) {
+    if !is_initialized() {
+        return;
+    }
+
+    let start = ticks();
+    let duration = ms_to_ticks(ms);
+
+    while ticks().wrapping_sub(start) < duration {
+        crate::task::yield_now();
+        crate::idle::idle();
+    }
+}
@@ -0,0 +1,204 @@
+use crate::gdt::{self, selectors};
+use crate::kfs_lib::memzero_frame;
+use crate::memory::{paging, pmm, PAGE_SIZE, USER_SPACE_END};
+use crate::stack_guard::StackArea;
+use crate::syscall;
+use core::arch::global_asm;
+use core::ptr::addr_of;
+
+const USER_BASE: usize = 0x40000000; // 1GB
+const USER_PAGES: usize = 2;
+const USER_STACK_TOP: usize = USER_BASE + USER_PAGES * PAGE_SIZE;
+pub const STACK_PAGES: usize = 4;
+pub const STACK_BOTTOM: usize = USER_SPACE_END - STACK_PAGES * PAGE_SIZE;
+const USER_EFLAGS: u32 = 0x202; // IF | reserved bit 1
+const SYSCALL_STACK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserModeError {
+    OutOfMemory,
+    AddressInUse,
+}
+
+impl UserModeError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            UserModeError::OutOfMemory => "not enough memory for the user program",
+            UserModeError::AddressInUse => "user address range already mapped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UserRun {
+    pub status: u32,
+    pub syscalls: u32,
+}
+
+static mut SYSCALL_STACK: StackArea<SYSCALL_STACK_SIZE> = StackArea::new();
+static mut KERNEL_ESP: u32 = 0;
+
+global_asm!(
+    ".global usermode_enter",
+    "usermode_enter:",
+    "push ebp",
+    "push ebx",
+    "push esi",
+    "push edi",
+    "pushfd",
+    "mov eax, [esp + 24]",
+    "mov ecx, [esp + 28]",
+    "mov dword ptr [{kernel_esp}], esp",
+    "cli",
+    "mov dx, {user_data}",
+    "mov ds, dx",
+    "mov es, dx",
+    "mov fs, dx",
+    "mov gs, dx",
+    "push {user_stack}",
+    "push ecx",
+    "push {eflags}",
+    "push {user_code}",
+    "push eax",
+    "iretd",
+    "",
+    ".global usermode_return",
+    "usermode_return:",
+    "mov eax, [esp + 4]",
+    "mov esp, dword ptr [{kernel_esp}]",
+    "mov dx, {kernel_data}",
+    "mov ds, dx",
+    "mov es, dx",
+    "mov fs, dx",
+    "mov gs, dx",
+    "popfd",
+    "pop edi",
+    "pop esi",
+    "pop ebx",
+    "pop ebp",
+    "ret",
+    "",
+    ".pushsection .rodata",
+    ".global usermode_program_start",
+    "usermode_program_start:",
+    "mov eax, {sys_write}",
+    "mov ebx, 1",
+    "mov edx, offset usermode_message_len",
+    "call 2f",
+    "usermode_message:",
+    ".ascii \"Hello from ring 3!\\n\"",
+    "usermode_message_end:",
+    "2:",
+    "pop ecx",
+    "int {vector}",
+    "mov eax, {sys_exit}",
+    "xor ebx, ebx",
+    "int {vector}",
+    "3:",
+    "jmp 3b",
+    ".set usermode_message_len, usermode_message_end - usermode_message",
+    ".global usermode_program_end",
+    "usermode_program_end:",
+    ".popsection",
+    kernel_esp = sym KERNEL_ESP,
+    kernel_data = const selectors::KERNEL_DATA,
+    user_code = const selectors::USER_CODE | 3,
+    user_data = const selectors::USER_DATA | 3,
+    user_stack = const selectors::USER_STACK | 3,
+    eflags = const USER_EFLAGS,
+    vector = const syscall::VECTOR,
+    sys_write = const syscall::SYS_WRITE,
+    sys_exit = const syscall::SYS_EXIT,
+);
+
+extern "C" {
+    fn usermode_enter(entry: u32, user_stack: u32) -> u32;
+    fn usermode_return(status: u32) -> !;
+    static usermode_program_start: u8;
+    static usermode_program_end: u8;
+}
+
+pub fn syscall_stack() -> (usize, usize) {
+    (
+        unsafe { (*addr_of!(SYSCALL_STACK)).bottom() },
+        SYSCALL_STACK_SIZE,
+    )
+}
+
+pub fn exit(status: u32) -> ! {
+    unsafe { usermode_return(status) }
+}
+
+fn program() -> &'static [u8] {
+    unsafe {
+        let start = addr_of!(usermode_program_start);
+        let end = addr_of!(usermode_program_end);
+        core::slice::from_raw_parts(start, end as usize - start as usize)
+    }
+}
+
+pub fn unmap_user_pages(base: usize, count: usize) {
+    for index in 0..count {
+        let address = base + index * PAGE_SIZE;
+        if let Some(frame) = paging::get_physical_address(address) {
+            paging::unmap_page(address);
+            pmm::free_frame(frame);
+        }
+    }
+}
+
+pub fn map_user_pages(base: usize, count: usize) -> Result<(), UserModeError> {
+    if (0..count).any(|index| paging::is_mapped(base + index * PAGE_SIZE)) {
+        return Err(UserModeError::AddressInUse);
+    }
+
+    for index in 0..count {
+        let address = base + index * PAGE_SIZE;
+        let mapped = match pmm::alloc_frame() {
+            Some(frame) if paging::map_page(address, frame, paging::USER_PAGE_FLAGS) => true,
+            Some(frame) => {
+                pmm::free_frame(frame);
+                false
+            }
+            None => false,
+        };
+        if !mapped {
+            unmap_user_pages(base, index);
+            return Err(UserModeError::OutOfMemory);
+        }
+        memzero_frame(address);
+    }
+    Ok(())
+}
+
+fn enter(entry: usize, stack_top: usize) -> UserRun {
+    let kernel_stack = unsafe { (*addr_of!(SYSCALL_STACK)).top() } as u32;
+    gdt::set_kernel_stack(kernel_stack);
+
+    syscall::reset_count();
+    let status = unsafe { usermode_enter(entry as u32, stack_top as u32) };
+    UserRun {
+        status,
+        syscalls: syscall::count(),
+    }
+}
+
+pub fn run() -> Result<UserRun, UserModeError> {
+    map_user_pages(USER_BASE, USER_PAGES)?;
+
+    let code = program();
+    unsafe {
+        core::ptr::copy_nonoverlapping(code.as_ptr(), USER_BASE as *mut u8, code.len());
+    }
+
+    let run = enter(USER_BASE, USER_STACK_TOP);
+    unmap_user_pages(USER_BASE, USER_PAGES);
+    Ok(run)
+}
+
+pub fn exec(entry: usize) -> Result<UserRun, UserModeError> {
+    map_user_pages(STACK_BOTTOM, STACK_PAGES)?;
+    let run = enter(entry, USER_SPACE_END);
+    unmap_user_pages(STACK_BOTTOM, STACK_PAGES);
+    Ok(run)
+}
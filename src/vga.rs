@@ -1,6 +1,66 @@
-const VGA_BUFFER_HEIGHT: usize = 25;
-const VGA_BUFFER_WIDTH: usize = 80;
-const VGA_BUFFER_ADDR: usize = 0xB8000;
+use crate::fbcon;
+use crate::io::{inb, outb};
+use crate::kfs_lib;
+use crate::theme::{self, Role};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub const VGA_BUFFER_HEIGHT: usize = 25;
+pub const VGA_BUFFER_WIDTH: usize = 80;
+pub const VGA_BUFFER_ADDR: usize = 0xB8000;
+
+pub const MAX_SCREENS: usize = 4;
+
+static BLINK_MODE: AtomicBool = AtomicBool::new(true);
+
+const SCROLLBACK_LINES: usize = 500;
+pub const HISTORY_LINES: usize = SCROLLBACK_LINES + VGA_BUFFER_HEIGHT;
+
+const ESCAPE: u8 = 0x1b;
+const TAB_WIDTH: usize = 8;
+const MAX_ESCAPE_PARAMS: usize = 4;
+
+const ANSI_COLORS: [Color; 8] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Brown,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightGray,
+];
+
+const ANSI_BRIGHT_COLORS: [Color; 8] = [
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::Yellow,
+    Color::LightBlue,
+    Color::Pink,
+    Color::LightCyan,
+    Color::White,
+];
+
+const CRTC_INDEX_PORT: u16 = 0x3D4;
+const CRTC_DATA_PORT: u16 = 0x3D5;
+
+const INPUT_STATUS_PORT: u16 = 0x3DA;
+const ATTRIBUTE_WRITE_PORT: u16 = 0x3C0;
+const ATTRIBUTE_READ_PORT: u16 = 0x3C1;
+const ATTRIBUTE_MODE_CONTROL: u8 = 0x10;
+const ATTRIBUTE_PALETTE_ENABLE: u8 = 0x20;
+const MODE_CONTROL_BLINK: u8 = 1 << 3;
+
+const BLINK_BIT: u8 = 1 << 7;
+
+pub const BOX_DRAWING_FIRST: u8 = 0xB3;
+pub const BOX_DRAWING_LAST: u8 = 0xDA;
+const BOX_TOP_LEFT: u8 = 0xC9;
+const BOX_TOP_RIGHT: u8 = 0xBB;
+const BOX_BOTTOM_LEFT: u8 = 0xC8;
+const BOX_BOTTOM_RIGHT: u8 = 0xBC;
+const BOX_HORIZONTAL: u8 = 0xCD;
+const BOX_VERTICAL: u8 = 0xBA;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,6 +84,57 @@ pub enum Color {
     White = 15,
 }
 
+const COLOR_NAMES: [(Color, &str); 16] = [
+    (Color::Black, "black"),
+    (Color::Blue, "blue"),
+    (Color::Green, "green"),
+    (Color::Cyan, "cyan"),
+    (Color::Red, "red"),
+    (Color::Magenta, "magenta"),
+    (Color::Brown, "brown"),
+    (Color::LightGray, "lightgray"),
+    (Color::DarkGray, "darkgray"),
+    (Color::LightBlue, "lightblue"),
+    (Color::LightGreen, "lightgreen"),
+    (Color::LightCyan, "lightcyan"),
+    (Color::LightRed, "lightred"),
+    (Color::Pink, "pink"),
+    (Color::Yellow, "yellow"),
+    (Color::White, "white"),
+];
+
+impl Color {
+    pub fn from_name(name: &str) -> Option<Color> {
+        COLOR_NAMES
+            .iter()
+            .find(|(_, color_name)| color_name.eq_ignore_ascii_case(name))
+            .map(|&(color, _)| color)
+    }
+
+    pub fn name(self) -> &'static str {
+        COLOR_NAMES[self as usize].1
+    }
+
+    pub fn from_index(index: u8) -> Option<Color> {
+        COLOR_NAMES.get(index as usize).map(|&(color, _)| color)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+}
+
+impl CursorShape {
+    pub fn scanlines(self) -> (u8, u8) {
+        match self {
+            CursorShape::Block => (0, 15),
+            CursorShape::Underline => (13, 15),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct ColorCode(u8);
@@ -32,6 +143,34 @@ impl ColorCode {
     pub const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    pub const fn with_blink(foreground: Color, background: Color, blink: bool) -> ColorCode {
+        let code = ColorCode::new(foreground, background).0;
+        if blink {
+            ColorCode(code | BLINK_BIT)
+        } else {
+            ColorCode(code & !BLINK_BIT)
+        }
+    }
+
+    pub const fn as_u8(self) -> u8 {
+        self.0
+    }
+
+    fn with_foreground(self, foreground: Color) -> ColorCode {
+        ColorCode((self.0 & 0xF0) | foreground as u8)
+    }
+
+    fn with_background(self, background: Color) -> ColorCode {
+        ColorCode((self.0 & 0x0F) | (background as u8) << 4)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    Normal,
+    Escape,
+    Csi,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -46,30 +185,278 @@ struct Buffer {
     chars: [[ScreenChar; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT],
 }
 
+struct BufferView(&'static mut Buffer);
+
+impl BufferView {
+    fn get(&self, row: usize, col: usize) -> Option<&ScreenChar> {
+        self.0.chars.get(row)?.get(col)
+    }
+
+    fn get_mut(&mut self, row: usize, col: usize) -> Option<&mut ScreenChar> {
+        self.0.chars.get_mut(row)?.get_mut(col)
+    }
+
+    fn set(&mut self, row: usize, col: usize, screen_char: ScreenChar) -> bool {
+        match self.get_mut(row, col) {
+            Some(cell) => {
+                *cell = screen_char;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn row(&self, row: usize) -> Option<&[ScreenChar; VGA_BUFFER_WIDTH]> {
+        self.0.chars.get(row)
+    }
+
+    fn set_row(&mut self, row: usize, line: &[ScreenChar; VGA_BUFFER_WIDTH]) -> bool {
+        match self.0.chars.get_mut(row) {
+            Some(target) => {
+                *target = *line;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn rows(&self, first: usize, last: usize) -> Option<&[[ScreenChar; VGA_BUFFER_WIDTH]]> {
+        self.0.chars.get(first..last)
+    }
+
+    fn rows_mut(
+        &mut self,
+        first: usize,
+        last: usize,
+    ) -> Option<&mut [[ScreenChar; VGA_BUFFER_WIDTH]]> {
+        self.0.chars.get_mut(first..last)
+    }
+
+    fn cells_mut(&mut self) -> &mut [ScreenChar] {
+        self.0.chars.as_flattened_mut()
+    }
+}
+
+struct Scrollback {
+    lines: [[ScreenChar; VGA_BUFFER_WIDTH]; SCROLLBACK_LINES],
+    start: usize,
+    len: usize,
+    live: [[ScreenChar; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT],
+}
+
+const EMPTY_CHAR: ScreenChar = ScreenChar {
+    ascii_char: 0,
+    color_code: ColorCode(0),
+};
+
+/// A copy of a screen's cells, cursor and color, used to put a screen
+/// back the way it was after borrowing it.
+pub struct SavedScreen {
+    chars: [[ScreenChar; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT],
+    cursor: (usize, usize),
+    color_code: ColorCode,
+}
+
+impl SavedScreen {
+    pub const fn new() -> SavedScreen {
+        SavedScreen {
+            chars: [[EMPTY_CHAR; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT],
+            cursor: (0, 0),
+            color_code: ColorCode(0),
+        }
+    }
+}
+
+static mut SCREEN_BUFFERS: [Buffer; MAX_SCREENS] = [const {
+    Buffer {
+        chars: [[EMPTY_CHAR; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT],
+    }
+}; MAX_SCREENS];
+
+static mut SCROLLBACKS: [Scrollback; MAX_SCREENS] = [const {
+    Scrollback {
+        lines: [[EMPTY_CHAR; VGA_BUFFER_WIDTH]; SCROLLBACK_LINES],
+        start: 0,
+        len: 0,
+        live: [[EMPTY_CHAR; VGA_BUFFER_WIDTH]; VGA_BUFFER_HEIGHT],
+    }
+}; MAX_SCREENS];
+
+impl Scrollback {
+    fn push(&mut self, line: &[ScreenChar; VGA_BUFFER_WIDTH]) {
+        let index = (self.start + self.len) % SCROLLBACK_LINES;
+        self.lines[index] = *line;
+        if self.len < SCROLLBACK_LINES {
+            self.len += 1;
+        } else {
+            self.start = (self.start + 1) % SCROLLBACK_LINES;
+        }
+    }
+
+    fn line(&self, index: usize) -> &[ScreenChar; VGA_BUFFER_WIDTH] {
+        &self.lines[(self.start + index) % SCROLLBACK_LINES]
+    }
+}
+
 pub struct Writer {
+    id: usize,
     column_position: usize,
     row_position: usize,
+    saved_cursor: Option<(usize, usize)>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    view_offset: usize,
     color_code: ColorCode,
-    buffer: &'static mut Buffer,
+    escape_state: EscapeState,
+    escape_params: [u16; MAX_ESCAPE_PARAMS],
+    escape_param_count: usize,
+    buffer: BufferView,
+    scrollback: &'static mut Scrollback,
+    visible: bool,
+    dirty: u32,
 }
 
 impl Writer {
-    pub fn new() -> Writer {
+    pub fn new(id: usize) -> Writer {
         Writer {
+            id,
             column_position: 0,
             row_position: 0,
+            saved_cursor: None,
+            scroll_top: 0,
+            scroll_bottom: VGA_BUFFER_HEIGHT,
+            view_offset: 0,
             color_code: ColorCode::new(Color::White, Color::Black),
-            buffer: unsafe { &mut *(VGA_BUFFER_ADDR as *mut Buffer) },
+            escape_state: EscapeState::Normal,
+            escape_params: [0; MAX_ESCAPE_PARAMS],
+            escape_param_count: 0,
+            buffer: BufferView(unsafe { &mut (*core::ptr::addr_of_mut!(SCREEN_BUFFERS))[id] }),
+            scrollback: unsafe { &mut (*core::ptr::addr_of_mut!(SCROLLBACKS))[id] },
+            visible: false,
+            dirty: 0,
         }
     }
 
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn show(&mut self) {
+        if self.visible {
+            return;
+        }
+
+        self.visible = true;
+        self.mark_dirty(0, VGA_BUFFER_HEIGHT);
+        self.flush();
+    }
+
+    fn hide(&mut self) {
+        if !self.visible {
+            return;
+        }
+
+        self.scroll_to_bottom();
+        self.flush();
+        self.visible = false;
+    }
+
+    fn adopt_screen(&mut self) {
+        if fbcon::is_active() {
+            self.visible = true;
+            self.clear_all();
+            return;
+        }
+
+        let vga = VGA_BUFFER_ADDR as *const ScreenChar;
+        for (index, cell) in self.buffer.cells_mut().iter_mut().enumerate() {
+            *cell = unsafe { core::ptr::read_volatile(vga.add(index)) };
+        }
+        self.visible = true;
+    }
+
+    fn mark_dirty(&mut self, first: usize, last: usize) {
+        for row in first..last {
+            self.dirty |= 1 << row;
+        }
+    }
+
+    pub fn repaint(&mut self) {
+        self.mark_dirty(0, VGA_BUFFER_HEIGHT);
+        self.flush();
+    }
+
+    pub fn flush(&mut self) {
+        if !self.visible {
+            self.dirty = 0;
+            return;
+        }
+
+        let vga = VGA_BUFFER_ADDR as *mut [ScreenChar; VGA_BUFFER_WIDTH];
+        while self.dirty != 0 {
+            let row = self.dirty.trailing_zeros() as usize;
+            self.dirty &= self.dirty - 1;
+            let Some(line) = self.buffer.row(row) else {
+                continue;
+            };
+            if fbcon::is_active() {
+                for (column, cell) in line.iter().enumerate() {
+                    fbcon::draw_cell(column, row, cell.ascii_char, cell.color_code.0);
+                }
+            } else {
+                unsafe {
+                    core::ptr::write_volatile(vga.add(row), *line);
+                }
+            }
+        }
+        self.update_cursor();
+    }
+
     pub fn set_color(&mut self, color_code: ColorCode) {
         self.color_code = color_code;
     }
 
+    pub fn get_color(&self) -> ColorCode {
+        self.color_code
+    }
+
+    pub fn set_blink(&mut self, blink: bool) {
+        if blink {
+            self.color_code = ColorCode(self.color_code.0 | BLINK_BIT);
+        } else {
+            self.color_code = ColorCode(self.color_code.0 & !BLINK_BIT);
+        }
+    }
+
     pub fn write_byte(&mut self, byte: u8) {
+        self.put_byte(byte);
+    }
+
+    fn put_byte(&mut self, byte: u8) {
+        if self.escape_state != EscapeState::Normal {
+            self.handle_escape(byte);
+            return;
+        }
+
+        if self.view_offset > 0 {
+            self.scroll_to_bottom();
+        }
+
         match byte {
+            ESCAPE => self.escape_state = EscapeState::Escape,
             b'\n' => self.new_line(),
+            b'\r' => self.column_position = 0,
+            b'\t' => {
+                let stop = (self.column_position / TAB_WIDTH + 1) * TAB_WIDTH;
+                while self.column_position < stop.min(VGA_BUFFER_WIDTH) {
+                    self.put_byte(b' ');
+                }
+            }
+            0x08 => self.backspace(),
             byte => {
                 if self.column_position >= VGA_BUFFER_WIDTH {
                     self.new_line();
@@ -78,10 +465,15 @@ impl Writer {
                 let row = self.row_position;
                 let col = self.column_position;
 
-                self.buffer.chars[row][col] = ScreenChar {
-                    ascii_char: byte,
-                    color_code: self.color_code,
-                };
+                self.buffer.set(
+                    row,
+                    col,
+                    ScreenChar {
+                        ascii_char: byte,
+                        color_code: self.color_code,
+                    },
+                );
+                self.mark_dirty(row, row + 1);
                 self.column_position += 1;
             }
         }
@@ -90,14 +482,223 @@ impl Writer {
     pub fn write_string(&mut self, s: &str) {
         for byte in s.bytes() {
             match byte {
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                _ => self.write_byte(0xfe),
+                0x20..=0x7e | b'\n' | b'\r' | b'\t' | 0x08 | ESCAPE => self.put_byte(byte),
+                _ => self.put_byte(0xfe),
+            }
+        }
+    }
+
+    pub fn write_char_at(&mut self, x: usize, y: usize, byte: u8, color_code: ColorCode) -> bool {
+        if x >= VGA_BUFFER_WIDTH || y >= VGA_BUFFER_HEIGHT {
+            return false;
+        }
+
+        let glyph = match byte {
+            0x20..=0x7e | BOX_DRAWING_FIRST..=BOX_DRAWING_LAST => byte,
+            _ => 0xfe,
+        };
+        self.put_glyph_at(x, y, glyph, color_code)
+    }
+
+    pub fn read_char_at(&self, x: usize, y: usize) -> Option<u8> {
+        self.buffer.get(y, x).map(|cell| cell.ascii_char)
+    }
+
+    pub fn invert_at(&mut self, x: usize, y: usize) -> bool {
+        let Some(cell) = self.buffer.get_mut(y, x) else {
+            return false;
+        };
+        cell.color_code = ColorCode(cell.color_code.0.rotate_left(4));
+        self.mark_dirty(y, y + 1);
+        true
+    }
+
+    fn put_glyph_at(&mut self, x: usize, y: usize, glyph: u8, color_code: ColorCode) -> bool {
+        let screen_char = ScreenChar {
+            ascii_char: glyph,
+            color_code,
+        };
+        if !self.buffer.set(y, x, screen_char) {
+            return false;
+        }
+        self.mark_dirty(y, y + 1);
+        true
+    }
+
+    pub fn fill_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        glyph: u8,
+        color_code: ColorCode,
+    ) {
+        let right = core::cmp::min(x.saturating_add(width), VGA_BUFFER_WIDTH);
+        let bottom = core::cmp::min(y.saturating_add(height), VGA_BUFFER_HEIGHT);
+
+        for row in y..bottom {
+            for column in x..right {
+                self.put_glyph_at(column, row, glyph, color_code);
+            }
+        }
+    }
+
+    pub fn draw_box(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        color_code: ColorCode,
+    ) {
+        if width < 2 || height < 2 {
+            return;
+        }
+
+        let right = x.saturating_add(width - 1);
+        let bottom = y.saturating_add(height - 1);
+
+        for column in x + 1..core::cmp::min(right, VGA_BUFFER_WIDTH) {
+            self.put_glyph_at(column, y, BOX_HORIZONTAL, color_code);
+            self.put_glyph_at(column, bottom, BOX_HORIZONTAL, color_code);
+        }
+        for row in y + 1..core::cmp::min(bottom, VGA_BUFFER_HEIGHT) {
+            self.put_glyph_at(x, row, BOX_VERTICAL, color_code);
+            self.put_glyph_at(right, row, BOX_VERTICAL, color_code);
+        }
+
+        self.put_glyph_at(x, y, BOX_TOP_LEFT, color_code);
+        self.put_glyph_at(right, y, BOX_TOP_RIGHT, color_code);
+        self.put_glyph_at(x, bottom, BOX_BOTTOM_LEFT, color_code);
+        self.put_glyph_at(right, bottom, BOX_BOTTOM_RIGHT, color_code);
+    }
+
+    pub fn write_str_at(&mut self, x: usize, y: usize, s: &str, color_code: ColorCode) -> usize {
+        let mut written = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            if !self.write_char_at(x + i, y, byte, color_code) {
+                break;
+            }
+            written += 1;
+        }
+        written
+    }
+
+    fn handle_escape(&mut self, byte: u8) {
+        match (self.escape_state, byte) {
+            (EscapeState::Escape, b'[') => {
+                self.escape_state = EscapeState::Csi;
+                self.escape_params = [0; MAX_ESCAPE_PARAMS];
+                self.escape_param_count = 0;
             }
+            (EscapeState::Escape, _) => self.escape_state = EscapeState::Normal,
+            (EscapeState::Csi, b'0'..=b'9') => {
+                if self.escape_param_count == 0 {
+                    self.escape_param_count = 1;
+                }
+                if self.escape_param_count <= MAX_ESCAPE_PARAMS {
+                    let param = &mut self.escape_params[self.escape_param_count - 1];
+                    *param = param
+                        .saturating_mul(10)
+                        .saturating_add((byte - b'0') as u16);
+                }
+            }
+            (EscapeState::Csi, b';') => {
+                if self.escape_param_count == 0 {
+                    self.escape_param_count = 1;
+                }
+                self.escape_param_count += 1;
+            }
+            (EscapeState::Csi, 0x40..=0x7e) => {
+                self.escape_state = EscapeState::Normal;
+                self.apply_escape(byte);
+            }
+            (EscapeState::Csi, 0x20..=0x3f) => {}
+            _ => self.escape_state = EscapeState::Normal,
         }
     }
 
+    fn apply_escape(&mut self, command: u8) {
+        let count = core::cmp::min(self.escape_param_count, MAX_ESCAPE_PARAMS);
+        let params = self.escape_params;
+
+        match command {
+            b'm' => {
+                if count == 0 {
+                    self.apply_sgr(0);
+                }
+                for &param in &params[..count] {
+                    self.apply_sgr(param);
+                }
+            }
+            b'J' if count > 0 && params[0] == 2 => self.clear_screen(),
+            b's' => self.save_cursor(),
+            b'u' => {
+                self.restore_cursor();
+            }
+            b'K' if count == 0 || params[0] == 0 => self.clear_to_line_end(),
+            b'K' if params[0] == 2 => self.clear_row(self.row_position),
+            b'H' | b'f' => {
+                let row = (params[0] as usize).saturating_sub(1);
+                let col = (params[1] as usize).saturating_sub(1);
+                self.row_position = core::cmp::min(self.scroll_top + row, self.scroll_bottom - 1);
+                self.column_position = core::cmp::min(col, VGA_BUFFER_WIDTH - 1);
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_sgr(&mut self, param: u16) {
+        let param = param as usize;
+        self.color_code = match param {
+            0 => {
+                let (fg, bg) = theme::colors(Role::Normal);
+                ColorCode::new(fg, bg)
+            }
+            5 => ColorCode(self.color_code.0 | BLINK_BIT),
+            25 => ColorCode(self.color_code.0 & !BLINK_BIT),
+            30..=37 => self.color_code.with_foreground(ANSI_COLORS[param - 30]),
+            39 => self
+                .color_code
+                .with_foreground(theme::colors(Role::Normal).0),
+            40..=47 => self.color_code.with_background(ANSI_COLORS[param - 40]),
+            49 => self
+                .color_code
+                .with_background(theme::colors(Role::Normal).1),
+            90..=97 => self
+                .color_code
+                .with_foreground(ANSI_BRIGHT_COLORS[param - 90]),
+            100..=107 => self
+                .color_code
+                .with_background(ANSI_BRIGHT_COLORS[param - 100]),
+            _ => self.color_code,
+        };
+    }
+
+    fn backspace(&mut self) {
+        if self.column_position > 0 {
+            self.column_position -= 1;
+        } else if self.row_position > self.scroll_top {
+            self.row_position -= 1;
+            self.column_position = VGA_BUFFER_WIDTH - 1;
+        } else {
+            return;
+        }
+
+        self.buffer.set(
+            self.row_position,
+            self.column_position,
+            ScreenChar {
+                ascii_char: b' ',
+                color_code: self.color_code,
+            },
+        );
+        self.mark_dirty(self.row_position, self.row_position + 1);
+    }
+
     fn new_line(&mut self) {
-        if self.row_position < VGA_BUFFER_HEIGHT - 1 {
+        if self.row_position < self.scroll_bottom - 1 {
             self.row_position += 1;
         } else {
             self.scroll();
@@ -106,30 +707,470 @@ impl Writer {
     }
 
     fn scroll(&mut self) {
-        for row in 1..VGA_BUFFER_HEIGHT {
-            for col in 0..VGA_BUFFER_WIDTH {
-                self.buffer.chars[row - 1][col] = self.buffer.chars[row][col];
-            }
+        if let Some(line) = self.buffer.row(self.scroll_top) {
+            self.scrollback.push(line);
+        }
+        if let Some(rows) = self.buffer.rows_mut(self.scroll_top, self.scroll_bottom) {
+            rows.copy_within(1.., 0);
+        }
+        if self.visible {
+            fbcon::scroll(self.scroll_top, self.scroll_bottom);
+        }
+        self.mark_dirty(self.scroll_top, self.scroll_bottom);
+        self.clear_row(self.scroll_bottom - 1);
+    }
+
+    fn clear_rows(&mut self, first: usize, last: usize) {
+        let blank = ScreenChar {
+            ascii_char: b' ',
+            color_code: self.color_code,
+        };
+        if let Some(rows) = self.buffer.rows_mut(first, last) {
+            rows.as_flattened_mut().fill(blank);
         }
-        self.clear_row(VGA_BUFFER_HEIGHT - 1);
+        self.mark_dirty(first, last);
     }
 
     fn clear_row(&mut self, row: usize) {
+        self.clear_rows(row, row + 1);
+    }
+
+    pub fn clear_to_line_end(&mut self) {
         let blank = ScreenChar {
             ascii_char: b' ',
             color_code: self.color_code,
         };
-        for col in 0..VGA_BUFFER_WIDTH {
-            self.buffer.chars[row][col] = blank;
+        if let Some(row) = self
+            .buffer
+            .rows_mut(self.row_position, self.row_position + 1)
+        {
+            row.as_flattened_mut()[self.column_position..].fill(blank);
         }
+        self.mark_dirty(self.row_position, self.row_position + 1);
+    }
+
+    pub fn clear_line(&mut self) {
+        self.clear_row(self.row_position);
+        self.column_position = 0;
     }
 
     pub fn clear_screen(&mut self) {
-        for row in 0..VGA_BUFFER_HEIGHT {
-            self.clear_row(row);
-        }
+        self.scroll_to_bottom();
+        self.clear_rows(self.scroll_top, self.scroll_bottom);
         self.column_position = 0;
-        self.row_position = 0;
+        self.row_position = self.scroll_top;
+    }
+
+    pub fn clear_all(&mut self) {
+        self.scroll_to_bottom();
+        self.clear_rows(0, VGA_BUFFER_HEIGHT);
+        self.column_position = 0;
+        self.row_position = self.scroll_top;
+    }
+
+    pub fn scroll_region(&self) -> (usize, usize) {
+        (self.scroll_top, self.scroll_bottom)
+    }
+
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) -> bool {
+        if top >= bottom || bottom > VGA_BUFFER_HEIGHT {
+            return false;
+        }
+
+        self.scroll_to_bottom();
+
+        while self.row_position >= bottom && self.row_position > self.scroll_top {
+            self.scroll();
+            self.row_position -= 1;
+        }
+
+        self.scroll_top = top;
+        self.scroll_bottom = bottom;
+
+        if self.row_position < top || self.row_position >= bottom {
+            self.row_position = top;
+            self.column_position = 0;
+        }
+        true
+    }
+
+    pub fn get_scroll_region(&self) -> (usize, usize) {
+        (self.scroll_top, self.scroll_bottom)
+    }
+
+    pub fn page_height(&self) -> usize {
+        self.scroll_bottom - self.scroll_top
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        let offset = core::cmp::min(self.view_offset + lines, self.scrollback.len);
+        if offset == self.view_offset {
+            return;
+        }
+
+        if self.view_offset == 0 {
+            if let Some(rows) = self.buffer.rows(self.scroll_top, self.scroll_bottom) {
+                self.scrollback.live[self.scroll_top..self.scroll_bottom].copy_from_slice(rows);
+            }
+        }
+
+        self.view_offset = offset;
+        self.draw_view();
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        if self.view_offset == 0 {
+            return;
+        }
+
+        self.view_offset = self.view_offset.saturating_sub(lines);
+        self.draw_view();
+    }
+
+    pub fn save(&mut self, saved: &mut SavedScreen) {
+        self.scroll_to_bottom();
+        if let Some(rows) = self.buffer.rows(0, VGA_BUFFER_HEIGHT) {
+            saved.chars.copy_from_slice(rows);
+        }
+        saved.cursor = (self.column_position, self.row_position);
+        saved.color_code = self.color_code;
+    }
+
+    pub fn restore(&mut self, saved: &SavedScreen) {
+        self.scroll_to_bottom();
+        if let Some(rows) = self.buffer.rows_mut(0, VGA_BUFFER_HEIGHT) {
+            rows.copy_from_slice(&saved.chars);
+        }
+        (self.column_position, self.row_position) = saved.cursor;
+        self.color_code = saved.color_code;
+        self.mark_dirty(0, VGA_BUFFER_HEIGHT);
+        self.update_cursor();
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll_down(self.view_offset);
+    }
+
+    fn draw_view(&mut self) {
+        let history_len = self.scrollback.len;
+        let first = history_len - self.view_offset;
+
+        for row in self.scroll_top..self.scroll_bottom {
+            let index = first + (row - self.scroll_top);
+            let line = if index < history_len {
+                self.scrollback.line(index)
+            } else {
+                &self.scrollback.live[self.scroll_top + index - history_len]
+            };
+            self.buffer.set_row(row, line);
+        }
+        self.mark_dirty(self.scroll_top, self.scroll_bottom);
+    }
+
+    pub fn export_history(&self, out: &mut dyn FnMut(&str)) {
+        let live = if self.view_offset > 0 {
+            &self.scrollback.live[self.scroll_top..self.scroll_bottom]
+        } else {
+            self.buffer
+                .rows(self.scroll_top, self.scroll_bottom)
+                .unwrap_or(&[])
+        };
+        let lines = (0..self.scrollback.len)
+            .map(|index| self.scrollback.line(index))
+            .chain(live.iter());
+
+        let mut text = [0u8; VGA_BUFFER_WIDTH];
+        let mut blank_lines = 0;
+        for line in lines {
+            let len = line_text(line, &mut text);
+            if len == 0 {
+                blank_lines += 1;
+                continue;
+            }
+            for _ in 0..blank_lines {
+                out("");
+            }
+            blank_lines = 0;
+            out(core::str::from_utf8(&text[..len]).unwrap_or(""));
+        }
+    }
+
+    pub fn set_cursor_position(&mut self, x: usize, y: usize) -> bool {
+        if x > VGA_BUFFER_WIDTH || y < self.scroll_top || y >= self.scroll_bottom {
+            return false;
+        }
+
+        self.scroll_to_bottom();
+        self.column_position = x;
+        self.row_position = y;
+        true
+    }
+
+    pub fn get_cursor_position(&self) -> (usize, usize) {
+        (self.column_position, self.row_position)
+    }
+
+    pub fn save_cursor(&mut self) {
+        self.saved_cursor = Some((self.column_position, self.row_position));
+    }
+
+    pub fn restore_cursor(&mut self) -> bool {
+        match self.saved_cursor {
+            Some((x, y)) => self.set_cursor_position(x, y),
+            None => false,
+        }
+    }
+
+    pub fn at_line_start(&self) -> bool {
+        self.column_position == 0
+    }
+
+    fn update_cursor(&self) {
+        if !self.is_visible() {
+            return;
+        }
+
+        let col = if self.column_position >= VGA_BUFFER_WIDTH {
+            VGA_BUFFER_WIDTH - 1
+        } else {
+            self.column_position
+        };
+        if fbcon::is_active() {
+            fbcon::set_cursor(col, self.row_position);
+            return;
+        }
+
+        let pos = (self.row_position * VGA_BUFFER_WIDTH + col) as u16;
+
+        unsafe {
+            outb(CRTC_INDEX_PORT, 0x0F);
+            outb(CRTC_DATA_PORT, (pos & 0xFF) as u8);
+            outb(CRTC_INDEX_PORT, 0x0E);
+            outb(CRTC_DATA_PORT, (pos >> 8) as u8);
+        }
+    }
+}
+
+fn line_text(line: &[ScreenChar; VGA_BUFFER_WIDTH], text: &mut [u8; VGA_BUFFER_WIDTH]) -> usize {
+    let mut len = 0;
+    for (index, cell) in line.iter().enumerate() {
+        text[index] = match cell.ascii_char {
+            0 => b' ',
+            byte @ 0x20..=0x7e => byte,
+            _ => b'?',
+        };
+        if text[index] != b' ' {
+            len = index + 1;
+        }
+    }
+    len
+}
+
+pub struct RawWriter {
+    column: usize,
+    row: usize,
+    color_code: ColorCode,
+}
+
+impl RawWriter {
+    pub const fn new(row: usize, color_code: ColorCode) -> RawWriter {
+        RawWriter {
+            column: 0,
+            row,
+            color_code,
+        }
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn read_char_at(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= VGA_BUFFER_WIDTH || y >= VGA_BUFFER_HEIGHT {
+            return None;
+        }
+        if fbcon::is_active() {
+            return fbcon::cell(x, y).map(|cell| cell as u8);
+        }
+
+        let cell = (VGA_BUFFER_ADDR as *const ScreenChar).wrapping_add(y * VGA_BUFFER_WIDTH + x);
+        Some(unsafe { core::ptr::read_volatile(cell) }.ascii_char)
+    }
+
+    fn put(&self, row: usize, column: usize, screen_char: ScreenChar) {
+        if fbcon::is_active() {
+            fbcon::draw_cell(
+                column,
+                row,
+                screen_char.ascii_char,
+                screen_char.color_code.0,
+            );
+            return;
+        }
+
+        let cell =
+            (VGA_BUFFER_ADDR as *mut ScreenChar).wrapping_add(row * VGA_BUFFER_WIDTH + column);
+        unsafe {
+            core::ptr::write_volatile(cell, screen_char);
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.column = 0;
+        self.row = (self.row + 1) % VGA_BUFFER_HEIGHT;
+        let blank = ScreenChar {
+            ascii_char: b' ',
+            color_code: self.color_code,
+        };
+        for column in 0..VGA_BUFFER_WIDTH {
+            self.put(self.row, column, blank);
+        }
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        if byte == b'\n' {
+            self.new_line();
+            return;
+        }
+        if self.column >= VGA_BUFFER_WIDTH {
+            self.new_line();
+        }
+
+        let ascii_char = match byte {
+            0x20..=0x7e => byte,
+            _ => 0xfe,
+        };
+        let screen_char = ScreenChar {
+            ascii_char,
+            color_code: self.color_code,
+        };
+        self.put(self.row, self.column, screen_char);
+        self.column += 1;
+    }
+
+    pub fn write_string(&mut self, s: &str) {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+    }
+}
+
+pub struct Screens {
+    writers: [Writer; MAX_SCREENS],
+    active: usize,
+}
+
+impl Screens {
+    pub fn new() -> Screens {
+        let mut screens = Screens {
+            writers: core::array::from_fn(Writer::new),
+            active: 0,
+        };
+
+        for writer in screens.writers.iter_mut().skip(1) {
+            writer.clear_screen();
+        }
+        screens.writers[0].adopt_screen();
+        screens
+    }
+
+    pub fn active(&mut self) -> &mut Writer {
+        &mut self.writers[self.active]
+    }
+
+    pub fn active_id(&self) -> usize {
+        self.active
+    }
+
+    pub fn get(&mut self, id: usize) -> Option<&mut Writer> {
+        self.writers.get_mut(id)
+    }
+
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) -> bool {
+        self.writers
+            .iter_mut()
+            .all(|writer| writer.set_scroll_region(top, bottom))
+    }
+
+    pub fn switch_to(&mut self, id: usize) -> bool {
+        if id >= MAX_SCREENS {
+            return false;
+        }
+        if id == self.active {
+            return true;
+        }
+
+        self.writers[self.active].hide();
+        self.writers[id].show();
+        self.active = id;
+        true
+    }
+}
+
+pub fn enable_cursor(start_scanline: u8, end_scanline: u8) {
+    if fbcon::is_active() {
+        fbcon::set_cursor_visible(true);
+        return;
+    }
+
+    unsafe {
+        outb(CRTC_INDEX_PORT, 0x0A);
+        outb(
+            CRTC_DATA_PORT,
+            (inb(CRTC_DATA_PORT) & 0xC0) | start_scanline,
+        );
+        outb(CRTC_INDEX_PORT, 0x0B);
+        outb(CRTC_DATA_PORT, (inb(CRTC_DATA_PORT) & 0xE0) | end_scanline);
+    }
+}
+
+pub fn set_cursor_shape(shape: CursorShape) {
+    let (start, end) = shape.scanlines();
+    enable_cursor(start, end);
+}
+
+// Attribute bit 7 means either "blink" or "bright background", never both:
+// with blinking enabled only the 8 dark background colors are available,
+// with it disabled all 16 backgrounds work but nothing can blink.
+pub fn set_blink_mode(enabled: bool) {
+    unsafe {
+        inb(INPUT_STATUS_PORT);
+        outb(
+            ATTRIBUTE_WRITE_PORT,
+            ATTRIBUTE_MODE_CONTROL | ATTRIBUTE_PALETTE_ENABLE,
+        );
+        let mode = inb(ATTRIBUTE_READ_PORT);
+        let mode = if enabled {
+            mode | MODE_CONTROL_BLINK
+        } else {
+            mode & !MODE_CONTROL_BLINK
+        };
+        outb(ATTRIBUTE_WRITE_PORT, mode);
+    }
+    BLINK_MODE.store(enabled, Ordering::SeqCst);
+}
+
+pub fn blink_mode() -> bool {
+    BLINK_MODE.load(Ordering::SeqCst)
+}
+
+pub fn clamp_background(background: Color) -> Color {
+    if blink_mode() {
+        Color::from_index(background as u8 & 0x7).unwrap_or(Color::Black)
+    } else {
+        background
+    }
+}
+
+pub fn disable_cursor() {
+    if fbcon::is_active() {
+        fbcon::set_cursor_visible(false);
+        return;
+    }
+
+    unsafe {
+        outb(CRTC_INDEX_PORT, 0x0A);
+        outb(CRTC_DATA_PORT, 0x20);
     }
 }
 
@@ -170,8 +1211,8 @@ pub fn memcpy(dest: &mut [u8], src: &[u8], n: usize) {
     let len = if n < dest.len() { n } else { dest.len() };
     let len = if len < src.len() { len } else { src.len() };
 
-    for i in 0..len {
-        dest[i] = src[i];
+    unsafe {
+        kfs_lib::memcpy(dest.as_mut_ptr(), src.as_ptr(), len);
     }
 }
 
@@ -179,7 +1220,7 @@ pub fn memcpy(dest: &mut [u8], src: &[u8], n: usize) {
 pub fn memset(dest: &mut [u8], val: u8, n: usize) {
     let len = if n < dest.len() { n } else { dest.len() };
 
-    for i in 0..len {
-        dest[i] = val;
+    unsafe {
+        kfs_lib::memset(dest.as_mut_ptr(), val, len);
     }
 }
@@ -0,0 +1,141 @@
+use crate::idt::InterruptStackFrame;
+use crate::printk::{self, print, print_hex_padded, println, reset_color};
+use crate::theme::{self, Role};
+use crate::{keyboard, panic, stack, timer};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+
+pub const DEFAULT_TIMEOUT_SECS: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WatchdogMode {
+    Warn = 0,
+    Panic = 1,
+}
+
+impl WatchdogMode {
+    pub fn from_name(name: &str) -> Option<WatchdogMode> {
+        match name {
+            "warn" => Some(WatchdogMode::Warn),
+            "panic" => Some(WatchdogMode::Panic),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WatchdogMode::Warn => "warn",
+            WatchdogMode::Panic => "panic",
+        }
+    }
+}
+
+static LAST_PET: AtomicU32 = AtomicU32::new(0);
+static TIMEOUT_SECS: AtomicU32 = AtomicU32::new(DEFAULT_TIMEOUT_SECS);
+static MODE: AtomicU8 = AtomicU8::new(WatchdogMode::Warn as u8);
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+pub fn pet() {
+    LAST_PET.store(timer::ticks(), Ordering::SeqCst);
+    REPORTED.store(false, Ordering::SeqCst);
+}
+
+pub fn set_timeout(seconds: u32) {
+    TIMEOUT_SECS.store(seconds, Ordering::SeqCst);
+    pet();
+}
+
+pub fn timeout() -> u32 {
+    TIMEOUT_SECS.load(Ordering::SeqCst)
+}
+
+pub fn is_enabled() -> bool {
+    timeout() != 0
+}
+
+pub fn set_mode(mode: WatchdogMode) {
+    MODE.store(mode as u8, Ordering::SeqCst);
+}
+
+pub fn mode() -> WatchdogMode {
+    match MODE.load(Ordering::SeqCst) {
+        1 => WatchdogMode::Panic,
+        _ => WatchdogMode::Warn,
+    }
+}
+
+pub fn ms_since_pet() -> u64 {
+    let ticks = timer::ticks().wrapping_sub(LAST_PET.load(Ordering::SeqCst));
+    ticks as u64 * 1000 / timer::TICK_RATE_HZ as u64
+}
+
+pub fn on_tick(frame: &InterruptStackFrame) {
+    if !is_enabled() || REPORTED.load(Ordering::SeqCst) {
+        return;
+    }
+
+    if ms_since_pet() < timeout() as u64 * 1000 {
+        return;
+    }
+
+    REPORTED.store(true, Ordering::SeqCst);
+    report(frame);
+
+    if mode() == WatchdogMode::Panic {
+        panic::panic("Soft lockup detected");
+    }
+}
+
+fn report(frame: &InterruptStackFrame) {
+    let held = [
+        ("printk", printk::is_locked()),
+        ("keyboard", keyboard::is_locked()),
+        ("theme", theme::is_locked()),
+    ];
+
+    if held[0].1 {
+        unsafe {
+            printk::force_unlock();
+        }
+    }
+    if held[2].1 {
+        unsafe {
+            theme::force_unlock();
+        }
+    }
+
+    crate::klog!(
+        "watchdog: soft lockup, no pet for {}ms, EIP={:#010x}",
+        ms_since_pet(),
+        frame.eip
+    );
+
+    theme::apply(Role::Error);
+    crate::printk::set_blink(true);
+    crate::printkln!(
+        "\nSOFT LOCKUP: main loop stuck for {}s",
+        ms_since_pet() / 1000
+    );
+    reset_color();
+
+    print("  EIP=");
+    print_hex_padded(frame.eip);
+    print("  CS=");
+    print_hex_padded(frame.cs);
+    print("  EFLAGS=");
+    print_hex_padded(frame.eflags);
+    println("");
+
+    print("  Locks held:");
+    let mut any = false;
+    for (name, locked) in held {
+        if locked {
+            print(" ");
+            print(name);
+            any = true;
+        }
+    }
+    println(if any { "" } else { " none" });
+
+    stack::print_stack_trace();
+}